@@ -0,0 +1,271 @@
+use crate::battery::{Battery, BatteryError, BatteryState};
+use crate::types::{Duration, Energy, Power, TelemetryPoint};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AncillaryError {
+    #[error("Committed raise and lower power must not be negative or above the battery's max power.")]
+    InvalidCommittedPower,
+    #[error("Service prices must be finite and non-negative.")]
+    InvalidPrice,
+    #[error("Reserving energy for the commitment needs {required} kWh, more than the battery's capacity.")]
+    InfeasibleCommitment { required: f64 },
+    #[error("The activation signal has {actual} steps, expected {expected}.")]
+    LengthMismatch { expected: usize, actual: usize },
+    #[error("Activation on step {0} must have at least one sample, each between -1 and 1.")]
+    InvalidActivation(usize),
+    #[error("Simulating the service failed on step {1}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize),
+}
+
+/// Raise and lower power the battery is enabled for, and what it is paid for them.
+#[derive(Debug, Clone)]
+pub struct ServiceCommitment {
+    raise: Power,
+    lower: Power,
+    sustain: Duration,
+    raise_price: f64,
+    lower_price: f64,
+}
+
+impl ServiceCommitment {
+    /// `sustain` is how long full activation must be held, and the prices are paid per kW
+    /// enabled per hour.
+    pub fn new(
+        battery: &Battery,
+        raise: Power,
+        lower: Power,
+        sustain: Duration,
+        raise_price: f64,
+        lower_price: f64,
+    ) -> Result<Self, AncillaryError> {
+        let valid_power = |power: Power| power >= Power::zero() && power <= battery.max_power();
+        if !(valid_power(raise) && valid_power(lower)) {
+            return Err(AncillaryError::InvalidCommittedPower);
+        }
+        if !(raise_price.is_finite() && raise_price >= 0.0 && lower_price.is_finite() && lower_price >= 0.0) {
+            return Err(AncillaryError::InvalidPrice);
+        }
+        let commitment = ServiceCommitment { raise, lower, sustain, raise_price, lower_price };
+        let required = commitment.raise_reserve(battery) + commitment.lower_reserve(battery);
+        if required > battery.capacity() {
+            return Err(AncillaryError::InfeasibleCommitment { required: required.as_kwh() });
+        }
+        Ok(commitment)
+    }
+
+    pub fn raise(&self) -> Power {
+        self.raise
+    }
+
+    pub fn lower(&self) -> Power {
+        self.lower
+    }
+
+    pub fn sustain(&self) -> Duration {
+        self.sustain
+    }
+
+    /// Energy that must stay in the battery to sustain a full raise.
+    pub fn raise_reserve(&self, battery: &Battery) -> Energy {
+        self.raise * self.sustain / battery.efficiency()
+    }
+
+    /// Room that must stay free in the battery to sustain a full lower.
+    pub fn lower_reserve(&self, battery: &Battery) -> Energy {
+        self.lower * self.sustain * battery.efficiency()
+    }
+}
+
+/// Committed and delivered service of an ancillary services simulation.
+#[derive(Debug, Clone)]
+pub struct AncillaryResult {
+    states: Vec<BatteryState>,
+    requested: Vec<Energy>,
+    delivered: Vec<Energy>,
+    availability_revenue: f64,
+}
+
+impl AncillaryResult {
+    /// The initial state followed by the battery state at the end of every step.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// Service energy the activation signal asked for on every step.
+    pub fn requested(&self) -> &[Energy] {
+        &self.requested
+    }
+
+    /// Service energy the battery delivered on every step.
+    pub fn delivered(&self) -> &[Energy] {
+        &self.delivered
+    }
+
+    /// Delivered over requested service energy, 1 when nothing was requested.
+    pub fn delivery_ratio(&self) -> f64 {
+        let requested: f64 = self.requested.iter().map(|e| e.as_kwh()).sum();
+        let delivered: f64 = self.delivered.iter().map(|e| e.as_kwh()).sum();
+        if requested > 0.0 { delivered / requested } else { 1.0 }
+    }
+
+    /// Payment for the committed raise and lower power over the whole run.
+    pub fn availability_revenue(&self) -> f64 {
+        self.availability_revenue
+    }
+
+    /// Availability payment scaled by the delivery ratio.
+    pub fn revenue(&self) -> f64 {
+        self.availability_revenue * self.delivery_ratio()
+    }
+}
+
+/// Load-follows with headroom held back for a frequency service, then replays the activation.
+///
+/// `activation` has one entry per telemetry step, holding equally spaced sub-interval
+/// samples between -1 and 1: positive asks for that fraction of the committed raise
+/// (discharge) and negative for that fraction of the committed lower (charge). The
+/// load-following power is limited so the committed power is always free and the
+/// reserves for a full activation stay in the battery.
+pub fn simulate_with_service(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    commitment: &ServiceCommitment,
+    activation: &[Vec<f64>],
+) -> Result<AncillaryResult, AncillaryError> {
+    if activation.len() != telemetry_points.len() {
+        return Err(AncillaryError::LengthMismatch { expected: telemetry_points.len(), actual: activation.len() });
+    }
+    if let Some(step) = activation.iter()
+        .position(|samples| samples.is_empty() || samples.iter().any(|a| !(-1.0..=1.0).contains(a)))
+    {
+        return Err(AncillaryError::InvalidActivation(step));
+    }
+
+    let efficiency = battery.efficiency().as_fraction();
+    let min_soc = commitment.raise_reserve(battery).as_kwh();
+    let max_soc = (battery.capacity() - commitment.lower_reserve(battery)).as_kwh();
+    let max_charge = (battery.max_power() - commitment.lower).as_kw();
+    let max_discharge = (battery.max_power() - commitment.raise).as_kw();
+
+    let mut states = Vec::with_capacity(telemetry_points.len() + 1);
+    states.push(initial_state);
+    let mut requested = Vec::with_capacity(telemetry_points.len());
+    let mut delivered = Vec::with_capacity(telemetry_points.len());
+    let mut hours = 0.0;
+    let mut state = initial_state;
+
+    for (step, (point, samples)) in telemetry_points.iter().zip(activation).enumerate() {
+        let duration = point.duration().as_hour();
+        let soc = state.state_of_charge_kwh();
+        let charge_room = ((max_soc - soc).max(0.0) / (duration * efficiency)).min(max_charge);
+        let discharge_room = ((soc - min_soc).max(0.0) * efficiency / duration).min(max_discharge);
+        let base = point.excess_pv().as_kw().min(charge_room).max(-discharge_room);
+
+        let sub_duration = Duration::from_hour(duration / samples.len() as f64)
+            .expect("sub-interval duration should be valid");
+        let (mut asked_kwh, mut given_kwh) = (0.0, 0.0);
+        for &a in samples {
+            let service = if a > 0.0 { -a * commitment.raise.as_kw() } else { -a * commitment.lower.as_kw() };
+            let set_point = Power::from_kw(base + service).expect("set point should be valid");
+            state = battery.step(&state, set_point, sub_duration)
+                .map_err(|e| AncillaryError::ErrorSimulatingStep(e, step))?;
+            let response = (state.power_kw() - base) * service.signum();
+            asked_kwh += service.abs() * sub_duration.as_hour();
+            given_kwh += response.clamp(0.0, service.abs()) * sub_duration.as_hour();
+        }
+        states.push(state);
+        requested.push(Energy::from_kwh(asked_kwh).expect("requested energy should be valid"));
+        delivered.push(Energy::from_kwh(given_kwh).expect("delivered energy should be valid"));
+        hours += duration;
+    }
+
+    let availability_revenue =
+        (commitment.raise.as_kw() * commitment.raise_price + commitment.lower.as_kw() * commitment.lower_price) * hours;
+    Ok(AncillaryResult { states, requested, delivered, availability_revenue })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AsEfficiency;
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    fn commitment(battery: &Battery) -> ServiceCommitment {
+        ServiceCommitment::new(battery, kw!(2.0), kw!(2.0), hour!(1.0), 0.01, 0.005).expect("valid commitment")
+    }
+
+    #[test]
+    fn test_load_following_keeps_power_and_energy_reserves() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(10.0), kw!(0.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(10.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(10.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(10.0)),
+        ];
+        let activation = vec![vec![0.0]; 4];
+
+        let result = simulate_with_service(&telemetry, &battery, initial_state, &commitment(&battery), &activation)
+            .expect("simulation should succeed");
+
+        let soc: Vec<f64> = result.states().iter().map(|s| s.state_of_charge_kwh()).collect();
+        // Charging stops at 3 kW and 8 kWh, discharging at 3 kW and 2 kWh.
+        assert_eq!(soc, vec![5.0, 8.0, 5.0, 2.0, 2.0]);
+        assert_abs_diff_eq!(result.delivery_ratio(), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_sub_interval_activation_is_delivered() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(0.5), kw!(1.0), kw!(1.0)); 2];
+        let activation = vec![vec![1.0, 0.5, 0.0], vec![-1.0, -1.0, 0.0]];
+
+        let result = simulate_with_service(&telemetry, &battery, initial_state, &commitment(&battery), &activation)
+            .expect("simulation should succeed");
+
+        // Raise of 2 kW then 1 kW for ten minutes each, then lower of 2 kW for twenty.
+        assert_abs_diff_eq!(result.requested()[0].as_kwh(), 0.5, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.delivered()[1].as_kwh(), 2.0 / 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.states()[2].state_of_charge_kwh(), 5.0 - 0.5 + 2.0 / 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.delivery_ratio(), 1.0, epsilon = EPSILON);
+        // 2 kW at 0.01 and 2 kW at 0.005 for an hour.
+        assert_abs_diff_eq!(result.revenue(), 0.03, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_undelivered_service_reduces_revenue() {
+        let battery = test_battery();
+        // Starts below the raise reserve, so a long raise runs the battery empty.
+        let initial_state = battery.init_state(kwh!(1.0), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0))];
+        let activation = vec![vec![1.0]];
+
+        let result = simulate_with_service(&telemetry, &battery, initial_state, &commitment(&battery), &activation)
+            .expect("simulation should succeed");
+
+        assert_abs_diff_eq!(result.delivery_ratio(), 0.5, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.revenue(), 0.015, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_rejects_infeasible_commitments() {
+        let battery = test_battery();
+        assert!(matches!(
+            ServiceCommitment::new(&battery, kw!(6.0), kw!(0.0), hour!(1.0), 0.0, 0.0),
+            Err(AncillaryError::InvalidCommittedPower)
+        ));
+        assert!(matches!(
+            ServiceCommitment::new(&battery, kw!(5.0), kw!(5.0), hour!(2.0), 0.0, 0.0),
+            Err(AncillaryError::InfeasibleCommitment { .. })
+        ));
+    }
+}
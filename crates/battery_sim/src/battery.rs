@@ -1,6 +1,6 @@
 use crate::types::{Energy, Power, Duration, Efficiency, TelemetryPoint};
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct BatteryState {
     state_of_charge: Energy, // the current energy that the battery has
     power: Power,           // the battery power
@@ -14,6 +14,15 @@ impl BatteryState {
         }
     }
 
+    /// A state for a custom `BatteryModel`. Only the state of charge is checked here;
+    /// limits that depend on the model belong in its `init_state` and `step`.
+    pub fn try_new(state_of_charge: Energy, power: Power) -> Result<BatteryState, BatteryStateError> {
+        if state_of_charge.as_kwh() < 0.0 {
+            return Err(BatteryStateError::NegativeStateOfCharge);
+        }
+        Ok(BatteryState::new(state_of_charge, power))
+    }
+
     pub fn state_of_charge_kwh(&self) -> f64 {
         self.state_of_charge.as_kwh()
     }
@@ -38,6 +47,56 @@ pub struct Battery {
     round_trip_efficiency: Efficiency, // the round trip efficiency of the battery between 0 and 1
 }
 
+/// The interface a battery model exposes to the optimisers, so that models with
+/// nonlinear behaviour can be dispatched the same way as `Battery`.
+pub trait BatteryModel {
+    fn capacity(&self) -> Energy;
+
+    fn max_power(&self) -> Power;
+
+    fn init_state(
+        &self,
+        state_of_charge: Energy,
+        power: Power,
+    ) -> Result<BatteryState, BatteryStateError>;
+
+    fn step(
+        &self,
+        battery_state: &BatteryState,
+        power: Power,
+        duration: Duration,
+    ) -> Result<BatteryState, BatteryError>;
+
+    /// Energy through the terminals less losses and the change in state of charge over
+    /// a step. Models with conversion losses should override this; the default treats
+    /// the model as lossless.
+    fn energy_imbalance(&self, previous: &BatteryState, next: &BatteryState, duration: Duration) -> Energy {
+        next.power * duration - (next.state_of_charge - previous.state_of_charge)
+    }
+}
+
+impl<M: BatteryModel + ?Sized> BatteryModel for &M {
+    fn capacity(&self) -> Energy {
+        (**self).capacity()
+    }
+
+    fn max_power(&self) -> Power {
+        (**self).max_power()
+    }
+
+    fn init_state(&self, state_of_charge: Energy, power: Power) -> Result<BatteryState, BatteryStateError> {
+        (**self).init_state(state_of_charge, power)
+    }
+
+    fn step(&self, battery_state: &BatteryState, power: Power, duration: Duration) -> Result<BatteryState, BatteryError> {
+        (**self).step(battery_state, power, duration)
+    }
+
+    fn energy_imbalance(&self, previous: &BatteryState, next: &BatteryState, duration: Duration) -> Energy {
+        (**self).energy_imbalance(previous, next, duration)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum BatteryError {
     #[error("Capacity must be greater than 0.")]
@@ -189,6 +248,29 @@ impl Battery {
         }
     }
 
+    /// Energy lost in conversion while running at `power` for `duration`.
+    pub fn losses(&self, power: Power, duration: Duration) -> Energy {
+        let energy = power.abs() * duration;
+        if power > Power::zero() {
+            energy - energy * self.efficiency()
+        } else {
+            energy / self.efficiency() - energy
+        }
+    }
+
+    /// Energy through the terminals less losses and the change in state of charge
+    /// over a step from `previous` to `next`. Zero when no energy went missing.
+    pub fn energy_imbalance(
+        &self,
+        previous: &BatteryState,
+        next: &BatteryState,
+        duration: Duration,
+    ) -> Energy {
+        next.power * duration
+            - self.losses(next.power, duration)
+            - (next.state_of_charge - previous.state_of_charge)
+    }
+
     pub fn load_follow_step(
         &self,
         battery_state: &BatteryState,
@@ -199,12 +281,43 @@ impl Battery {
     }
 }
 
+impl BatteryModel for Battery {
+    fn capacity(&self) -> Energy {
+        Battery::capacity(self)
+    }
+
+    fn max_power(&self) -> Power {
+        Battery::max_power(self)
+    }
+
+    fn init_state(
+        &self,
+        state_of_charge: Energy,
+        power: Power,
+    ) -> Result<BatteryState, BatteryStateError> {
+        Battery::init_state(self, state_of_charge, power)
+    }
+
+    fn step(
+        &self,
+        battery_state: &BatteryState,
+        power: Power,
+        duration: Duration,
+    ) -> Result<BatteryState, BatteryError> {
+        Battery::step(self, battery_state, power, duration)
+    }
+
+    fn energy_imbalance(&self, previous: &BatteryState, next: &BatteryState, duration: Duration) -> Energy {
+        Battery::energy_imbalance(self, previous, next, duration)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use approx::assert_abs_diff_eq;
     use crate::{hour, kw, kwh};
-    use crate::types::{AsDuration, AsEfficiency};
+    use crate::types::AsEfficiency;
     const EPSILON: f64 = 1e-9;
 
     /* --------------- BATTERY CONSTRUCTION TESTS ------------------- */
@@ -612,6 +725,38 @@ mod tests {
         assert_abs_diff_eq!(state.power_kw(), 25.0, epsilon = EPSILON);
     }
 
+    /* --------------- ENERGY BALANCE TESTS ------------------- */
+
+    #[test]
+    fn test_losses_charge_and_discharge() {
+        let battery = Battery::new(kwh!(100.0), kw!(50.0), 0.81.fraction())
+            .expect("battery should be valid");
+        // 10 kWh in stores 9 kWh; 9 kWh out drains 10 kWh.
+        assert_abs_diff_eq!(battery.losses(kw!(10.0), hour!(1.0)).as_kwh(), 1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(battery.losses(kw!(-9.0), hour!(1.0)).as_kwh(), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_energy_imbalance_zero_for_valid_steps() {
+        let battery = Battery::new(kwh!(100.0), kw!(50.0), 0.81.fraction())
+            .expect("battery should be valid");
+        let state = battery.init_state(kwh!(95.0), Power::zero()).expect("valid state");
+        let charged = battery.charge(&state, kw!(50.0), hour!(1.0)).expect("charge should succeed");
+        let discharged = battery.discharge(&charged, kw!(50.0), hour!(3.0)).expect("discharge should succeed");
+        assert_abs_diff_eq!(battery.energy_imbalance(&state, &charged, hour!(1.0)).as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(battery.energy_imbalance(&charged, &discharged, hour!(3.0)).as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_energy_imbalance_detects_missing_energy() {
+        let battery = Battery::new(kwh!(100.0), kw!(50.0), 0.81.fraction())
+            .expect("battery should be valid");
+        let previous = battery.init_state(kwh!(100.0), Power::zero()).expect("valid state");
+        // Charging at 10 kW while already full stores nothing, so 9 kWh goes missing.
+        let next = battery.init_state(kwh!(100.0), kw!(10.0)).expect("valid state");
+        assert_abs_diff_eq!(battery.energy_imbalance(&previous, &next, hour!(1.0)).as_kwh(), 9.0, epsilon = EPSILON);
+    }
+
     /* --------------- LOAD FOLLOW STEP TESTS ------------------- */
 
     #[test]
@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::battery::{Battery, BatteryError, BatteryState, BatteryStateError};
+use crate::types::{Efficiency, Energy, Power};
+
+const CHECKPOINT_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheckpointError {
+    #[error("Checkpoint file error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Checkpoint format error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported checkpoint version {0}.")]
+    UnsupportedVersion(u32),
+    #[error("Invalid value {0} in checkpoint.")]
+    InvalidValue(f64),
+    #[error("Invalid battery in checkpoint.")]
+    InvalidBattery(#[source] BatteryError),
+    #[error("Invalid battery state in checkpoint.")]
+    InvalidState(#[source] BatteryStateError),
+}
+
+// On-disk layout. Plain numbers keep the unit types free of serde, and loading goes
+// back through the validating constructors.
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointFile {
+    version: u32,
+    capacity_kwh: f64,
+    max_power_kw: f64,
+    round_trip_efficiency: f64,
+    state_of_charge_kwh: f64,
+    power_kw: f64,
+    step: usize,
+    elapsed_hours: f64,
+    balance_tolerance_kwh: Option<f64>,
+}
+
+/// Everything needed to carry a simulation on from where it stopped: the battery,
+/// its state and the time cursor.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    battery: Battery,
+    state: BatteryState,
+    step: usize,
+    elapsed_hours: f64,
+    balance_tolerance: Option<Energy>,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(
+        battery: Battery,
+        state: BatteryState,
+        step: usize,
+        elapsed_hours: f64,
+        balance_tolerance: Option<Energy>,
+    ) -> Self {
+        Checkpoint { battery, state, step, elapsed_hours, balance_tolerance }
+    }
+
+    pub fn battery(&self) -> &Battery {
+        &self.battery
+    }
+
+    pub fn state(&self) -> BatteryState {
+        self.state
+    }
+
+    /// Number of steps simulated before the checkpoint.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Simulated time before the checkpoint, in hours.
+    pub fn elapsed_hours(&self) -> f64 {
+        self.elapsed_hours
+    }
+
+    pub(crate) fn balance_tolerance(&self) -> Option<Energy> {
+        self.balance_tolerance
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        let file = CheckpointFile {
+            version: CHECKPOINT_VERSION,
+            capacity_kwh: self.battery.capacity().as_kwh(),
+            max_power_kw: self.battery.max_power().as_kw(),
+            round_trip_efficiency: self.battery.round_trip_efficiency().as_fraction(),
+            state_of_charge_kwh: self.state.state_of_charge_kwh(),
+            power_kw: self.state.power_kw(),
+            step: self.step,
+            elapsed_hours: self.elapsed_hours,
+            balance_tolerance_kwh: self.balance_tolerance.map(|tolerance| tolerance.as_kwh()),
+        };
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &file)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CheckpointError> {
+        let file: CheckpointFile = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if file.version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(file.version));
+        }
+        let battery = Battery::new(
+            Energy::from_kwh(file.capacity_kwh).map_err(CheckpointError::InvalidValue)?,
+            Power::from_kw(file.max_power_kw).map_err(CheckpointError::InvalidValue)?,
+            Efficiency::from_fraction(file.round_trip_efficiency).map_err(CheckpointError::InvalidValue)?,
+        ).map_err(CheckpointError::InvalidBattery)?;
+        let state = battery.init_state(
+            Energy::from_kwh(file.state_of_charge_kwh).map_err(CheckpointError::InvalidValue)?,
+            Power::from_kw(file.power_kw).map_err(CheckpointError::InvalidValue)?,
+        ).map_err(CheckpointError::InvalidState)?;
+        let balance_tolerance = file.balance_tolerance_kwh
+            .map(Energy::from_kwh)
+            .transpose()
+            .map_err(CheckpointError::InvalidValue)?;
+        if let Some(tolerance) = balance_tolerance.filter(|tolerance| tolerance.as_kwh() < 0.0) {
+            return Err(CheckpointError::InvalidValue(tolerance.as_kwh()));
+        }
+        if !file.elapsed_hours.is_finite() || file.elapsed_hours < 0.0 {
+            return Err(CheckpointError::InvalidValue(file.elapsed_hours));
+        }
+        Ok(Checkpoint::new(battery, state, file.step, file.elapsed_hours, balance_tolerance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{LoadFollowingSimulation, StepResult};
+    use crate::types::{AsEfficiency, Duration, TelemetryPoint};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    use std::path::PathBuf;
+    const EPSILON: f64 = 1e-9;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("battery_sim_{}_{}.json", name, std::process::id()))
+    }
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(13.5), kw!(5.0), 0.9.fraction()).expect("battery should be valid")
+    }
+
+    fn test_telemetry() -> Vec<TelemetryPoint> {
+        (0..96)
+            .map(|i| {
+                let hour_of_day = (i % 48) as f64 / 2.0;
+                let solar = (6.0 - (hour_of_day - 12.0).abs()).max(0.0) * 1.3;
+                let load = 0.4 + 0.17 * ((i * 7) % 11) as f64;
+                TelemetryPoint::new(
+                    hour!(0.5),
+                    Power::from_kw(solar).expect("valid power"),
+                    Power::from_kw(load).expect("valid power"),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resumed_run_matches_uninterrupted_run() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(3.1), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+        let path = temp_path("resume");
+
+        let uninterrupted: Vec<StepResult> = LoadFollowingSimulation::new(telemetry.clone(), battery.clone(), initial_state)
+            .collect::<Result<_, _>>()
+            .expect("simulation should succeed");
+
+        let mut first_day = LoadFollowingSimulation::new(telemetry[..48].to_vec(), battery, initial_state);
+        let mut resumed: Vec<StepResult> = first_day.by_ref()
+            .collect::<Result<_, _>>()
+            .expect("simulation should succeed");
+        first_day.checkpoint().save(&path).expect("checkpoint should save");
+
+        let checkpoint = Checkpoint::load(&path).expect("checkpoint should load");
+        std::fs::remove_file(&path).expect("checkpoint should be removable");
+        assert_eq!(checkpoint.step(), 48);
+        assert_abs_diff_eq!(checkpoint.elapsed_hours(), 24.0, epsilon = EPSILON);
+        resumed.extend(
+            LoadFollowingSimulation::resume(checkpoint, telemetry[48..].to_vec())
+                .map(|result| result.expect("simulation should succeed")),
+        );
+
+        assert_eq!(resumed.len(), uninterrupted.len());
+        for (resumed, uninterrupted) in resumed.iter().zip(&uninterrupted) {
+            assert_eq!(resumed.step(), uninterrupted.step());
+            assert_eq!(resumed.state().state_of_charge_kwh(), uninterrupted.state().state_of_charge_kwh());
+            assert_eq!(resumed.state().power_kw(), uninterrupted.state().power_kw());
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_state() {
+        let path = temp_path("invalid_state");
+        let json = r#"{
+            "version": 1, "capacity_kwh": 10.0, "max_power_kw": 5.0, "round_trip_efficiency": 0.9,
+            "state_of_charge_kwh": 12.0, "power_kw": 0.0, "step": 3, "elapsed_hours": 3.0,
+            "balance_tolerance_kwh": null
+        }"#;
+        std::fs::write(&path, json).expect("should write checkpoint");
+
+        let result = Checkpoint::load(&path);
+        std::fs::remove_file(&path).expect("checkpoint should be removable");
+
+        assert!(matches!(result, Err(CheckpointError::InvalidState(_))));
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_version() {
+        let path = temp_path("version");
+        let json = r#"{
+            "version": 99, "capacity_kwh": 10.0, "max_power_kw": 5.0, "round_trip_efficiency": 0.9,
+            "state_of_charge_kwh": 2.0, "power_kw": 0.0, "step": 3, "elapsed_hours": 3.0,
+            "balance_tolerance_kwh": null
+        }"#;
+        std::fs::write(&path, json).expect("should write checkpoint");
+
+        let result = Checkpoint::load(&path);
+        std::fs::remove_file(&path).expect("checkpoint should be removable");
+
+        assert!(matches!(result, Err(CheckpointError::UnsupportedVersion(99))));
+    }
+}
@@ -0,0 +1,322 @@
+use crate::battery::{Battery, BatteryError, BatteryState};
+use crate::types::{Energy, Power, TelemetryPoint};
+
+// How far the member shares may sum away from one.
+const SHARE_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommunityError {
+    #[error("A community battery needs at least one member.")]
+    NoMembers,
+    #[error("Member share {0} must be greater than 0 and at most 1.")]
+    InvalidShare(f64),
+    #[error("Member shares sum to {0}, not 1.")]
+    SharesDoNotSumToOne(f64),
+    #[error("Member {member} has {actual} telemetry points, expected {expected}.")]
+    LengthMismatch { member: usize, expected: usize, actual: usize },
+    #[error("Member {member} has a different step duration from the first member on step {step}.")]
+    MisalignedStep { member: usize, step: usize },
+    #[error("Member {1}'s share of the battery is not a valid battery.")]
+    InvalidVirtualBattery(#[source] BatteryError, usize),
+    #[error("Simulating the community battery failed on step {1}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize),
+}
+
+/// A household sharing the battery, with its own telemetry and share of the battery.
+#[derive(Debug, Clone)]
+pub struct CommunityMember {
+    telemetry: Vec<TelemetryPoint>,
+    share: f64,
+}
+
+impl CommunityMember {
+    pub fn new(telemetry: Vec<TelemetryPoint>, share: f64) -> Result<Self, CommunityError> {
+        if !(share > 0.0 && share <= 1.0) {
+            return Err(CommunityError::InvalidShare(share));
+        }
+        Ok(CommunityMember { telemetry, share })
+    }
+
+    pub fn telemetry(&self) -> &[TelemetryPoint] {
+        &self.telemetry
+    }
+
+    pub fn share(&self) -> f64 {
+        self.share
+    }
+}
+
+/// How the shared battery's charge and discharge are divided between members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationRule {
+    /// Each member owns a virtual battery of its share of the capacity and max power,
+    /// charged only from its own excess PV and discharged only into its own load.
+    /// Every member's energy goes through the battery, so losses are counted per member.
+    VirtualShares,
+    /// The battery follows the community's net excess PV. Members are credited with
+    /// what it stores in proportion to their surplus and debited with what it delivers
+    /// in proportion to their deficit, so a member's virtual state of charge goes
+    /// negative once they have drawn more than they put in.
+    ///
+    /// Shares only split the initial state of charge. No member is entitled to any
+    /// part of the capacity or power, and a member with a small share can draw on the
+    /// whole battery.
+    Pooled,
+}
+
+/// Physical and per-member results of a community battery simulation.
+#[derive(Debug, Clone)]
+pub struct CommunityResult {
+    states: Vec<BatteryState>,
+    member_soc: Vec<Vec<Energy>>,
+    member_power: Vec<Vec<Power>>,
+    member_grid_power: Vec<Vec<Power>>,
+}
+
+impl CommunityResult {
+    /// The initial state followed by the physical battery state after every step.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// The member's virtual state of charge, initial value first.
+    pub fn member_soc(&self, member: usize) -> &[Energy] {
+        &self.member_soc[member]
+    }
+
+    /// The member's part of the battery power on every step.
+    pub fn member_power(&self, member: usize) -> &[Power] {
+        &self.member_power[member]
+    }
+
+    /// Power the member draws from the grid on every step, negative when exporting.
+    pub fn member_grid_power(&self, member: usize) -> &[Power] {
+        &self.member_grid_power[member]
+    }
+
+    pub fn members(&self) -> usize {
+        self.member_soc.len()
+    }
+}
+
+/// Simulates one battery shared by several households.
+///
+/// The members' telemetry must be aligned step for step. The initial state is split
+/// between members by share, and on every step the members' virtual states of charge
+/// and powers sum to those of the physical battery.
+pub fn simulate_community_battery(
+    members: &[CommunityMember],
+    battery: &Battery,
+    initial_state: BatteryState,
+    rule: AllocationRule,
+) -> Result<CommunityResult, CommunityError> {
+    let first = members.first().ok_or(CommunityError::NoMembers)?;
+    let total_share: f64 = members.iter().map(|member| member.share).sum();
+    if (total_share - 1.0).abs() > SHARE_TOLERANCE {
+        return Err(CommunityError::SharesDoNotSumToOne(total_share));
+    }
+    let steps = first.telemetry.len();
+    for (k, member) in members.iter().enumerate() {
+        if member.telemetry.len() != steps {
+            return Err(CommunityError::LengthMismatch { member: k, expected: steps, actual: member.telemetry.len() });
+        }
+        if let Some(step) = (0..steps).find(|&i| member.telemetry[i].duration() != first.telemetry[i].duration()) {
+            return Err(CommunityError::MisalignedStep { member: k, step });
+        }
+    }
+
+    // Shares and allocation fractions are at most 1, so scaling never leaves the valid range.
+    let scale_energy = |energy: Energy, share: f64| Energy::from_kwh(energy.as_kwh() * share)
+        .expect("a fraction of a valid energy should be valid");
+    let scale_power = |power: Power, share: f64| Power::from_kw(power.as_kw() * share)
+        .expect("a fraction of a valid power should be valid");
+
+    let mut member_soc: Vec<Vec<Energy>> = members.iter()
+        .map(|member| vec![scale_energy(initial_state.state_of_charge(), member.share)])
+        .collect();
+    let mut member_power: Vec<Vec<Power>> = vec![Vec::with_capacity(steps); members.len()];
+    let mut member_grid_power: Vec<Vec<Power>> = vec![Vec::with_capacity(steps); members.len()];
+    let mut states = Vec::with_capacity(steps + 1);
+    states.push(initial_state);
+    let mut state = initial_state;
+
+    match rule {
+        AllocationRule::VirtualShares => {
+            // A share small enough to scale the battery down to nothing is rejected here.
+            let virtual_batteries = members.iter().enumerate()
+                .map(|(k, member)| Battery::new(
+                    scale_energy(battery.capacity(), member.share),
+                    scale_power(battery.max_power(), member.share),
+                    battery.round_trip_efficiency(),
+                ).map_err(|e| CommunityError::InvalidVirtualBattery(e, k)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut virtual_states = members.iter().zip(&virtual_batteries).zip(&member_soc).enumerate()
+                .map(|(k, ((member, virtual_battery), soc))| virtual_battery.init_state(
+                    soc[0].min(virtual_battery.capacity()),
+                    scale_power(initial_state.power(), member.share),
+                ).map_err(|e| CommunityError::InvalidVirtualBattery(BatteryError::ErrorCharging(e), k)))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for step in 0..steps {
+                let (mut soc, mut power) = (Energy::zero(), Power::zero());
+                for (k, member) in members.iter().enumerate() {
+                    let point = &member.telemetry[step];
+                    let next = virtual_batteries[k].load_follow_step(&virtual_states[k], point)
+                        .map_err(|e| CommunityError::ErrorSimulatingStep(e, step))?;
+                    virtual_states[k] = next;
+                    member_soc[k].push(next.state_of_charge());
+                    member_power[k].push(next.power());
+                    member_grid_power[k].push(next.power() - point.excess_pv());
+                    soc = soc + next.state_of_charge();
+                    power = power + next.power();
+                }
+                state = battery.init_state(soc.min(battery.capacity()), power)
+                    .map_err(|e| CommunityError::ErrorSimulatingStep(BatteryError::ErrorCharging(e), step))?;
+                states.push(state);
+            }
+        }
+        AllocationRule::Pooled => {
+            for step in 0..steps {
+                let excess: Vec<Power> = members.iter().map(|member| member.telemetry[step].excess_pv()).collect();
+                let net = excess.iter().fold(Power::zero(), |total, &power| total + power);
+                let next = battery.step(&state, net, first.telemetry[step].duration())
+                    .map_err(|e| CommunityError::ErrorSimulatingStep(e, step))?;
+                let stored = next.state_of_charge() - state.state_of_charge();
+
+                // Charge is shared by surplus and discharge by deficit.
+                let weights: Vec<f64> = excess.iter()
+                    .map(|power| if next.power() > Power::zero() {
+                        power.as_kw().max(0.0)
+                    } else {
+                        (-power.as_kw()).max(0.0)
+                    })
+                    .collect();
+                let total_weight: f64 = weights.iter().sum();
+                for k in 0..members.len() {
+                    let fraction = if total_weight > 0.0 { weights[k] / total_weight } else { 0.0 };
+                    let power = scale_power(next.power(), fraction);
+                    let soc = member_soc[k][step] + scale_energy(stored, fraction);
+                    member_soc[k].push(soc);
+                    member_power[k].push(power);
+                    member_grid_power[k].push(power - excess[k]);
+                }
+                state = next;
+                states.push(state);
+            }
+        }
+    }
+
+    Ok(CommunityResult { states, member_soc, member_power, member_grid_power })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(4.0), 0.81.fraction()).expect("battery should be valid")
+    }
+
+    // One member exports in the day, the other imports in the evening.
+    fn members(shares: [f64; 2]) -> Vec<CommunityMember> {
+        let producer = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(5.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(5.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+        ];
+        let consumer = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.5)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(3.0)),
+        ];
+        vec![
+            CommunityMember::new(producer, shares[0]).expect("valid member"),
+            CommunityMember::new(consumer, shares[1]).expect("valid member"),
+        ]
+    }
+
+    fn assert_members_sum_to_battery(result: &CommunityResult) {
+        for (step, state) in result.states().iter().enumerate() {
+            let soc: f64 = (0..result.members()).map(|k| result.member_soc(k)[step].as_kwh()).sum();
+            assert_abs_diff_eq!(soc, state.state_of_charge_kwh(), epsilon = EPSILON);
+            if step > 0 {
+                let power: f64 = (0..result.members()).map(|k| result.member_power(k)[step - 1].as_kw()).sum();
+                assert_abs_diff_eq!(power, state.power_kw(), epsilon = EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn test_virtual_shares_keep_members_separate() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(2.0), Power::zero()).expect("valid state");
+
+        let result = simulate_community_battery(&members([0.5, 0.5]), &battery, initial_state, AllocationRule::VirtualShares)
+            .expect("simulation should succeed");
+
+        assert_members_sum_to_battery(&result);
+        // The producer is limited to half the max power and capacity.
+        assert_abs_diff_eq!(result.member_power(0)[0].as_kw(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.member_soc(0)[2].as_kwh(), 1.0 + 2.0 * 1.8, epsilon = EPSILON);
+        // The consumer only ever gets its own starting energy.
+        assert_abs_diff_eq!(result.member_soc(1)[3].as_kwh(), 0.0, epsilon = EPSILON);
+        assert!(result.member_grid_power(1)[2] > Power::zero());
+    }
+
+    #[test]
+    fn test_pooled_lets_members_draw_on_each_others_surplus() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+
+        let result = simulate_community_battery(&members([0.5, 0.5]), &battery, initial_state, AllocationRule::Pooled)
+            .expect("simulation should succeed");
+
+        assert_members_sum_to_battery(&result);
+        // The first step nets the consumer's load against the producer's surplus.
+        assert_abs_diff_eq!(result.states()[1].power_kw(), 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.member_power(0)[0].as_kw(), 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.member_power(1)[0].as_kw(), 0.0, epsilon = EPSILON);
+        // In the evening the consumer draws from what the producer stored.
+        assert!(result.member_power(1)[2] < Power::zero());
+        assert!(result.member_soc(1)[3] < Energy::zero());
+    }
+
+    #[test]
+    fn test_rejects_invalid_communities() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+
+        assert!(matches!(CommunityMember::new(vec![], 0.0), Err(CommunityError::InvalidShare(_))));
+        assert!(matches!(
+            simulate_community_battery(&members([0.5, 0.6]), &battery, initial_state, AllocationRule::Pooled),
+            Err(CommunityError::SharesDoNotSumToOne(_))
+        ));
+        let mut short = members([0.5, 0.5]);
+        short[1] = CommunityMember::new(vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0))], 0.5)
+            .expect("valid member");
+        assert!(matches!(
+            simulate_community_battery(&short, &battery, initial_state, AllocationRule::Pooled),
+            Err(CommunityError::LengthMismatch { member: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_share_too_small_for_a_virtual_battery() {
+        let battery = Battery::new(kwh!(0.1), kw!(0.1), 0.81.fraction()).expect("battery should be valid");
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        // The smallest positive share scales the capacity down to zero.
+        let tiny = f64::from_bits(1);
+        let members = members([tiny, 1.0 - tiny]);
+
+        assert!(matches!(
+            simulate_community_battery(&members, &battery, initial_state, AllocationRule::VirtualShares),
+            Err(CommunityError::InvalidVirtualBattery(BatteryError::NonPositiveCapacity, 0))
+        ));
+        // Pooled members have no virtual battery of their own.
+        assert!(simulate_community_battery(&members, &battery, initial_state, AllocationRule::Pooled).is_ok());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::battery::{Battery, BatteryError, BatteryState};
+use crate::types::{Energy, Power, TelemetryPoint};
+
+const HOURS_PER_DAY: f64 = 24.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ControllableLoadError {
+    #[error("Window from {start} to {end} must satisfy 0 <= start < end <= 24 hours of the day.")]
+    InvalidWindow { start: f64, end: f64 },
+    #[error("Windows from {first_start} to {first_end} and from {second_start} to {second_end} overlap.")]
+    OverlappingWindows { first_start: f64, first_end: f64, second_start: f64, second_end: f64 },
+    #[error("A controllable load needs at least one allowed window.")]
+    NoWindows,
+    #[error("Daily energy must not be negative.")]
+    NegativeDailyEnergy,
+    #[error("Max power must be greater than 0.")]
+    NonPositiveMaxPower,
+    #[error("Daily energy of {daily_kwh} kWh cannot be delivered in {window_hours} window hours at max power.")]
+    InfeasibleDailyEnergy { daily_kwh: f64, window_hours: f64 },
+    #[error("Controllable load power {0} kW on step {1} is out of range.")]
+    LoadPowerOutOfRange(f64, usize),
+    #[error("Simulating controllable loads failed on step {1}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize),
+}
+
+/// Hours of the day during which a load may run. Windows do not wrap past midnight;
+/// use two windows for e.g. 22:00 to 06:00. A load's windows must not overlap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadWindow {
+    start: f64,
+    end: f64,
+}
+
+impl LoadWindow {
+    pub fn new(start: f64, end: f64) -> Result<Self, ControllableLoadError> {
+        if !(start >= 0.0 && end <= HOURS_PER_DAY && start < end) {
+            return Err(ControllableLoadError::InvalidWindow { start, end });
+        }
+        Ok(LoadWindow { start, end })
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    fn overlap(&self, from: f64, to: f64) -> f64 {
+        (to.min(self.end) - from.max(self.start)).max(0.0)
+    }
+}
+
+/// What happens to a load's daily energy when it cannot be met from surplus PV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadFlexibility {
+    /// The daily energy must be consumed. It is moved into surplus PV where possible
+    /// and otherwise run as late in its windows as still finishes the day.
+    Shiftable,
+    /// The load only runs on surplus PV. Whatever the surplus does not cover is curtailed.
+    Curtailable,
+}
+
+/// A flexible load such as a pool pump, hot water system or HVAC pre-cooling.
+#[derive(Debug, Clone)]
+pub struct ControllableLoad {
+    daily_energy: Energy,
+    max_power: Power,
+    windows: Vec<LoadWindow>,
+    flexibility: LoadFlexibility,
+}
+
+impl ControllableLoad {
+    pub fn new(
+        daily_energy: Energy,
+        max_power: Power,
+        windows: Vec<LoadWindow>,
+        flexibility: LoadFlexibility,
+    ) -> Result<Self, ControllableLoadError> {
+        if daily_energy < Energy::zero() {
+            return Err(ControllableLoadError::NegativeDailyEnergy);
+        }
+        if max_power <= Power::zero() {
+            return Err(ControllableLoadError::NonPositiveMaxPower);
+        }
+        if windows.is_empty() {
+            return Err(ControllableLoadError::NoWindows);
+        }
+        let mut sorted = windows.clone();
+        sorted.sort_by(|a, b| a.start.total_cmp(&b.start));
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[1].start < pair[0].end) {
+            return Err(ControllableLoadError::OverlappingWindows {
+                first_start: pair[0].start,
+                first_end: pair[0].end,
+                second_start: pair[1].start,
+                second_end: pair[1].end,
+            });
+        }
+        let window_hours: f64 = windows.iter().map(|w| w.end - w.start).sum();
+        if daily_energy.as_kwh() > max_power.as_kw() * window_hours {
+            return Err(ControllableLoadError::InfeasibleDailyEnergy {
+                daily_kwh: daily_energy.as_kwh(),
+                window_hours,
+            });
+        }
+        Ok(ControllableLoad { daily_energy, max_power, windows, flexibility })
+    }
+
+    pub fn daily_energy(&self) -> Energy {
+        self.daily_energy
+    }
+
+    pub fn max_power(&self) -> Power {
+        self.max_power
+    }
+
+    pub fn windows(&self) -> &[LoadWindow] {
+        &self.windows
+    }
+
+    pub fn flexibility(&self) -> LoadFlexibility {
+        self.flexibility
+    }
+
+    // Window hours between two hours of the same day.
+    fn window_hours(&self, from: f64, to: f64) -> f64 {
+        self.windows.iter().map(|w| w.overlap(from, to)).sum()
+    }
+}
+
+/// Battery and load results of a simulation with controllable loads.
+#[derive(Debug, Clone)]
+pub struct ControllableLoadResult {
+    states: Vec<BatteryState>,
+    load_power: Vec<Vec<Power>>,
+    grid_power: Vec<Power>,
+    shortfall: Vec<Energy>,
+    grid_import: Energy,
+    grid_export: Energy,
+}
+
+impl ControllableLoadResult {
+    /// The initial state followed by the battery state after every step.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// Power of each load on every step, indexed by load then step.
+    pub fn load_power(&self) -> &[Vec<Power>] {
+        &self.load_power
+    }
+
+    /// Power drawn from the grid on every step, negative when exporting.
+    pub fn grid_power(&self) -> &[Power] {
+        &self.grid_power
+    }
+
+    /// Daily energy each load did not get over the whole run. For curtailable loads
+    /// this is the curtailed energy.
+    pub fn shortfall(&self) -> &[Energy] {
+        &self.shortfall
+    }
+
+    pub fn grid_import(&self) -> Energy {
+        self.grid_import
+    }
+
+    pub fn grid_export(&self) -> Energy {
+        self.grid_export
+    }
+}
+
+/// Schedules controllable loads alongside a load-following battery.
+///
+/// On every step the loads take surplus PV first, in the order given, then shiftable
+/// loads draw whatever they need to still meet their daily energy. The battery
+/// load-follows what is left. Telemetry is assumed to start at midnight, and the
+/// daily energy of a day the telemetry ends part way through still counts in full.
+pub fn simulate_with_controllable_loads(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    loads: &[ControllableLoad],
+) -> Result<ControllableLoadResult, ControllableLoadError> {
+    let mut remaining: Vec<f64> = loads.iter().map(|load| load.daily_energy.as_kwh()).collect();
+    let mut shortfall = vec![0.0; loads.len()];
+    let mut load_power = vec![Vec::with_capacity(telemetry_points.len()); loads.len()];
+    let mut states = Vec::with_capacity(telemetry_points.len() + 1);
+    states.push(initial_state);
+    let mut grid_power = Vec::with_capacity(telemetry_points.len());
+    let (mut grid_import, mut grid_export) = (Energy::zero(), Energy::zero());
+    let mut state = initial_state;
+    let mut start = 0.0;
+    let mut day = 0.0;
+
+    for (step, telemetry_point) in telemetry_points.iter().enumerate() {
+        let hours = telemetry_point.duration().as_hour();
+        let step_day = (start / HOURS_PER_DAY).floor();
+        if step_day > day {
+            for (k, load) in loads.iter().enumerate() {
+                shortfall[k] += remaining[k];
+                remaining[k] = load.daily_energy.as_kwh();
+            }
+            day = step_day;
+        }
+        // A step crossing midnight only runs loads in the part before it.
+        let from = start - day * HOURS_PER_DAY;
+        let to = (from + hours).min(HOURS_PER_DAY);
+        let mut surplus_kwh = telemetry_point.excess_pv().as_kw().max(0.0) * hours;
+        let mut consumed_kwh = 0.0;
+
+        for (k, load) in loads.iter().enumerate() {
+            let limit = remaining[k].min(load.max_power.as_kw() * load.window_hours(from, to));
+            let energy = match load.flexibility {
+                LoadFlexibility::Curtailable => limit.min(surplus_kwh),
+                LoadFlexibility::Shiftable => {
+                    let needed_now = remaining[k] - load.max_power.as_kw() * load.window_hours(to, HOURS_PER_DAY);
+                    limit.min(needed_now.max(surplus_kwh))
+                }
+            };
+            let energy = energy.max(0.0);
+            surplus_kwh = (surplus_kwh - energy).max(0.0);
+            remaining[k] -= energy;
+            consumed_kwh += energy;
+            load_power[k].push(
+                Power::from_kw(energy / hours).map_err(|power| ControllableLoadError::LoadPowerOutOfRange(power, step))?,
+            );
+        }
+
+        let flexible = Power::from_kw(consumed_kwh / hours)
+            .map_err(|power| ControllableLoadError::LoadPowerOutOfRange(power, step))?;
+        let site_excess = telemetry_point.excess_pv() - flexible;
+        state = battery.step(&state, site_excess, telemetry_point.duration())
+            .map_err(|e| ControllableLoadError::ErrorSimulatingStep(e, step))?;
+        states.push(state);
+        let grid = state.power() - site_excess;
+        if grid > Power::zero() {
+            grid_import = grid_import + grid * telemetry_point.duration();
+        } else {
+            grid_export = grid_export - grid * telemetry_point.duration();
+        }
+        grid_power.push(grid);
+        start += hours;
+    }
+
+    let shortfall = shortfall.iter().zip(&remaining)
+        .map(|(&missed, &left)| Energy::from_kwh(missed + left).expect("shortfall should be valid"))
+        .collect();
+    Ok(ControllableLoadResult { states, load_power, grid_power, shortfall, grid_import, grid_export })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn battery(capacity: Energy) -> Battery {
+        Battery::new(capacity, kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    // A battery too weak to affect the grid, so the loads alone are tested.
+    fn no_battery() -> Battery {
+        Battery::new(kwh!(1.0), kw!(1e-12), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    fn powers(values: &[Power]) -> Vec<f64> {
+        values.iter().map(|p| p.as_kw()).collect()
+    }
+
+    fn window(start: f64, end: f64) -> LoadWindow {
+        LoadWindow::new(start, end).expect("valid window")
+    }
+
+    // Six hourly steps of a day: a night, a sunny morning and an evening.
+    fn sunny_morning() -> Vec<TelemetryPoint> {
+        vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(4.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(4.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+        ]
+    }
+
+    #[test]
+    fn test_shiftable_load_moves_into_surplus() {
+        let battery = no_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let pump = ControllableLoad::new(kwh!(4.0), kw!(2.0), vec![window(0.0, 6.0)], LoadFlexibility::Shiftable)
+            .expect("valid load");
+
+        let result = simulate_with_controllable_loads(&sunny_morning(), &battery, initial_state, &[pump])
+            .expect("simulation should succeed");
+
+        assert_eq!(powers(&result.load_power()[0]), vec![0.0, 2.0, 2.0, 0.0, 0.0, 0.0]);
+        assert_abs_diff_eq!(result.grid_export().as_kwh(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.shortfall()[0].as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_shiftable_load_runs_late_without_surplus() {
+        let battery = no_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0)); 6];
+        let heater = ControllableLoad::new(kwh!(3.0), kw!(2.0), vec![window(1.0, 5.0)], LoadFlexibility::Shiftable)
+            .expect("valid load");
+
+        let result = simulate_with_controllable_loads(&telemetry, &battery, initial_state, &[heater])
+            .expect("simulation should succeed");
+
+        assert_eq!(powers(&result.load_power()[0]), vec![0.0, 0.0, 0.0, 1.0, 2.0, 0.0]);
+        assert_abs_diff_eq!(result.grid_import().as_kwh(), 3.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_curtailable_load_only_uses_surplus() {
+        let battery = no_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let cooling = ControllableLoad::new(kwh!(10.0), kw!(5.0), vec![window(0.0, 6.0)], LoadFlexibility::Curtailable)
+            .expect("valid load");
+
+        let result = simulate_with_controllable_loads(&sunny_morning(), &battery, initial_state, &[cooling])
+            .expect("simulation should succeed");
+
+        assert_eq!(powers(&result.load_power()[0]), vec![0.0, 3.0, 3.0, 0.0, 0.0, 0.0]);
+        assert_abs_diff_eq!(result.shortfall()[0].as_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.grid_export().as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_daily_energy_resets_each_day() {
+        let battery = no_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(12.0), kw!(0.0), kw!(0.0)); 4];
+        let pump = ControllableLoad::new(kwh!(6.0), kw!(1.0), vec![window(12.0, 24.0)], LoadFlexibility::Curtailable)
+            .expect("valid load");
+
+        let result = simulate_with_controllable_loads(&telemetry, &battery, initial_state, &[pump])
+            .expect("simulation should succeed");
+
+        assert_abs_diff_eq!(result.shortfall()[0].as_kwh(), 12.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_load_flexibility_substitutes_for_battery() {
+        let initial = |battery: &Battery| battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = sunny_morning();
+        let pump = ControllableLoad::new(kwh!(4.0), kw!(2.0), vec![window(0.0, 6.0)], LoadFlexibility::Shiftable)
+            .expect("valid load");
+        let fixed_pump = ControllableLoad::new(kwh!(4.0), kw!(2.0), vec![window(4.0, 6.0)], LoadFlexibility::Shiftable)
+            .expect("valid load");
+
+        let small = battery(kwh!(2.0));
+        let flexible = simulate_with_controllable_loads(&telemetry, &small, initial(&small), &[pump])
+            .expect("simulation should succeed");
+        let inflexible = simulate_with_controllable_loads(&telemetry, &small, initial(&small), std::slice::from_ref(&fixed_pump))
+            .expect("simulation should succeed");
+        let large = battery(kwh!(6.0));
+        let large_inflexible = simulate_with_controllable_loads(&telemetry, &large, initial(&large), &[fixed_pump])
+            .expect("simulation should succeed");
+
+        // A flexible pump and a small battery import as little as an inflexible pump and a bigger battery.
+        assert_abs_diff_eq!(flexible.grid_import().as_kwh(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(inflexible.grid_import().as_kwh(), 6.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(large_inflexible.grid_import().as_kwh(), 2.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_rejects_invalid_loads() {
+        assert!(matches!(LoadWindow::new(22.0, 6.0), Err(ControllableLoadError::InvalidWindow { .. })));
+        assert!(matches!(
+            ControllableLoad::new(kwh!(1.0), kw!(1.0), vec![], LoadFlexibility::Shiftable),
+            Err(ControllableLoadError::NoWindows)
+        ));
+        assert!(matches!(
+            ControllableLoad::new(kwh!(5.0), kw!(1.0), vec![window(0.0, 2.0)], LoadFlexibility::Shiftable),
+            Err(ControllableLoadError::InfeasibleDailyEnergy { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_overlapping_windows() {
+        assert!(matches!(
+            ControllableLoad::new(kwh!(1.0), kw!(1.0), vec![window(2.0, 8.0), window(0.0, 6.0)], LoadFlexibility::Shiftable),
+            Err(ControllableLoadError::OverlappingWindows { first_start: 0.0, first_end: 6.0, second_start: 2.0, second_end: 8.0 })
+        ));
+        // Overlapping hours would otherwise count twice towards the feasibility check.
+        assert!(matches!(
+            ControllableLoad::new(kwh!(10.0), kw!(1.0), vec![window(0.0, 6.0), window(0.0, 6.0)], LoadFlexibility::Shiftable),
+            Err(ControllableLoadError::OverlappingWindows { .. })
+        ));
+        assert!(ControllableLoad::new(kwh!(1.0), kw!(1.0), vec![window(0.0, 6.0), window(6.0, 8.0)], LoadFlexibility::Shiftable).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_combined_load_power_out_of_range() {
+        let battery = no_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0))];
+        let heater = ControllableLoad::new(kwh!(600_000.0), kw!(600_000.0), vec![window(0.0, 1.0)], LoadFlexibility::Shiftable)
+            .expect("valid load");
+
+        let result = simulate_with_controllable_loads(&telemetry, &battery, initial_state, &[heater.clone(), heater]);
+
+        assert!(matches!(result, Err(ControllableLoadError::LoadPowerOutOfRange(_, 0))));
+    }
+}
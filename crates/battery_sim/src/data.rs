@@ -1,12 +1,28 @@
+use std::fs::File;
+use std::io;
 use std::path::Path;
+use std::sync::Arc;
 use serde::Deserialize;
-use crate::types::{TelemetryPoint, Duration, Power};
+use crate::envelope::OperatingEnvelope;
+use crate::types::{ChannelError, ChannelKind, TelemetryChannel, TelemetryPoint, Duration, Power};
+
+#[derive(Debug, Deserialize)]
+struct EnvelopeRow {
+    min_site_power_kw: f64,
+    max_site_power_kw: f64,
+}
 
 #[derive(Debug, Deserialize)]
 struct CsvRow {
     duration_hour: f64,
     solar_power_kw: f64,
     load_power_kw: f64,
+    #[serde(default = "grid_available_by_default")]
+    grid_available: bool,
+}
+
+fn grid_available_by_default() -> bool {
+    true
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -19,29 +35,205 @@ pub enum CsvParseError {
     InvalidSolarPower { row: usize, value: f64 },
     #[error("Invalid load power value at row {row}: {value}")]
     InvalidLoadPower { row: usize, value: f64 },
+    #[error("Invalid operating envelope at row {row}: min {min}, max {max}")]
+    InvalidEnvelope { row: usize, min: f64, max: f64 },
+    #[error("Column {0} is missing from the CSV header")]
+    MissingColumn(String),
+    #[error("Invalid value in column {column} at row {row}: {value}")]
+    InvalidChannelValue { row: usize, column: String, value: String },
+    #[error("Channel {0} is mapped more than once")]
+    DuplicateChannel(String),
+    #[error("Total {kind:?} power at row {row} is out of range: {total}")]
+    InvalidChannelTotal { row: usize, kind: ChannelKind, total: f64 },
 }
 
 pub fn read_telemetry_csv<P: AsRef<Path>> (path: P) -> Result<Vec<TelemetryPoint>, CsvParseError> {
-    let mut reader = csv::ReaderBuilder::new()
-    .trim(csv::Trim::All)
-    .from_path(path)?;
+    TelemetryCsvReader::from_path(path)?.collect()
+}
+
+/// Reads telemetry one row at a time, so a CSV file can be streamed straight
+/// into a simulation without loading the whole series.
+pub struct TelemetryCsvReader<R> {
+    records: csv::StringRecordsIntoIter<R>,
+    headers: csv::StringRecord,
+    layout: Layout,
+    row_num: usize,
+}
+
+// Which columns the reader turns into telemetry points.
+enum Layout {
+    // The `solar_power_kw` and `load_power_kw` columns, read as a `CsvRow`.
+    SolarLoad,
+    Channels {
+        duration_index: usize,
+        grid_index: Option<usize>,
+        channels: Vec<ChannelColumn>,
+    },
+}
+
+// A CSV column read as a telemetry channel.
+struct ChannelColumn {
+    index: usize,
+    name: Arc<str>,
+    kind: ChannelKind,
+}
+
+impl TelemetryCsvReader<File> {
+    /// Reads the `solar_power_kw` and `load_power_kw` columns as the solar and load
+    /// power of `TelemetryPoint::new`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, CsvParseError> {
+        let (records, headers) = TelemetryCsvReader::open(path)?;
+        for column in ["duration_hour", "solar_power_kw", "load_power_kw"] {
+            position(&headers, column)?;
+        }
+        Ok(TelemetryCsvReader { records, headers, layout: Layout::SolarLoad, row_num: 1 })
+    }
+
+    /// Reads the columns listed in `mapping`, each as a channel named after its column.
+    pub fn with_channels<P: AsRef<Path>>(path: P, mapping: &ChannelMapping) -> Result<Self, CsvParseError> {
+        let (records, headers) = TelemetryCsvReader::open(path)?;
+        let duration_index = position(&headers, "duration_hour")?;
+        let grid_index = position(&headers, "grid_available").ok();
+        let mut channels: Vec<ChannelColumn> = Vec::with_capacity(mapping.columns.len());
+        for (column, kind) in &mapping.columns {
+            if channels.iter().any(|channel| *channel.name == **column) {
+                return Err(CsvParseError::DuplicateChannel(column.clone()));
+            }
+            channels.push(ChannelColumn { index: position(&headers, column)?, name: Arc::from(column.as_str()), kind: *kind });
+        }
+
+        let layout = Layout::Channels { duration_index, grid_index, channels };
+        Ok(TelemetryCsvReader { records, headers, layout, row_num: 1 })
+    }
+
+    fn open<P: AsRef<Path>>(path: P) -> Result<(csv::StringRecordsIntoIter<File>, csv::StringRecord), CsvParseError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+        let headers = reader.headers()?.clone();
+        Ok((reader.into_records(), headers))
+    }
+}
+
+fn position(headers: &csv::StringRecord, column: &str) -> Result<usize, CsvParseError> {
+    headers.iter().position(|header| header == column)
+        .ok_or_else(|| CsvParseError::MissingColumn(column.to_string()))
+}
+
+impl<R> TelemetryCsvReader<R> {
+    fn parse_record(&self, record: &csv::StringRecord, row: usize) -> Result<TelemetryPoint, CsvParseError> {
+        let Layout::Channels { duration_index, grid_index, channels } = &self.layout else {
+            return parse_solar_load(record.deserialize(Some(&self.headers))?, row);
+        };
+        let field = |index: usize| record.get(index).unwrap_or("");
+        let invalid = |index: usize, value: String| CsvParseError::InvalidChannelValue {
+            row,
+            column: self.headers[index].to_string(),
+            value,
+        };
+        let number = |index: usize| -> Result<f64, CsvParseError> {
+            field(index).parse().map_err(|_| invalid(index, field(index).to_string()))
+        };
+
+        let duration = Duration::from_hour(number(*duration_index)?)
+            .map_err(|value| CsvParseError::InvalidDuration { row, value })?;
+        let channels = channels.iter()
+            .map(|channel| {
+                let power = Power::from_kw(number(channel.index)?)
+                    .map_err(|value| invalid(channel.index, value.to_string()))?;
+                Ok(TelemetryChannel::new(channel.name.clone(), channel.kind, power))
+            })
+            .collect::<Result<Vec<_>, CsvParseError>>()?;
+        let grid_available = match grid_index {
+            Some(index) => field(*index).parse().map_err(|_| invalid(*index, field(*index).to_string()))?,
+            None => grid_available_by_default(),
+        };
 
-    let mut telemetry = Vec::new();
-    for (idx, result) in reader.deserialize().enumerate() {
-        let row: CsvRow = result?;
-        let row_num = idx + 2;
+        let point = TelemetryPoint::from_channels(duration, channels).map_err(|e| match e {
+            ChannelError::DuplicateName(name) => CsvParseError::DuplicateChannel(name.to_string()),
+            ChannelError::TotalOutOfRange { kind, total } => CsvParseError::InvalidChannelTotal { row, kind, total },
+        })?;
+        Ok(point.with_grid_available(grid_available))
+    }
+}
 
-        let duration: Duration = Duration::from_hour(row.duration_hour)
-            .map_err(|value: f64| CsvParseError::InvalidDuration { row: row_num, value })?;
-        let load_power: Power = Power::from_kw(row.load_power_kw)
-            .map_err(|value: f64| CsvParseError::InvalidLoadPower { row: row_num, value })?;
-        let solar_power: Power = Power::from_kw(row.solar_power_kw)
-            .map_err(|value: f64| CsvParseError::InvalidSolarPower { row: row_num, value })?;
+fn parse_solar_load(row: CsvRow, row_num: usize) -> Result<TelemetryPoint, CsvParseError> {
+    let duration = Duration::from_hour(row.duration_hour)
+        .map_err(|value| CsvParseError::InvalidDuration { row: row_num, value })?;
+    let solar_power = Power::from_kw(row.solar_power_kw)
+        .map_err(|value| CsvParseError::InvalidSolarPower { row: row_num, value })?;
+    let load_power = Power::from_kw(row.load_power_kw)
+        .map_err(|value| CsvParseError::InvalidLoadPower { row: row_num, value })?;
+    Ok(TelemetryPoint::new(duration, solar_power, load_power).with_grid_available(row.grid_available))
+}
 
-        telemetry.push(TelemetryPoint::new(duration, solar_power, load_power));
+impl<R: io::Read> Iterator for TelemetryCsvReader<R> {
+    type Item = Result<TelemetryPoint, CsvParseError>;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.records.next()?;
+        self.row_num += 1;
+        Some(result.map_err(CsvParseError::from).and_then(|record| self.parse_record(&record, self.row_num)))
     }
-    Ok(telemetry)
+}
+
+/// Reads one operating envelope per row from the `min_site_power_kw` and
+/// `max_site_power_kw` columns. Other columns are ignored, so the envelope can sit in
+/// the telemetry file itself.
+pub fn read_envelope_csv<P: AsRef<Path>>(path: P) -> Result<Vec<OperatingEnvelope>, CsvParseError> {
+    let reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    reader.into_deserialize::<EnvelopeRow>().enumerate()
+        .map(|(i, row)| {
+            let row_num = i + 2;
+            let row = row?;
+            let invalid = || CsvParseError::InvalidEnvelope {
+                row: row_num,
+                min: row.min_site_power_kw,
+                max: row.max_site_power_kw,
+            };
+            let min = Power::from_kw(row.min_site_power_kw).map_err(|_| invalid())?;
+            let max = Power::from_kw(row.max_site_power_kw).map_err(|_| invalid())?;
+            OperatingEnvelope::new(min, max).map_err(|_| invalid())
+        })
+        .collect()
+}
+
+/// Which CSV columns hold which telemetry channels. Each channel is named after its
+/// column. `duration_hour` and the optional `grid_available` column are read as usual.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelMapping {
+    columns: Vec<(String, ChannelKind)>,
+}
+
+impl ChannelMapping {
+    pub fn new() -> Self {
+        ChannelMapping::default()
+    }
+
+    pub fn with_generation(mut self, column: impl Into<String>) -> Self {
+        self.columns.push((column.into(), ChannelKind::Generation));
+        self
+    }
+
+    pub fn with_consumption(mut self, column: impl Into<String>) -> Self {
+        self.columns.push((column.into(), ChannelKind::Consumption));
+        self
+    }
+
+    pub fn columns(&self) -> &[(String, ChannelKind)] {
+        &self.columns
+    }
+}
+
+/// Reads telemetry with named channels from the columns listed in `mapping`.
+pub fn read_channel_telemetry_csv<P: AsRef<Path>>(
+    path: P,
+    mapping: &ChannelMapping,
+) -> Result<Vec<TelemetryPoint>, CsvParseError> {
+    TelemetryCsvReader::with_channels(path, mapping)?.collect()
 }
 
 #[cfg(test)]
@@ -59,4 +251,92 @@ mod tests {
         assert_eq!(telemetry[4].duration(), hour!(0.5));
         assert_eq!(telemetry[4].load_power(), kw!(4.5));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_telemetry_csv_reader_streams_into_simulation() {
+        use crate::battery::Battery;
+        use crate::simulation::LoadFollowingSimulation;
+        use crate::types::{AsEfficiency, Energy};
+        use crate::kwh;
+
+        let battery = Battery::new(kwh!(10.0), kw!(5.0), 0.81.fraction()).expect("valid battery");
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = TelemetryCsvReader::from_path("data/test_data.csv")
+            .expect("Should open telemetry")
+            .map(|point| point.expect("Should parse row"));
+
+        let steps = LoadFollowingSimulation::new(telemetry, battery, initial_state).count();
+        assert_eq!(steps, 5);
+    }
+
+    #[test]
+    fn test_read_telemetry_csv_grid_availability() {
+        let telemetry = read_telemetry_csv("data/outage_test_data.csv").expect("Should read telemetry");
+        let available: Vec<bool> = telemetry.iter().map(|point| point.grid_available()).collect();
+        assert_eq!(available, vec![true, false, false, true]);
+        // Files without the column have the grid available throughout.
+        let telemetry = read_telemetry_csv("data/test_data.csv").expect("Should read telemetry");
+        assert!(telemetry.iter().all(|point| point.grid_available()));
+    }
+
+    #[test]
+    fn test_read_channel_telemetry_csv() {
+        let mapping = ChannelMapping::new()
+            .with_generation("pv_east_kw")
+            .with_generation("pv_west_kw")
+            .with_generation("wind_kw")
+            .with_consumption("critical_kw")
+            .with_consumption("non_critical_kw");
+
+        let telemetry = read_channel_telemetry_csv("data/channel_test_data.csv", &mapping)
+            .expect("Should read telemetry");
+
+        assert_eq!(telemetry.len(), 3);
+        assert_eq!(telemetry[0].duration(), hour!(0.5));
+        assert_eq!(telemetry[0].solar_power(), kw!(4.0));
+        assert_eq!(telemetry[0].load_power(), kw!(1.5));
+        assert_eq!(telemetry[1].excess_pv(), kw!(-1.0));
+        assert_eq!(telemetry[2].channel_power("wind_kw"), Some(kw!(2.0)));
+        assert!(!telemetry[2].grid_available());
+    }
+
+    #[test]
+    fn test_read_channel_telemetry_csv_missing_column() {
+        let mapping = ChannelMapping::new().with_generation("battery_kw");
+        let result = read_channel_telemetry_csv("data/channel_test_data.csv", &mapping);
+        assert!(matches!(result, Err(CsvParseError::MissingColumn(column)) if column == "battery_kw"));
+
+        let mapping = ChannelMapping::new().with_generation("wind_kw").with_consumption("wind_kw");
+        let result = read_channel_telemetry_csv("data/channel_test_data.csv", &mapping);
+        assert!(matches!(result, Err(CsvParseError::DuplicateChannel(name)) if name == "wind_kw"));
+    }
+
+    #[test]
+    fn test_read_telemetry_csv_names_solar_and_load_channels() {
+        let telemetry = read_telemetry_csv("data/test_data.csv").expect("Should read telemetry");
+        assert_eq!(telemetry[0].channel_power("solar"), Some(kw!(5.)));
+        assert_eq!(telemetry[0].channel_power("load"), Some(kw!(1.0)));
+        assert_eq!(telemetry[0].channels().len(), 2);
+    }
+
+    #[test]
+    fn test_read_telemetry_csv_rejects_invalid_power() {
+        let result = read_telemetry_csv("data/invalid_power_test_data.csv");
+        assert!(matches!(result, Err(CsvParseError::InvalidSolarPower { row: 3, value }) if value == 2e6));
+
+        let mapping = ChannelMapping::new().with_generation("solar_power_kw");
+        let result = read_channel_telemetry_csv("data/invalid_power_test_data.csv", &mapping);
+        assert!(matches!(result, Err(CsvParseError::InvalidChannelValue { row: 3, column, .. }) if column == "solar_power_kw"));
+    }
+
+    #[test]
+    fn test_read_envelope_csv_alongside_telemetry() {
+        let telemetry = read_telemetry_csv("data/envelope_test_data.csv").expect("Should read telemetry");
+        let envelopes = read_envelope_csv("data/envelope_test_data.csv").expect("Should read envelopes");
+
+        assert_eq!(envelopes.len(), telemetry.len());
+        assert_eq!(envelopes[0].min_site_power(), kw!(-1.5));
+        assert_eq!(envelopes[2].max_site_power(), kw!(2.0));
+        assert!(matches!(read_envelope_csv("data/test_data.csv"), Err(CsvParseError::Csv(_))));
+    }
+}
@@ -0,0 +1,334 @@
+use crate::battery::{BatteryModel, BatteryState, BatteryError, BatteryStateError};
+use crate::types::{Energy, Power, TelemetryPoint};
+
+/// Resolution of the state of charge and power grids searched by the optimiser.
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicProgrammingConfig {
+    soc_points: usize,   // number of grid points between empty and full, inclusive
+    power_levels: usize, // number of non-zero power levels in each direction
+}
+
+impl DynamicProgrammingConfig {
+    pub fn new(soc_points: usize, power_levels: usize) -> Result<Self, DynamicProgrammingError> {
+        if soc_points < 2 {
+            return Err(DynamicProgrammingError::TooFewSocPoints(soc_points));
+        }
+        if power_levels < 1 {
+            return Err(DynamicProgrammingError::TooFewPowerLevels);
+        }
+        Ok(DynamicProgrammingConfig { soc_points, power_levels })
+    }
+
+    pub fn soc_points(&self) -> usize {
+        self.soc_points
+    }
+
+    pub fn power_levels(&self) -> usize {
+        self.power_levels
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DynamicProgrammingError {
+    #[error("The state of charge grid needs at least 2 points, got {0}.")]
+    TooFewSocPoints(usize),
+    #[error("At least one power level is required in each direction.")]
+    TooFewPowerLevels,
+    #[error("Failed to place a grid state of charge in the battery.")]
+    ErrorBuildingGrid(#[source] BatteryStateError),
+    #[error("No feasible set point on step {0}.")]
+    NoFeasibleSetPoint(usize),
+    #[error("Applying the optimal dispatch failed on step {1}.")]
+    ErrorApplyingDispatch(#[source] BatteryError, usize),
+}
+
+/// The optimal set point for every step and every state of charge on the grid.
+#[derive(Debug, Clone)]
+pub struct DispatchPolicy {
+    soc_grid: Vec<Energy>,
+    set_points: Vec<Vec<Power>>, // indexed by [step][soc grid index]
+}
+
+impl DispatchPolicy {
+    pub fn soc_grid(&self) -> &[Energy] {
+        &self.soc_grid
+    }
+
+    pub fn steps(&self) -> usize {
+        self.set_points.len()
+    }
+
+    /// Set point for `step` at the grid point nearest to `state_of_charge`.
+    pub fn set_point(&self, step: usize, state_of_charge: Energy) -> Power {
+        let nearest = self.soc_grid.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                let distance_a = (a.as_kwh() - state_of_charge.as_kwh()).abs();
+                let distance_b = (b.as_kwh() - state_of_charge.as_kwh()).abs();
+                distance_a.total_cmp(&distance_b)
+            })
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        self.set_points[step][nearest]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DynamicProgrammingResult {
+    policy: DispatchPolicy,
+    states: Vec<BatteryState>,
+    cost: f64,
+}
+
+impl DynamicProgrammingResult {
+    pub fn policy(&self) -> &DispatchPolicy {
+        &self.policy
+    }
+
+    /// The initial state followed by the state after every telemetry point,
+    /// in the same shape as `simulate_load_following`.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    pub fn into_states(self) -> Vec<BatteryState> {
+        self.states
+    }
+
+    /// Total stage cost along the returned trajectory.
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+}
+
+/// Finds the dispatch minimising the sum of `step_cost` over the telemetry by backward
+/// induction on a discretised state of charge.
+///
+/// `step_cost` receives the step index, its telemetry and the battery state the step
+/// produced, so it can price grid energy, penalise aging or reject set points by
+/// returning `f64::INFINITY`. The battery is only ever moved through `BatteryModel::step`,
+/// so nonlinear models are handled exactly between grid points.
+pub fn optimise_dispatch_dp<M, C>(
+    telemetry_points: &[TelemetryPoint],
+    battery: &M,
+    initial_state: BatteryState,
+    config: DynamicProgrammingConfig,
+    step_cost: C,
+) -> Result<DynamicProgrammingResult, DynamicProgrammingError>
+where
+    M: BatteryModel,
+    C: Fn(usize, &TelemetryPoint, &BatteryState) -> f64,
+{
+    let soc_grid = build_soc_grid(battery, config.soc_points);
+    let grid_states = soc_grid.iter()
+        .map(|&soc| battery.init_state(soc, Power::zero()))
+        .collect::<Result<Vec<BatteryState>, BatteryStateError>>()
+        .map_err(DynamicProgrammingError::ErrorBuildingGrid)?;
+    let candidates = build_power_levels(battery, config.power_levels);
+
+    // Backward induction: values[t] is the optimal cost-to-go from step t on the grid.
+    let steps = telemetry_points.len();
+    let mut values = vec![vec![0.0; soc_grid.len()]; steps + 1];
+    let mut set_points = vec![vec![Power::zero(); soc_grid.len()]; steps];
+
+    for (t, point) in telemetry_points.iter().enumerate().rev() {
+        for (i, state) in grid_states.iter().enumerate() {
+            let (power, value) = best_set_point(
+                battery, state, t, point, &candidates, &soc_grid, &values[t + 1], &step_cost,
+            ).ok_or(DynamicProgrammingError::NoFeasibleSetPoint(t))?;
+            set_points[t][i] = power;
+            values[t][i] = value;
+        }
+    }
+
+    // Forward pass from the actual initial state, re-solving the one-step problem against
+    // the interpolated cost-to-go rather than snapping to the nearest grid point.
+    let mut states = Vec::with_capacity(steps + 1);
+    states.push(initial_state);
+    let mut cost = 0.0;
+    for (t, point) in telemetry_points.iter().enumerate() {
+        let (power, _) = best_set_point(
+            battery, &states[t], t, point, &candidates, &soc_grid, &values[t + 1], &step_cost,
+        ).ok_or(DynamicProgrammingError::NoFeasibleSetPoint(t))?;
+        let new_state = battery.step(&states[t], power, point.duration())
+            .map_err(|e| DynamicProgrammingError::ErrorApplyingDispatch(e, t))?;
+        cost += step_cost(t, point, &new_state);
+        states.push(new_state);
+    }
+
+    Ok(DynamicProgrammingResult {
+        policy: DispatchPolicy { soc_grid, set_points },
+        states,
+        cost,
+    })
+}
+
+fn build_soc_grid<M: BatteryModel>(battery: &M, soc_points: usize) -> Vec<Energy> {
+    let capacity = battery.capacity().as_kwh();
+    let last = (soc_points - 1) as f64;
+    (0..soc_points)
+        .map(|i| Energy::from_kwh(capacity * i as f64 / last).expect("grid energy within capacity"))
+        .collect()
+}
+
+fn build_power_levels<M: BatteryModel>(battery: &M, power_levels: usize) -> Vec<Power> {
+    let max_power = battery.max_power().as_kw();
+    let levels = power_levels as i64;
+    (-levels..=levels)
+        .map(|k| Power::from_kw(max_power * k as f64 / levels as f64).expect("grid power within max power"))
+        .collect()
+}
+
+// Linear interpolation of the cost-to-go between grid points.
+fn interpolate(soc_grid: &[Energy], values: &[f64], state_of_charge: Energy) -> f64 {
+    let capacity = soc_grid[soc_grid.len() - 1].as_kwh();
+    let position = (state_of_charge.as_kwh() / capacity * (soc_grid.len() - 1) as f64)
+        .clamp(0.0, (soc_grid.len() - 1) as f64);
+    let lower = (position.floor() as usize).min(soc_grid.len() - 2);
+    let weight = position - lower as f64;
+    values[lower] * (1.0 - weight) + values[lower + 1] * weight
+}
+
+#[allow(clippy::too_many_arguments)]
+fn best_set_point<M, C>(
+    battery: &M,
+    state: &BatteryState,
+    step: usize,
+    telemetry_point: &TelemetryPoint,
+    candidates: &[Power],
+    soc_grid: &[Energy],
+    next_values: &[f64],
+    step_cost: &C,
+) -> Option<(Power, f64)>
+where
+    M: BatteryModel,
+    C: Fn(usize, &TelemetryPoint, &BatteryState) -> f64,
+{
+    candidates.iter()
+        .filter_map(|&power| {
+            let next_state = battery.step(state, power, telemetry_point.duration()).ok()?;
+            let value = step_cost(step, telemetry_point, &next_state)
+                + interpolate(soc_grid, next_values, next_state.state_of_charge());
+            value.is_finite().then_some((power, value))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery::Battery;
+    use crate::optimiser::{optimise_dispatch, Objective};
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-6;
+
+    fn lossless_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction())
+            .expect("battery should be valid")
+    }
+
+    #[test]
+    fn test_config_rejects_too_few_points() {
+        assert!(matches!(
+            DynamicProgrammingConfig::new(1, 5),
+            Err(DynamicProgrammingError::TooFewSocPoints(1))
+        ));
+        assert!(matches!(
+            DynamicProgrammingConfig::new(11, 0),
+            Err(DynamicProgrammingError::TooFewPowerLevels)
+        ));
+    }
+
+    #[test]
+    fn test_dp_empty_telemetry() {
+        let battery = lossless_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let config = DynamicProgrammingConfig::new(11, 5).expect("valid config");
+
+        let result = optimise_dispatch_dp(&[], &battery, initial_state, config, |_, _, _| 0.0)
+            .expect("optimisation should succeed");
+
+        assert_eq!(result.states().len(), 1);
+        assert_eq!(result.policy().steps(), 0);
+        assert_abs_diff_eq!(result.cost(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_dp_arbitrages_energy_cost() {
+        let battery = lossless_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let config = DynamicProgrammingConfig::new(11, 5).expect("valid config");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+        ];
+        let objective = Objective::EnergyCost {
+            import_price: vec![0.10, 0.50],
+            export_price: vec![0.0, 0.0],
+        };
+
+        let result = optimise_dispatch_dp(
+            &telemetry, &battery, initial_state, config, |i, p, s| objective.step_cost(i, p, s),
+        ).expect("optimisation should succeed");
+
+        // Buy 4 kWh in the cheap step, 2 kWh of which is stored for the expensive step.
+        let states = result.states();
+        assert_abs_diff_eq!(states[1].power_kw(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(states[2].power_kw(), -2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.cost(), 0.4, epsilon = EPSILON);
+        assert_eq!(result.policy().set_point(0, Energy::zero()), kw!(2.0));
+    }
+
+    #[test]
+    fn test_dp_matches_lp_benchmark() {
+        let battery = lossless_battery();
+        let initial_state = battery.init_state(kwh!(2.0), Power::zero()).expect("valid state");
+        let config = DynamicProgrammingConfig::new(21, 10).expect("valid config");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(3.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(4.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(4.0)),
+        ];
+        let objective = Objective::GridImport;
+
+        let dp = optimise_dispatch_dp(
+            &telemetry, &battery, initial_state, config, |i, p, s| objective.step_cost(i, p, s),
+        ).expect("optimisation should succeed");
+        let lp = optimise_dispatch(&telemetry, &battery, initial_state, &objective)
+            .expect("optimisation should succeed");
+        let lp_cost: f64 = telemetry.iter().zip(&lp[1..]).enumerate()
+            .map(|(i, (p, s))| objective.step_cost(i, p, s))
+            .sum();
+
+        assert_abs_diff_eq!(dp.cost(), lp_cost, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_dp_respects_nonlinear_aging_cost() {
+        let battery = lossless_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let config = DynamicProgrammingConfig::new(11, 5).expect("valid config");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+        ];
+        let objective = Objective::EnergyCost {
+            import_price: vec![0.10, 0.50],
+            export_price: vec![0.0, 0.0],
+        };
+        // A quadratic wear cost that outweighs the 0.40 per kWh price spread.
+        let aging = |s: &BatteryState| 0.25 * s.power_kw() * s.power_kw();
+
+        let result = optimise_dispatch_dp(
+            &telemetry, &battery, initial_state, config,
+            |i, p, s| objective.step_cost(i, p, s) + aging(s),
+        ).expect("optimisation should succeed");
+
+        // Shifting 1 kWh saves 0.40 but wears 0.50, so the battery should stay idle.
+        assert_abs_diff_eq!(result.states()[1].power_kw(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.cost(), 1.2, epsilon = EPSILON);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::battery::{BatteryError, BatteryModel, BatteryState};
+use crate::types::{Energy, Power, TelemetryPoint};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error("Envelope minimum {min} kW is above its maximum {max} kW.")]
+    InvalidEnvelope { min: f64, max: f64 },
+    #[error("The envelope has {actual} steps, expected {expected}.")]
+    LengthMismatch { expected: usize, actual: usize },
+    #[error("Applying the set point failed on step {1}.")]
+    ErrorApplyingSetPoint(#[source] BatteryError, usize),
+}
+
+/// Limits on the site's grid power for one step, positive when importing. A negative
+/// minimum is an export limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OperatingEnvelope {
+    min_site_power: Power,
+    max_site_power: Power,
+}
+
+impl OperatingEnvelope {
+    pub fn new(min_site_power: Power, max_site_power: Power) -> Result<Self, EnvelopeError> {
+        if min_site_power > max_site_power {
+            return Err(EnvelopeError::InvalidEnvelope { min: min_site_power.as_kw(), max: max_site_power.as_kw() });
+        }
+        Ok(OperatingEnvelope { min_site_power, max_site_power })
+    }
+
+    pub fn min_site_power(&self) -> Power {
+        self.min_site_power
+    }
+
+    pub fn max_site_power(&self) -> Power {
+        self.max_site_power
+    }
+
+    /// The battery set point moved into the range that keeps the site within the envelope.
+    pub fn constrain(&self, set_point: Power, telemetry_point: &TelemetryPoint) -> Power {
+        let excess = telemetry_point.excess_pv();
+        set_point.min(excess + self.max_site_power).max(excess + self.min_site_power)
+    }
+}
+
+/// Battery states and the energy the envelope cost the site.
+#[derive(Debug, Clone)]
+pub struct EnvelopeResult {
+    states: Vec<BatteryState>,
+    grid_power: Vec<Power>,
+    curtailed: Vec<Energy>,
+    unserved: Vec<Energy>,
+}
+
+impl EnvelopeResult {
+    /// The initial state followed by the battery state after every step.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// Power drawn from the grid on every step, within the envelope wherever shedding
+    /// load or curtailing PV could bring it there.
+    pub fn grid_power(&self) -> &[Power] {
+        &self.grid_power
+    }
+
+    /// PV curtailed on every step to stay above the envelope minimum.
+    pub fn curtailed(&self) -> &[Energy] {
+        &self.curtailed
+    }
+
+    /// Load shed on every step to stay below the envelope maximum.
+    pub fn unserved(&self) -> &[Energy] {
+        &self.unserved
+    }
+
+    pub fn total_curtailed(&self) -> Energy {
+        self.curtailed.iter().fold(Energy::zero(), |total, &energy| total + energy)
+    }
+
+    pub fn total_unserved(&self) -> Energy {
+        self.unserved.iter().fold(Energy::zero(), |total, &energy| total + energy)
+    }
+}
+
+/// Runs any dispatch strategy inside a per-step operating envelope.
+///
+/// `set_point` is asked for the battery power on every step given the state so far,
+/// so load following, a `DispatchPolicy` or a precomputed schedule can all be used.
+/// The set point is constrained to the envelope before it reaches the battery. Where
+/// the battery cannot keep the site inside the envelope, PV is curtailed or load is
+/// shed and the energy is reported against the envelope, up to the PV and load there
+/// were on the step.
+///
+/// This is the supported route for holding a strategy to an envelope;
+/// `LoadFollowingSimulation` does not take one. Strategies that plan ahead should also
+/// see the envelope while planning, as `optimise_dispatch_within_envelope` and
+/// `simulate_mpc_within_envelope` do, since this only corrects each step as it comes.
+pub fn simulate_with_envelope<M, S>(
+    telemetry_points: &[TelemetryPoint],
+    model: &M,
+    initial_state: BatteryState,
+    envelopes: &[OperatingEnvelope],
+    mut set_point: S,
+) -> Result<EnvelopeResult, EnvelopeError>
+where
+    M: BatteryModel,
+    S: FnMut(usize, &TelemetryPoint, &BatteryState) -> Power,
+{
+    if envelopes.len() != telemetry_points.len() {
+        return Err(EnvelopeError::LengthMismatch { expected: telemetry_points.len(), actual: envelopes.len() });
+    }
+
+    let mut states = Vec::with_capacity(telemetry_points.len() + 1);
+    states.push(initial_state);
+    let mut grid_power = Vec::with_capacity(telemetry_points.len());
+    let mut curtailed = Vec::with_capacity(telemetry_points.len());
+    let mut unserved = Vec::with_capacity(telemetry_points.len());
+    let mut state = initial_state;
+
+    for (step, (point, envelope)) in telemetry_points.iter().zip(envelopes).enumerate() {
+        let requested = envelope.constrain(set_point(step, point, &state), point);
+        state = model.step(&state, requested, point.duration())
+            .map_err(|e| EnvelopeError::ErrorApplyingSetPoint(e, step))?;
+        states.push(state);
+
+        let grid = state.power() - point.excess_pv();
+        let shed = (grid - envelope.max_site_power).max(Power::zero()).min(point.load_power().max(Power::zero()));
+        let curtail = (envelope.min_site_power - grid).max(Power::zero()).min(point.solar_power().max(Power::zero()));
+        unserved.push(shed * point.duration());
+        curtailed.push(curtail * point.duration());
+        grid_power.push(grid - shed + curtail);
+    }
+
+    Ok(EnvelopeResult { states, grid_power, curtailed, unserved })
+}
+
+/// Load following inside a per-step operating envelope.
+pub fn simulate_load_following_with_envelope<M: BatteryModel>(
+    telemetry_points: &[TelemetryPoint],
+    model: &M,
+    initial_state: BatteryState,
+    envelopes: &[OperatingEnvelope],
+) -> Result<EnvelopeResult, EnvelopeError> {
+    simulate_with_envelope(telemetry_points, model, initial_state, envelopes, |_, point, _| point.excess_pv())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battery::Battery;
+    use crate::optimiser::{optimise_dispatch, optimise_dispatch_within_envelope, Objective};
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    fn envelope(min: Power, max: Power) -> OperatingEnvelope {
+        OperatingEnvelope::new(min, max).expect("valid envelope")
+    }
+
+    #[test]
+    fn test_export_limit_curtails_what_the_battery_cannot_take() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(8.0), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)); 2];
+        let envelopes = vec![envelope(kw!(-1.0), kw!(10.0)); 2];
+
+        let result = simulate_load_following_with_envelope(&telemetry, &battery, initial_state, &envelopes)
+            .expect("simulation should succeed");
+
+        // 2 kWh fills the battery, 1 kW is exported and the rest is curtailed.
+        let grid: Vec<f64> = result.grid_power().iter().map(|p| p.as_kw()).collect();
+        assert_eq!(grid, vec![-1.0, -1.0]);
+        assert_abs_diff_eq!(result.curtailed()[0].as_kwh(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.curtailed()[1].as_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.total_unserved().as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_import_limit_sheds_load() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(1.0), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(4.0))];
+        let envelopes = vec![envelope(kw!(-5.0), kw!(2.0))];
+
+        let result = simulate_load_following_with_envelope(&telemetry, &battery, initial_state, &envelopes)
+            .expect("simulation should succeed");
+
+        assert_abs_diff_eq!(result.grid_power()[0].as_kw(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.total_unserved().as_kwh(), 1.0, epsilon = EPSILON);
+    }
+
+    // Cheap power on the first two steps ahead of an evening load that cannot import.
+    fn evening_peak() -> (Vec<TelemetryPoint>, Objective, Vec<OperatingEnvelope>) {
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(5.0)),
+        ];
+        let objective = Objective::EnergyCost { import_price: vec![0.1, 0.2, 1.0], export_price: vec![0.0; 3] };
+        let envelopes = vec![envelope(kw!(-3.0), kw!(1.0)), envelope(kw!(-3.0), kw!(5.0)), envelope(kw!(-3.0), kw!(0.0))];
+        (telemetry, objective, envelopes)
+    }
+
+    #[test]
+    fn test_envelope_cuts_schedule_planned_without_it() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let (telemetry, objective, envelopes) = evening_peak();
+        let schedule = optimise_dispatch(&telemetry, &battery, initial_state, &objective)
+            .expect("optimisation should succeed");
+
+        let result = simulate_with_envelope(
+            &telemetry, &battery, initial_state, &envelopes, |step, _, _| schedule[step + 1].power(),
+        ).expect("simulation should succeed");
+
+        // The planned 5 kW precharge is cut to 1 kW, leaving too little for the evening.
+        assert_abs_diff_eq!(schedule[1].power_kw(), 5.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(result.states()[1].power_kw(), 1.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(result.total_unserved().as_kwh(), 4.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_envelope_aware_optimisation_serves_load() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let (telemetry, objective, envelopes) = evening_peak();
+        let schedule = optimise_dispatch_within_envelope(&telemetry, &battery, initial_state, &objective, &envelopes)
+            .expect("optimisation should succeed");
+
+        let result = simulate_with_envelope(
+            &telemetry, &battery, initial_state, &envelopes, |step, _, _| schedule[step + 1].power(),
+        ).expect("simulation should succeed");
+
+        // The plan tops up on the dearer second step once the first is capped.
+        assert_abs_diff_eq!(schedule[1].power_kw(), 1.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(schedule[2].power_kw(), 4.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(result.total_unserved().as_kwh(), 0.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(result.grid_power()[2].as_kw(), 0.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_curtailed_and_unserved_capped_at_site_flows() {
+        let battery = test_battery();
+        let full = battery.init_state(kwh!(10.0), Power::zero()).expect("valid state");
+        let empty = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0))];
+
+        // A minimum import a full battery cannot absorb, with no PV to curtail.
+        let result = simulate_load_following_with_envelope(&telemetry, &battery, full, &[envelope(kw!(1.0), kw!(5.0))])
+            .expect("simulation should succeed");
+        assert_abs_diff_eq!(result.total_curtailed().as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.grid_power()[0].as_kw(), 0.0, epsilon = EPSILON);
+
+        // A minimum export an empty battery cannot supply, with no load to shed.
+        let result = simulate_load_following_with_envelope(&telemetry, &battery, empty, &[envelope(kw!(-5.0), kw!(-1.0))])
+            .expect("simulation should succeed");
+        assert_abs_diff_eq!(result.total_unserved().as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.grid_power()[0].as_kw(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_rejects_invalid_envelopes() {
+        assert!(matches!(
+            OperatingEnvelope::new(kw!(2.0), kw!(1.0)),
+            Err(EnvelopeError::InvalidEnvelope { .. })
+        ));
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0))];
+        assert!(matches!(
+            simulate_load_following_with_envelope(&telemetry, &battery, initial_state, &[]),
+            Err(EnvelopeError::LengthMismatch { expected: 1, actual: 0 })
+        ));
+        assert!(matches!(
+            optimise_dispatch_within_envelope(&telemetry, &battery, initial_state, &Objective::GridImport, &[]),
+            Err(crate::optimiser::OptimiserError::EnvelopeLengthMismatch(0, 1))
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::battery::{Battery, BatteryError, BatteryState};
+use crate::types::{Energy, Power, TelemetryPoint};
+
+#[derive(Debug, thiserror::Error)]
+pub enum EvError {
+    #[error("Session departs at {departure} hours, before it arrives at {arrival} hours.")]
+    DepartureBeforeArrival { arrival: f64, departure: f64 },
+    #[error("Session arrival time {0} hours must be finite and non-negative.")]
+    InvalidArrival(f64),
+    #[error("Session required energy must not be negative.")]
+    NegativeRequiredEnergy,
+    #[error("Session max charge power must be greater than 0.")]
+    NonPositiveMaxPower,
+    #[error("Charger power {0} kW on step {1} is out of range.")]
+    ChargerPowerOutOfRange(f64, usize),
+    #[error("Simulating EV charging failed on step {1}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize),
+}
+
+/// A vehicle plugged in between two times, measured in hours from the start of the telemetry.
+#[derive(Debug, Clone)]
+pub struct ChargingSession {
+    arrival_hours: f64,
+    departure_hours: f64,
+    required_energy: Energy,
+    max_power: Power,
+}
+
+impl ChargingSession {
+    pub fn new(
+        arrival_hours: f64,
+        departure_hours: f64,
+        required_energy: Energy,
+        max_power: Power,
+    ) -> Result<Self, EvError> {
+        if !(arrival_hours.is_finite() && arrival_hours >= 0.0) {
+            return Err(EvError::InvalidArrival(arrival_hours));
+        }
+        if departure_hours.is_nan() || departure_hours <= arrival_hours {
+            return Err(EvError::DepartureBeforeArrival { arrival: arrival_hours, departure: departure_hours });
+        }
+        if required_energy < Energy::zero() {
+            return Err(EvError::NegativeRequiredEnergy);
+        }
+        if max_power <= Power::zero() {
+            return Err(EvError::NonPositiveMaxPower);
+        }
+        Ok(ChargingSession { arrival_hours, departure_hours, required_energy, max_power })
+    }
+
+    pub fn arrival_hours(&self) -> f64 {
+        self.arrival_hours
+    }
+
+    pub fn departure_hours(&self) -> f64 {
+        self.departure_hours
+    }
+
+    pub fn required_energy(&self) -> Energy {
+        self.required_energy
+    }
+
+    pub fn max_power(&self) -> Power {
+        self.max_power
+    }
+
+    // Hours of the step from `start` to `end` the vehicle is plugged in for.
+    fn plugged_in_hours(&self, start: f64, end: f64) -> f64 {
+        (end.min(self.departure_hours) - start.max(self.arrival_hours)).max(0.0)
+    }
+}
+
+/// How the charger decides how much to charge each plugged-in vehicle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargingMode {
+    /// Charge at full rate from arrival until the session is satisfied.
+    Immediate,
+    /// Charge only from PV the site does not use itself.
+    SolarOnly,
+    /// Charge from surplus PV, and from anywhere else only as much as is needed to
+    /// still finish by departure at full rate.
+    DeadlineAware,
+}
+
+/// Battery and charger results of an EV simulation.
+#[derive(Debug, Clone)]
+pub struct EvSimulationResult {
+    states: Vec<BatteryState>,
+    ev_power: Vec<Power>,
+    grid_power: Vec<Power>,
+    delivered: Vec<Energy>,
+    unmet: Vec<Energy>,
+    baseline_peak_import: Power,
+}
+
+impl EvSimulationResult {
+    /// The initial state followed by the battery state after every step.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// Total charger power on every step, averaged over the step.
+    pub fn ev_power(&self) -> &[Power] {
+        &self.ev_power
+    }
+
+    /// Power drawn from the grid on every step, negative when exporting.
+    pub fn grid_power(&self) -> &[Power] {
+        &self.grid_power
+    }
+
+    /// Energy delivered to each session, in the order given.
+    pub fn delivered(&self) -> &[Energy] {
+        &self.delivered
+    }
+
+    /// Energy each session still needed when it departed, in the order given.
+    pub fn unmet(&self) -> &[Energy] {
+        &self.unmet
+    }
+
+    pub fn total_unmet(&self) -> Energy {
+        self.unmet.iter().fold(Energy::zero(), |total, &unmet| total + unmet)
+    }
+
+    pub fn peak_import(&self) -> Power {
+        self.grid_power.iter().fold(Power::zero(), |peak, &power| if power > peak { power } else { peak })
+    }
+
+    /// Peak import of the same site and battery without any EV charging.
+    pub fn baseline_peak_import(&self) -> Power {
+        self.baseline_peak_import
+    }
+
+    /// How much EV charging raised the peak import.
+    pub fn peak_import_increase(&self) -> Power {
+        self.peak_import() - self.baseline_peak_import
+    }
+}
+
+/// Simulates EV charging sessions as an extra load on a site with PV and a battery.
+///
+/// The charger acts first on every step, then the battery load-follows what is left of
+/// the PV surplus or deficit, so the battery can also discharge into the vehicles.
+/// Sessions are served in order of departure.
+///
+/// This is a greedy heuristic, not a co-optimisation of the charger, battery and PV: the
+/// charger never looks ahead at the battery, and `DeadlineAware` does not count energy
+/// stored in the battery as available surplus.
+pub fn simulate_with_ev(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    sessions: &[ChargingSession],
+    mode: ChargingMode,
+) -> Result<EvSimulationResult, EvError> {
+    let mut order: Vec<usize> = (0..sessions.len()).collect();
+    order.sort_by(|&a, &b| sessions[a].departure_hours.total_cmp(&sessions[b].departure_hours));
+
+    let mut remaining: Vec<Energy> = sessions.iter().map(|session| session.required_energy).collect();
+    let mut states = Vec::with_capacity(telemetry_points.len() + 1);
+    states.push(initial_state);
+    let mut ev_power = Vec::with_capacity(telemetry_points.len());
+    let mut grid_power = Vec::with_capacity(telemetry_points.len());
+    let mut state = initial_state;
+    let mut baseline_state = initial_state;
+    let mut baseline_peak_import = Power::zero();
+    let mut start = 0.0;
+
+    for (step, telemetry_point) in telemetry_points.iter().enumerate() {
+        let hours = telemetry_point.duration().as_hour();
+        let end = start + hours;
+        let mut surplus_kwh = telemetry_point.excess_pv().as_kw().max(0.0) * hours;
+        let mut charged_kwh = 0.0;
+
+        for &k in &order {
+            let session = &sessions[k];
+            let plugged_in = session.plugged_in_hours(start, end);
+            if plugged_in <= 0.0 {
+                continue;
+            }
+            let limit = remaining[k].as_kwh().min(session.max_power.as_kw() * plugged_in);
+            let energy = match mode {
+                ChargingMode::Immediate => limit,
+                ChargingMode::SolarOnly => limit.min(surplus_kwh),
+                ChargingMode::DeadlineAware => {
+                    let hours_after = (session.departure_hours - end).max(0.0);
+                    let needed_now = remaining[k].as_kwh() - session.max_power.as_kw() * hours_after;
+                    limit.min(needed_now.max(surplus_kwh))
+                }
+            };
+            let energy = energy.max(0.0);
+            surplus_kwh = (surplus_kwh - energy).max(0.0);
+            remaining[k] = remaining[k] - Energy::from_kwh(energy).expect("charged energy should be valid");
+            charged_kwh += energy;
+        }
+
+        let charger = Power::from_kw(charged_kwh / hours)
+            .map_err(|power| EvError::ChargerPowerOutOfRange(power, step))?;
+        let site_excess = telemetry_point.excess_pv() - charger;
+        state = battery.step(&state, site_excess, telemetry_point.duration())
+            .map_err(|e| EvError::ErrorSimulatingStep(e, step))?;
+        states.push(state);
+        ev_power.push(charger);
+        grid_power.push(state.power() - site_excess);
+
+        baseline_state = battery.load_follow_step(&baseline_state, telemetry_point)
+            .map_err(|e| EvError::ErrorSimulatingStep(e, step))?;
+        let baseline_import = baseline_state.power() - telemetry_point.excess_pv();
+        if baseline_import > baseline_peak_import {
+            baseline_peak_import = baseline_import;
+        }
+        start = end;
+    }
+
+    let delivered = sessions.iter().zip(&remaining)
+        .map(|(session, &left)| session.required_energy - left)
+        .collect();
+    Ok(EvSimulationResult { states, ev_power, grid_power, delivered, unmet: remaining, baseline_peak_import })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    fn powers(values: &[Power]) -> Vec<f64> {
+        values.iter().map(|p| p.as_kw()).collect()
+    }
+
+    #[test]
+    fn test_immediate_charging_raises_peak() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)); 4];
+        let sessions = [ChargingSession::new(0.0, 4.0, kwh!(10.0), kw!(7.0)).expect("valid session")];
+
+        let result = simulate_with_ev(&telemetry, &battery, initial_state, &sessions, ChargingMode::Immediate)
+            .expect("simulation should succeed");
+
+        assert_eq!(powers(result.ev_power()), vec![7.0, 3.0, 0.0, 0.0]);
+        assert_abs_diff_eq!(result.peak_import().as_kw(), 8.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.baseline_peak_import().as_kw(), 1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.peak_import_increase().as_kw(), 7.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.total_unmet().as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_solar_only_leaves_energy_unmet() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(5.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(5.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+        ];
+        let sessions = [ChargingSession::new(0.0, 3.0, kwh!(10.0), kw!(7.0)).expect("valid session")];
+
+        let result = simulate_with_ev(&telemetry, &battery, initial_state, &sessions, ChargingMode::SolarOnly)
+            .expect("simulation should succeed");
+
+        assert_eq!(powers(result.ev_power()), vec![4.0, 4.0, 0.0]);
+        assert_abs_diff_eq!(result.delivered()[0].as_kwh(), 8.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.unmet()[0].as_kwh(), 2.0, epsilon = EPSILON);
+        // The EV took all the surplus, so the battery had nothing to store.
+        assert_abs_diff_eq!(result.states()[2].state_of_charge_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_deadline_aware_waits_for_solar() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)),
+        ];
+        let sessions = [ChargingSession::new(0.0, 4.0, kwh!(10.0), kw!(5.0)).expect("valid session")];
+
+        let smart = simulate_with_ev(&telemetry, &battery, initial_state, &sessions, ChargingMode::DeadlineAware)
+            .expect("simulation should succeed");
+        let immediate = simulate_with_ev(&telemetry, &battery, initial_state, &sessions, ChargingMode::Immediate)
+            .expect("simulation should succeed");
+
+        assert_eq!(powers(smart.ev_power()), vec![0.0, 0.0, 5.0, 5.0]);
+        assert_abs_diff_eq!(smart.total_unmet().as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(smart.peak_import().as_kw(), 1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(immediate.peak_import().as_kw(), 6.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_deadline_aware_charges_from_grid_when_needed() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0)); 4];
+        // Arrives half way through the first step and leaves at the end of the third.
+        let sessions = [ChargingSession::new(0.5, 3.0, kwh!(8.0), kw!(4.0)).expect("valid session")];
+
+        let result = simulate_with_ev(&telemetry, &battery, initial_state, &sessions, ChargingMode::DeadlineAware)
+            .expect("simulation should succeed");
+
+        // 8 kWh at 4 kW takes the last two hours before departure.
+        assert_eq!(powers(result.ev_power()), vec![0.0, 4.0, 4.0, 0.0]);
+        assert_abs_diff_eq!(result.total_unmet().as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_rejects_combined_charger_power_out_of_range() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0))];
+        let session = ChargingSession::new(0.0, 1.0, kwh!(600_000.0), kw!(600_000.0)).expect("valid session");
+        let sessions = [session.clone(), session];
+
+        let result = simulate_with_ev(&telemetry, &battery, initial_state, &sessions, ChargingMode::Immediate);
+
+        assert!(matches!(result, Err(EvError::ChargerPowerOutOfRange(_, 0))));
+    }
+
+    #[test]
+    fn test_rejects_invalid_sessions() {
+        assert!(matches!(
+            ChargingSession::new(4.0, 2.0, kwh!(10.0), kw!(7.0)),
+            Err(EvError::DepartureBeforeArrival { .. })
+        ));
+        assert!(matches!(ChargingSession::new(0.0, 2.0, kwh!(-1.0), kw!(7.0)), Err(EvError::NegativeRequiredEnergy)));
+        assert!(matches!(ChargingSession::new(0.0, 2.0, kwh!(1.0), kw!(0.0)), Err(EvError::NonPositiveMaxPower)));
+    }
+}
@@ -0,0 +1,297 @@
+use std::borrow::Borrow;
+use crate::battery::{Battery, BatteryModel, BatteryState};
+use crate::simulation::{LoadFollowingSimulation, SimulationError, StepResult};
+use crate::types::{Power, TelemetryPoint};
+
+// How close to a limit a value must be to count as having reached it.
+const LIMIT_TOLERANCE: f64 = 1e-9;
+
+/// A battery constraint becoming active, or the battery changing direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatteryEvent {
+    /// The state of charge reached capacity on this step.
+    HitFull,
+    /// The state of charge reached zero on this step.
+    HitEmpty,
+    /// The requested power was cut back to the battery's max power.
+    PowerLimited { requested: Power, delivered: Power },
+    /// The requested power was first cut back because the battery is full or empty.
+    /// Later steps held at the same limit are not reported again.
+    EnergyLimited { requested: Power, delivered: Power },
+    /// The battery started charging after last discharging, idle steps in between aside.
+    SwitchedToCharging,
+    /// The battery started discharging after last charging, idle steps in between aside.
+    SwitchedToDischarging,
+}
+
+/// The events of a single step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepEvent {
+    step: usize,
+    event: BatteryEvent,
+}
+
+impl StepEvent {
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn event(&self) -> BatteryEvent {
+        self.event
+    }
+}
+
+/// Called by the simulation after every successful step.
+pub trait StepObserver {
+    fn on_step<P: Borrow<TelemetryPoint>>(&mut self, result: &StepResult<P>, events: &[BatteryEvent]);
+}
+
+/// Observes nothing; the default for simulations without an observer.
+impl StepObserver for () {
+    fn on_step<P: Borrow<TelemetryPoint>>(&mut self, _result: &StepResult<P>, _events: &[BatteryEvent]) {}
+}
+
+impl<O: StepObserver> StepObserver for &mut O {
+    fn on_step<P: Borrow<TelemetryPoint>>(&mut self, result: &StepResult<P>, events: &[BatteryEvent]) {
+        (**self).on_step(result, events)
+    }
+}
+
+/// Observer that keeps every event in order.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    events: Vec<StepEvent>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog::default()
+    }
+
+    pub fn events(&self) -> &[StepEvent] {
+        &self.events
+    }
+
+    pub fn into_events(self) -> Vec<StepEvent> {
+        self.events
+    }
+}
+
+impl StepObserver for EventLog {
+    fn on_step<P: Borrow<TelemetryPoint>>(&mut self, result: &StepResult<P>, events: &[BatteryEvent]) {
+        self.events.extend(events.iter().map(|&event| StepEvent { step: result.step(), event }));
+    }
+}
+
+/// Finds the events of each step, remembering what it needs from earlier steps.
+#[derive(Debug, Clone)]
+pub struct EventDetector {
+    // Battery power on the most recent step the battery was not idle, so switches
+    // are found across idle steps.
+    last_power: Power,
+    full_limited: bool,
+    empty_limited: bool,
+}
+
+impl EventDetector {
+    /// A detector for a run starting from `initial_state`.
+    pub fn new(initial_state: &BatteryState) -> Self {
+        EventDetector { last_power: initial_state.power(), full_limited: false, empty_limited: false }
+    }
+
+    /// The events caused by asking `battery` for `requested` power from `previous`
+    /// and getting `state`. Steps must be passed in order.
+    pub fn detect<M: BatteryModel>(
+        &mut self,
+        battery: &M,
+        previous: &BatteryState,
+        requested: Power,
+        state: &BatteryState,
+    ) -> Vec<BatteryEvent> {
+        let mut events = Vec::new();
+        let capacity = battery.capacity().as_kwh();
+        let (before, after) = (previous.state_of_charge_kwh(), state.state_of_charge_kwh());
+
+        if after >= capacity - LIMIT_TOLERANCE && before < capacity - LIMIT_TOLERANCE {
+            events.push(BatteryEvent::HitFull);
+        }
+        if after <= LIMIT_TOLERANCE && before > LIMIT_TOLERANCE {
+            events.push(BatteryEvent::HitEmpty);
+        }
+
+        let delivered = state.power();
+        let (mut full_limited, mut empty_limited) = (false, false);
+        if delivered.abs().as_kw() < requested.abs().as_kw() - LIMIT_TOLERANCE {
+            if delivered.abs().as_kw() >= battery.max_power().as_kw() - LIMIT_TOLERANCE {
+                events.push(BatteryEvent::PowerLimited { requested, delivered });
+            } else {
+                full_limited = requested > Power::zero();
+                empty_limited = requested < Power::zero();
+                if (full_limited && !self.full_limited) || (empty_limited && !self.empty_limited) {
+                    events.push(BatteryEvent::EnergyLimited { requested, delivered });
+                }
+            }
+        }
+        self.full_limited = full_limited;
+        self.empty_limited = empty_limited;
+
+        if self.last_power < Power::zero() && delivered > Power::zero() {
+            events.push(BatteryEvent::SwitchedToCharging);
+        }
+        if self.last_power > Power::zero() && delivered < Power::zero() {
+            events.push(BatteryEvent::SwitchedToDischarging);
+        }
+        if delivered != Power::zero() {
+            self.last_power = delivered;
+        }
+        events
+    }
+}
+
+/// As `simulate_load_following`, reporting every step and its events to `observer`.
+pub fn simulate_load_following_observed<O: StepObserver>(
+    telemetry_points: Vec<TelemetryPoint>,
+    battery: Battery,
+    initial_state: BatteryState,
+    observer: &mut O,
+) -> Result<Vec<BatteryState>, SimulationError> {
+    let mut states: Vec<BatteryState> = Vec::with_capacity(telemetry_points.len() + 1);
+    states.push(initial_state);
+    for result in LoadFollowingSimulation::new(telemetry_points, battery, initial_state).with_observer(observer) {
+        states.push(result?.state());
+    }
+    Ok(states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration, Energy};
+    use crate::{hour, kw, kwh};
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    #[test]
+    fn test_event_log_records_constraint_activations() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(2.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(8.0), kw!(1.0)),  // asks for 7 kW, limited to 5 kW
+            TelemetryPoint::new(hour!(1.0), kw!(5.0), kw!(1.0)),  // 4 kW but only 3 kWh of room
+            TelemetryPoint::new(hour!(1.0), kw!(5.0), kw!(1.0)),  // full, nothing accepted
+            TelemetryPoint::new(hour!(4.0), kw!(0.0), kw!(3.0)),  // 12 kWh asked of 10 kWh
+        ];
+        let mut log = EventLog::new();
+
+        simulate_load_following_observed(telemetry, battery, initial_state, &mut log)
+            .expect("simulation should succeed");
+
+        let events: Vec<(usize, BatteryEvent)> = log.events().iter().map(|e| (e.step(), e.event())).collect();
+        assert_eq!(events, vec![
+            (0, BatteryEvent::PowerLimited { requested: kw!(7.0), delivered: kw!(5.0) }),
+            (1, BatteryEvent::HitFull),
+            (1, BatteryEvent::EnergyLimited { requested: kw!(4.0), delivered: kw!(3.0) }),
+            (3, BatteryEvent::HitEmpty),
+            (3, BatteryEvent::EnergyLimited { requested: kw!(-3.0), delivered: kw!(-2.5) }),
+            (3, BatteryEvent::SwitchedToDischarging),
+        ]);
+    }
+
+    #[test]
+    fn test_direction_switches() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(2.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(1.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(2.0), kw!(1.0)),
+        ];
+        let mut log = EventLog::new();
+
+        simulate_load_following_observed(telemetry, battery, initial_state, &mut log)
+            .expect("simulation should succeed");
+
+        // Idling in between does not hide the switch back to charging.
+        let events: Vec<(usize, BatteryEvent)> = log.events().iter().map(|e| (e.step(), e.event())).collect();
+        assert_eq!(events, vec![(1, BatteryEvent::SwitchedToDischarging), (3, BatteryEvent::SwitchedToCharging)]);
+    }
+
+    #[test]
+    fn test_switch_detected_across_idle_steps() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(2.0), kw!(1.0)),  // charge
+            TelemetryPoint::new(hour!(1.0), kw!(1.0), kw!(1.0)),  // idle
+            TelemetryPoint::new(hour!(1.0), kw!(1.0), kw!(1.0)),  // idle
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),  // discharge
+        ];
+        let mut log = EventLog::new();
+
+        simulate_load_following_observed(telemetry, battery, initial_state, &mut log)
+            .expect("simulation should succeed");
+
+        let events: Vec<(usize, BatteryEvent)> = log.events().iter().map(|e| (e.step(), e.event())).collect();
+        assert_eq!(events, vec![(3, BatteryEvent::SwitchedToDischarging)]);
+    }
+
+    #[test]
+    fn test_observer_sees_every_step() {
+        struct Counter(usize);
+        impl StepObserver for Counter {
+            fn on_step<P: Borrow<TelemetryPoint>>(&mut self, result: &StepResult<P>, _events: &[BatteryEvent]) {
+                assert_eq!(result.step(), self.0);
+                self.0 += 1;
+            }
+        }
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(1.0), kw!(0.0)); 5];
+        let mut counter = Counter(0);
+
+        simulate_load_following_observed(telemetry, battery, initial_state, &mut counter)
+            .expect("simulation should succeed");
+
+        assert_eq!(counter.0, 5);
+    }
+
+    #[test]
+    fn test_observer_on_resumed_and_tolerant_runs() {
+        use crate::simulation::FailurePolicy;
+
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(2.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(8.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(5.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(4.0), kw!(0.0), kw!(3.0)),
+        ];
+        let mut log = EventLog::new();
+        let mut simulation = LoadFollowingSimulation::new(&telemetry[..2], battery.clone(), initial_state)
+            .with_observer(&mut log);
+        for result in simulation.by_ref() {
+            result.expect("step should succeed");
+        }
+        let checkpoint = simulation.checkpoint();
+        for result in LoadFollowingSimulation::resume(checkpoint, &telemetry[2..]).with_observer(&mut log) {
+            result.expect("step should succeed");
+        }
+
+        let events: Vec<(usize, BatteryEvent)> = log.events().iter().map(|e| (e.step(), e.event())).collect();
+        assert_eq!(events[..2], [
+            (0, BatteryEvent::PowerLimited { requested: kw!(7.0), delivered: kw!(5.0) }),
+            (1, BatteryEvent::HitFull),
+        ]);
+        assert_eq!(events.last(), Some(&(2, BatteryEvent::SwitchedToDischarging)));
+
+        let mut tolerant_log = EventLog::new();
+        let result = LoadFollowingSimulation::new(telemetry, &battery, initial_state)
+            .with_observer(&mut tolerant_log)
+            .run_tolerant(FailurePolicy::Skip);
+        assert!(result.diagnostics().is_empty());
+        assert_eq!(tolerant_log.events(), log.events());
+    }
+}
@@ -0,0 +1,396 @@
+use crate::types::{Duration, Power, TelemetryPoint};
+
+/// Supplies the controller's view of the upcoming telemetry.
+pub trait ForecastProvider {
+    /// Forecast of the `horizon` telemetry points starting at `step`, given the
+    /// telemetry observed before it (`history.len() == step`).
+    fn forecast(&self, step: usize, history: &[TelemetryPoint], horizon: usize) -> Vec<TelemetryPoint>;
+}
+
+/// A forecast that returns the actual telemetry, for perfect-foresight benchmarks.
+#[derive(Debug, Clone, Copy)]
+pub struct PerfectForecast<'a> {
+    telemetry_points: &'a [TelemetryPoint],
+}
+
+impl<'a> PerfectForecast<'a> {
+    pub fn new(telemetry_points: &'a [TelemetryPoint]) -> Self {
+        PerfectForecast { telemetry_points }
+    }
+}
+
+impl ForecastProvider for PerfectForecast<'_> {
+    fn forecast(&self, step: usize, _history: &[TelemetryPoint], horizon: usize) -> Vec<TelemetryPoint> {
+        let start = step.min(self.telemetry_points.len());
+        let end = (step + horizon).min(self.telemetry_points.len());
+        self.telemetry_points[start..end].to_vec()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ForecastError {
+    #[error("Seasonal period must be at least one step.")]
+    ZeroPeriod,
+    #[error("Smoothing factor {0} must be in (0, 1].")]
+    InvalidSmoothingFactor(f64),
+    #[error("Regression window must cover at least 2 steps, got {0}.")]
+    WindowTooShort(usize),
+    #[error("Actual series has {0} values but the forecast has {1}.")]
+    LengthMismatch(usize, usize),
+    #[error("Cannot score an empty series.")]
+    EmptySeries,
+}
+
+/// Forecasts a single power series (in kW) from its history.
+pub trait SeriesForecaster {
+    fn forecast_series(&self, history: &[f64], horizon: usize) -> Vec<f64>;
+}
+
+/// Repeats the last observed value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Persistence;
+
+impl SeriesForecaster for Persistence {
+    fn forecast_series(&self, history: &[f64], horizon: usize) -> Vec<f64> {
+        vec![history.last().copied().unwrap_or(0.0); horizon]
+    }
+}
+
+/// Repeats the value observed one season earlier, e.g. the same time yesterday.
+#[derive(Debug, Clone, Copy)]
+pub struct SeasonalNaive {
+    period: usize, // season length in steps
+}
+
+impl SeasonalNaive {
+    pub fn new(period: usize) -> Result<Self, ForecastError> {
+        if period == 0 {
+            return Err(ForecastError::ZeroPeriod);
+        }
+        Ok(SeasonalNaive { period })
+    }
+
+    /// Same time yesterday, for telemetry sampled every `step_duration`.
+    pub fn daily(step_duration: Duration) -> Result<Self, ForecastError> {
+        SeasonalNaive::new(steps_per(24.0, step_duration))
+    }
+
+    /// Same time last week, for telemetry sampled every `step_duration`.
+    pub fn weekly(step_duration: Duration) -> Result<Self, ForecastError> {
+        SeasonalNaive::new(steps_per(168.0, step_duration))
+    }
+
+    pub fn period(&self) -> usize {
+        self.period
+    }
+}
+
+impl SeriesForecaster for SeasonalNaive {
+    fn forecast_series(&self, history: &[f64], horizon: usize) -> Vec<f64> {
+        if history.len() < self.period {
+            return Persistence.forecast_series(history, horizon);
+        }
+        let season_start = history.len() - self.period;
+        (0..horizon)
+            .map(|h| history[season_start + h % self.period])
+            .collect()
+    }
+}
+
+/// Simple exponential smoothing: a flat forecast at the smoothed level.
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialSmoothing {
+    alpha: f64, // weight on the newest observation
+}
+
+impl ExponentialSmoothing {
+    pub fn new(alpha: f64) -> Result<Self, ForecastError> {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            return Err(ForecastError::InvalidSmoothingFactor(alpha));
+        }
+        Ok(ExponentialSmoothing { alpha })
+    }
+}
+
+impl SeriesForecaster for ExponentialSmoothing {
+    fn forecast_series(&self, history: &[f64], horizon: usize) -> Vec<f64> {
+        let level = history.iter()
+            .copied()
+            .reduce(|level, value| self.alpha * value + (1.0 - self.alpha) * level)
+            .unwrap_or(0.0);
+        vec![level; horizon]
+    }
+}
+
+/// Least-squares fit of `value = a + b * value_one_season_ago` over a trailing window.
+/// Falls back to seasonal-naive until there is enough history to fit.
+#[derive(Debug, Clone, Copy)]
+pub struct SeasonalRegression {
+    period: usize, // season length in steps
+    window: usize, // number of recent steps the fit uses
+}
+
+impl SeasonalRegression {
+    pub fn new(period: usize, window: usize) -> Result<Self, ForecastError> {
+        if period == 0 {
+            return Err(ForecastError::ZeroPeriod);
+        }
+        if window < 2 {
+            return Err(ForecastError::WindowTooShort(window));
+        }
+        Ok(SeasonalRegression { period, window })
+    }
+
+    // Returns (intercept, slope), or None when the window is too short or degenerate.
+    fn fit(&self, history: &[f64]) -> Option<(f64, f64)> {
+        let first = history.len().saturating_sub(self.window).max(self.period);
+        let pairs: Vec<(f64, f64)> = (first..history.len())
+            .map(|t| (history[t - self.period], history[t]))
+            .collect();
+        if pairs.len() < 2 {
+            return None;
+        }
+
+        let n = pairs.len() as f64;
+        let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let covariance: f64 = pairs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let variance: f64 = pairs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if variance <= f64::EPSILON {
+            return None;
+        }
+        let slope = covariance / variance;
+        Some((mean_y - slope * mean_x, slope))
+    }
+}
+
+impl SeriesForecaster for SeasonalRegression {
+    fn forecast_series(&self, history: &[f64], horizon: usize) -> Vec<f64> {
+        let Some((intercept, slope)) = self.fit(history) else {
+            return SeasonalNaive { period: self.period }.forecast_series(history, horizon);
+        };
+
+        // Forecasts more than a season ahead regress on earlier forecasts.
+        let mut extended = history.to_vec();
+        for _ in 0..horizon {
+            let lagged = extended[extended.len() - self.period];
+            extended.push(intercept + slope * lagged);
+        }
+        extended.split_off(history.len())
+    }
+}
+
+/// Applies a series forecaster to both the solar and load channels of the telemetry.
+#[derive(Debug, Clone, Copy)]
+pub struct TelemetryForecaster<S> {
+    forecaster: S,
+    step_duration: Duration,
+}
+
+impl<S: SeriesForecaster> TelemetryForecaster<S> {
+    pub fn new(forecaster: S, step_duration: Duration) -> Self {
+        TelemetryForecaster { forecaster, step_duration }
+    }
+}
+
+impl<S: SeriesForecaster> ForecastProvider for TelemetryForecaster<S> {
+    fn forecast(&self, _step: usize, history: &[TelemetryPoint], horizon: usize) -> Vec<TelemetryPoint> {
+        let solar: Vec<f64> = history.iter().map(|p| p.solar_power().as_kw()).collect();
+        let load: Vec<f64> = history.iter().map(|p| p.load_power().as_kw()).collect();
+
+        self.forecaster.forecast_series(&solar, horizon).into_iter()
+            .zip(self.forecaster.forecast_series(&load, horizon))
+            // Forecasts are clamped to valid power, and solar cannot be negative.
+            .map(|(solar_kw, load_kw)| TelemetryPoint::new(
+                self.step_duration,
+                Power::from_kw_saturating(solar_kw.max(0.0)),
+                Power::from_kw_saturating(load_kw),
+            ))
+            .collect()
+    }
+}
+
+fn steps_per(hours: f64, step_duration: Duration) -> usize {
+    (hours / step_duration.as_hour()).round() as usize
+}
+
+/* --------------- ERROR METRICS ------------------- */
+
+fn check_lengths(actual: &[f64], forecast: &[f64]) -> Result<(), ForecastError> {
+    if actual.len() != forecast.len() {
+        Err(ForecastError::LengthMismatch(actual.len(), forecast.len()))
+    } else if actual.is_empty() {
+        Err(ForecastError::EmptySeries)
+    } else {
+        Ok(())
+    }
+}
+
+/// Mean absolute error.
+pub fn mae(actual: &[f64], forecast: &[f64]) -> Result<f64, ForecastError> {
+    check_lengths(actual, forecast)?;
+    let total: f64 = actual.iter().zip(forecast).map(|(a, f)| (a - f).abs()).sum();
+    Ok(total / actual.len() as f64)
+}
+
+/// Root mean squared error.
+pub fn rmse(actual: &[f64], forecast: &[f64]) -> Result<f64, ForecastError> {
+    check_lengths(actual, forecast)?;
+    let total: f64 = actual.iter().zip(forecast).map(|(a, f)| (a - f).powi(2)).sum();
+    Ok((total / actual.len() as f64).sqrt())
+}
+
+/// RMSE skill score against a reference forecast, typically persistence:
+/// 1 is perfect, 0 is no better than the reference, negative is worse.
+pub fn skill_score(actual: &[f64], forecast: &[f64], reference: &[f64]) -> Result<f64, ForecastError> {
+    let forecast_rmse = rmse(actual, forecast)?;
+    let reference_rmse = rmse(actual, reference)?;
+    if reference_rmse == 0.0 {
+        return Ok(if forecast_rmse == 0.0 { 0.0 } else { f64::NEG_INFINITY });
+    }
+    Ok(1.0 - forecast_rmse / reference_rmse)
+}
+
+/// Rolling-origin backtest: from every origin with at least one step of history,
+/// forecast `horizon` steps and collect the forecasts alongside the actual values.
+/// Returns `(actual, forecast)` ready for the error metrics.
+pub fn backtest<S: SeriesForecaster>(forecaster: &S, series: &[f64], horizon: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut actual = Vec::new();
+    let mut forecast = Vec::new();
+    for origin in 1..series.len() {
+        let steps = horizon.min(series.len() - origin);
+        actual.extend_from_slice(&series[origin..origin + steps]);
+        forecast.extend(forecaster.forecast_series(&series[..origin], steps));
+    }
+    (actual, forecast)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hour, kw};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn test_perfect_forecast_truncates_at_end() {
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(1.0), kw!(0.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(2.0), kw!(0.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(3.0), kw!(0.0)),
+        ];
+        let forecaster = PerfectForecast::new(&telemetry);
+
+        let forecast = forecaster.forecast(1, &telemetry[..1], 5);
+        assert_eq!(forecast.len(), 2);
+        assert_eq!(forecast[0].solar_power(), kw!(2.0));
+        assert_eq!(forecast[1].solar_power(), kw!(3.0));
+    }
+
+    #[test]
+    fn test_persistence_repeats_last_value() {
+        assert_eq!(Persistence.forecast_series(&[1.0, 2.0, 3.0], 2), vec![3.0, 3.0]);
+        assert_eq!(Persistence.forecast_series(&[], 2), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_seasonal_naive_repeats_last_season() {
+        let forecaster = SeasonalNaive::new(3).expect("valid period");
+        let history = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(forecaster.forecast_series(&history, 4), vec![4.0, 5.0, 6.0, 4.0]);
+    }
+
+    #[test]
+    fn test_seasonal_naive_daily_period() {
+        let forecaster = SeasonalNaive::daily(hour!(0.5)).expect("valid period");
+        assert_eq!(forecaster.period(), 48);
+        let forecaster = SeasonalNaive::weekly(hour!(1.0)).expect("valid period");
+        assert_eq!(forecaster.period(), 168);
+        assert!(matches!(SeasonalNaive::new(0), Err(ForecastError::ZeroPeriod)));
+    }
+
+    #[test]
+    fn test_exponential_smoothing_level() {
+        let forecaster = ExponentialSmoothing::new(0.5).expect("valid alpha");
+        // level: 4 -> 0.5 * 8 + 0.5 * 4 = 6 -> 0.5 * 2 + 0.5 * 6 = 4
+        let forecast = forecaster.forecast_series(&[4.0, 8.0, 2.0], 2);
+        assert_abs_diff_eq!(forecast[0], 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(forecast[1], 4.0, epsilon = EPSILON);
+        assert!(matches!(ExponentialSmoothing::new(0.0), Err(ForecastError::InvalidSmoothingFactor(_))));
+    }
+
+    #[test]
+    fn test_seasonal_regression_recovers_scaled_season() {
+        // Each day is twice the previous one.
+        let history = [1.0, 2.0, 2.0, 4.0, 4.0, 8.0];
+        let forecaster = SeasonalRegression::new(2, 4).expect("valid regression");
+        let forecast = forecaster.forecast_series(&history, 3);
+        assert_abs_diff_eq!(forecast[0], 8.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(forecast[1], 16.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(forecast[2], 16.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_telemetry_forecaster_forecasts_both_channels() {
+        let history = vec![
+            TelemetryPoint::new(hour!(0.5), kw!(3.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(0.5), kw!(4.0), kw!(2.0)),
+        ];
+        let forecaster = TelemetryForecaster::new(Persistence, hour!(0.5));
+
+        let forecast = forecaster.forecast(2, &history, 3);
+        assert_eq!(forecast.len(), 3);
+        assert_eq!(forecast[2].duration(), hour!(0.5));
+        assert_eq!(forecast[2].solar_power(), kw!(4.0));
+        assert_eq!(forecast[2].load_power(), kw!(2.0));
+    }
+
+    // Forecasts the same out-of-range values whatever the history.
+    struct Unbounded;
+
+    impl SeriesForecaster for Unbounded {
+        fn forecast_series(&self, _history: &[f64], _horizon: usize) -> Vec<f64> {
+            vec![-2.0, f64::NAN, 1e9]
+        }
+    }
+
+    #[test]
+    fn test_telemetry_forecaster_clamps_model_output() {
+        let history = vec![TelemetryPoint::new(hour!(1.0), kw!(1.0), kw!(1.0))];
+        let forecaster = TelemetryForecaster::new(Unbounded, hour!(1.0));
+
+        let forecast = forecaster.forecast(1, &history, 3);
+        let solar: Vec<f64> = forecast.iter().map(|p| p.solar_power().as_kw()).collect();
+        let load: Vec<f64> = forecast.iter().map(|p| p.load_power().as_kw()).collect();
+        assert_eq!(solar, vec![0.0, 0.0, 1e6]);
+        assert_eq!(load, vec![-2.0, 0.0, 1e6]);
+    }
+
+    #[test]
+    fn test_error_metrics() {
+        let actual = [1.0, 2.0, 3.0, 4.0];
+        let forecast = [1.0, 2.0, 3.0, 8.0];
+        assert_abs_diff_eq!(mae(&actual, &forecast).expect("ok"), 1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(rmse(&actual, &forecast).expect("ok"), 2.0, epsilon = EPSILON);
+
+        let reference = [0.0, 0.0, 0.0, 0.0];
+        // reference rmse = sqrt(30 / 4)
+        let expected = 1.0 - 2.0 / (30.0_f64 / 4.0).sqrt();
+        assert_abs_diff_eq!(skill_score(&actual, &forecast, &reference).expect("ok"), expected, epsilon = EPSILON);
+
+        assert!(matches!(mae(&actual, &[1.0]), Err(ForecastError::LengthMismatch(4, 1))));
+        assert!(matches!(rmse(&[], &[]), Err(ForecastError::EmptySeries)));
+    }
+
+    #[test]
+    fn test_backtest_picks_seasonal_forecaster_for_periodic_series() {
+        let series: Vec<f64> = (0..24).map(|t| [0.0, 5.0, 10.0, 5.0][t % 4]).collect();
+        let seasonal = SeasonalNaive::new(4).expect("valid period");
+
+        let (actual, persistence_forecast) = backtest(&Persistence, &series, 2);
+        let (_, seasonal_forecast) = backtest(&seasonal, &series, 2);
+
+        let skill = skill_score(&actual, &seasonal_forecast, &persistence_forecast).expect("ok");
+        assert!(skill > 0.0);
+    }
+}
@@ -0,0 +1,370 @@
+use crate::battery::{Battery, BatteryError, BatteryState};
+use crate::types::{Energy, Power, TelemetryPoint};
+
+#[derive(Debug, thiserror::Error)]
+pub enum GeneratorError {
+    #[error("Rated power must be greater than 0.")]
+    NonPositiveRatedPower,
+    #[error("Minimum loading {0} must be between 0 and 1.")]
+    InvalidMinimumLoading(f64),
+    #[error("Fuel curve coefficient {0} must be finite and non-negative.")]
+    InvalidFuelCurve(f64),
+    #[error("Start threshold {start} and stop threshold {stop} do not leave a dead band.")]
+    InvalidThresholds { start: f64, stop: f64 },
+    #[error("A state of charge start rule must charge the battery, or the generator never stops.")]
+    StateOfChargeRuleWithoutCharging,
+    #[error("Minimum run time {0} hours must be finite and non-negative.")]
+    InvalidMinimumRunTime(f64),
+    #[error("Simulating the generator failed on step {1}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize),
+}
+
+/// Fuel use as a straight line in output power:
+/// `litres per hour = no_load + per_kwh * output kW` while running.
+#[derive(Debug, Clone, Copy)]
+pub struct FuelCurve {
+    no_load_litres_per_hour: f64,
+    litres_per_kwh: f64,
+}
+
+impl FuelCurve {
+    pub fn new(no_load_litres_per_hour: f64, litres_per_kwh: f64) -> Result<Self, GeneratorError> {
+        for coefficient in [no_load_litres_per_hour, litres_per_kwh] {
+            if !(coefficient.is_finite() && coefficient >= 0.0) {
+                return Err(GeneratorError::InvalidFuelCurve(coefficient));
+            }
+        }
+        Ok(FuelCurve { no_load_litres_per_hour, litres_per_kwh })
+    }
+
+    /// Litres burnt per hour at `output`.
+    pub fn litres_per_hour(&self, output: Power) -> f64 {
+        self.no_load_litres_per_hour + self.litres_per_kwh * output.as_kw()
+    }
+}
+
+/// A diesel or gas genset.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    rated_power: Power,
+    minimum_loading: f64,  // fraction of rated power it must run at while on
+    fuel_curve: FuelCurve,
+}
+
+impl Generator {
+    pub fn new(rated_power: Power, minimum_loading: f64, fuel_curve: FuelCurve) -> Result<Self, GeneratorError> {
+        if rated_power <= Power::zero() {
+            return Err(GeneratorError::NonPositiveRatedPower);
+        }
+        if !(0.0..=1.0).contains(&minimum_loading) {
+            return Err(GeneratorError::InvalidMinimumLoading(minimum_loading));
+        }
+        Ok(Generator { rated_power, minimum_loading, fuel_curve })
+    }
+
+    pub fn rated_power(&self) -> Power {
+        self.rated_power
+    }
+
+    pub fn minimum_power(&self) -> Power {
+        Power::from_kw(self.rated_power.as_kw() * self.minimum_loading)
+            .expect("fraction of a valid power should be valid")
+    }
+
+    pub fn fuel_curve(&self) -> &FuelCurve {
+        &self.fuel_curve
+    }
+
+    // Output when asked for `demand`, held between minimum loading and rated power.
+    fn output(&self, demand: Power) -> Power {
+        let minimum = self.minimum_power();
+        let demand = if demand < minimum { minimum } else { demand };
+        demand.min(self.rated_power)
+    }
+}
+
+/// When the generator starts and stops. Rules are checked at the start of every step.
+#[derive(Debug, Clone, Copy)]
+pub enum StartRule {
+    /// Start when the state of charge falls to `start_below` of capacity, stop once it
+    /// reaches `stop_above`.
+    StateOfCharge { start_below: f64, stop_above: f64 },
+    /// Start when the load net of solar reaches `start_above`, stop once it falls to `stop_below`.
+    NetLoad { start_above: Power, stop_below: Power },
+}
+
+/// How the generator is run alongside the battery.
+#[derive(Debug, Clone, Copy)]
+pub struct GeneratorDispatch {
+    rule: StartRule,
+    minimum_run_hours: f64,
+    charge_battery: bool,
+}
+
+impl GeneratorDispatch {
+    /// With `charge_battery` the generator also charges the battery as fast as it will
+    /// accept, within rated power; otherwise it only follows the net load. The
+    /// `StateOfCharge` rule needs `charge_battery`, as only charging ever stops it.
+    pub fn new(rule: StartRule, minimum_run_hours: f64, charge_battery: bool) -> Result<Self, GeneratorError> {
+        let valid = match rule {
+            StartRule::StateOfCharge { start_below, stop_above } => {
+                (0.0..=1.0).contains(&start_below) && (0.0..=1.0).contains(&stop_above) && start_below < stop_above
+            }
+            StartRule::NetLoad { start_above, stop_below } => start_above > stop_below,
+        };
+        if !valid {
+            return Err(match rule {
+                StartRule::StateOfCharge { start_below, stop_above } => {
+                    GeneratorError::InvalidThresholds { start: start_below, stop: stop_above }
+                }
+                StartRule::NetLoad { start_above, stop_below } => {
+                    GeneratorError::InvalidThresholds { start: start_above.as_kw(), stop: stop_below.as_kw() }
+                }
+            });
+        }
+        if matches!(rule, StartRule::StateOfCharge { .. }) && !charge_battery {
+            return Err(GeneratorError::StateOfChargeRuleWithoutCharging);
+        }
+        if !(minimum_run_hours.is_finite() && minimum_run_hours >= 0.0) {
+            return Err(GeneratorError::InvalidMinimumRunTime(minimum_run_hours));
+        }
+        Ok(GeneratorDispatch { rule, minimum_run_hours, charge_battery })
+    }
+
+    fn should_start(&self, soc_fraction: f64, net_load: Power) -> bool {
+        match self.rule {
+            StartRule::StateOfCharge { start_below, .. } => soc_fraction <= start_below,
+            StartRule::NetLoad { start_above, .. } => net_load >= start_above,
+        }
+    }
+
+    fn should_stop(&self, soc_fraction: f64, net_load: Power) -> bool {
+        match self.rule {
+            StartRule::StateOfCharge { stop_above, .. } => soc_fraction >= stop_above,
+            StartRule::NetLoad { stop_below, .. } => net_load <= stop_below,
+        }
+    }
+}
+
+/// Battery and generator results of a hybrid simulation.
+#[derive(Debug, Clone)]
+pub struct GeneratorSimulationResult {
+    states: Vec<BatteryState>,
+    generator_power: Vec<Power>,
+    fuel_litres: f64,
+    run_hours: f64,
+    starts: usize,
+    unserved_energy: Energy,
+    curtailed_energy: Energy,
+}
+
+impl GeneratorSimulationResult {
+    /// The initial state followed by the battery state after every step.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// Generator output on every step, zero while off.
+    pub fn generator_power(&self) -> &[Power] {
+        &self.generator_power
+    }
+
+    pub fn fuel_litres(&self) -> f64 {
+        self.fuel_litres
+    }
+
+    pub fn run_hours(&self) -> f64 {
+        self.run_hours
+    }
+
+    pub fn starts(&self) -> usize {
+        self.starts
+    }
+
+    /// Load that PV, the battery and the generator together could not serve.
+    pub fn unserved_energy(&self) -> Energy {
+        self.unserved_energy
+    }
+
+    /// PV and generator output with nowhere to go.
+    pub fn curtailed_energy(&self) -> Energy {
+        self.curtailed_energy
+    }
+}
+
+/// Simulates an off-grid site where PV, the battery and a generator serve the load.
+///
+/// The battery absorbs any surplus and covers any deficit it can; what is left over is
+/// curtailed or unserved. The generator starts and stops according to `dispatch`, and
+/// once started runs for at least the minimum run time.
+pub fn simulate_with_generator(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    generator: &Generator,
+    dispatch: &GeneratorDispatch,
+) -> Result<GeneratorSimulationResult, GeneratorError> {
+    let mut result = GeneratorSimulationResult {
+        states: Vec::with_capacity(telemetry_points.len() + 1),
+        generator_power: Vec::with_capacity(telemetry_points.len()),
+        fuel_litres: 0.0,
+        run_hours: 0.0,
+        starts: 0,
+        unserved_energy: Energy::zero(),
+        curtailed_energy: Energy::zero(),
+    };
+    result.states.push(initial_state);
+    let mut state = initial_state;
+    let mut running = false;
+    let mut hours_since_start = 0.0;
+
+    for (step, telemetry_point) in telemetry_points.iter().enumerate() {
+        let duration = telemetry_point.duration();
+        let net_load = -telemetry_point.excess_pv();
+        let soc_fraction = state.state_of_charge_kwh() / battery.capacity().as_kwh();
+
+        if running {
+            if hours_since_start >= dispatch.minimum_run_hours && dispatch.should_stop(soc_fraction, net_load) {
+                running = false;
+            }
+        } else if dispatch.should_start(soc_fraction, net_load) {
+            running = true;
+            hours_since_start = 0.0;
+            result.starts += 1;
+        }
+
+        let generator_power = if running {
+            let demand = if dispatch.charge_battery {
+                net_load + battery.max_achievable_charge_power(&state, duration)
+            } else {
+                net_load
+            };
+            generator.output(demand)
+        } else {
+            Power::zero()
+        };
+
+        let battery_power = generator_power - net_load;
+        state = battery.step(&state, battery_power, duration)
+            .map_err(|e| GeneratorError::ErrorSimulatingStep(e, step))?;
+        let residual = (battery_power - state.power()) * duration;
+        if residual > Energy::zero() {
+            result.curtailed_energy = result.curtailed_energy + residual;
+        } else {
+            result.unserved_energy = result.unserved_energy - residual;
+        }
+
+        if running {
+            hours_since_start += duration.as_hour();
+            result.run_hours += duration.as_hour();
+            result.fuel_litres += generator.fuel_curve.litres_per_hour(generator_power) * duration.as_hour();
+        }
+        result.states.push(state);
+        result.generator_power.push(generator_power);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    fn test_generator(rated_power: Power) -> Generator {
+        let fuel_curve = FuelCurve::new(0.5, 0.25).expect("valid fuel curve");
+        Generator::new(rated_power, 0.3, fuel_curve).expect("valid generator")
+    }
+
+    #[test]
+    fn test_state_of_charge_rule_charges_battery() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(3.0), Power::zero()).expect("valid state");
+        let dispatch = GeneratorDispatch::new(
+            StartRule::StateOfCharge { start_below: 0.2, stop_above: 0.8 }, 1.0, true,
+        ).expect("valid dispatch");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)); 5];
+
+        let result = simulate_with_generator(&telemetry, &battery, initial_state, &test_generator(kw!(5.0)), &dispatch)
+            .expect("simulation should succeed");
+
+        let socs: Vec<f64> = result.states().iter().map(|s| s.state_of_charge_kwh()).collect();
+        assert_eq!(socs, vec![3.0, 1.0, 4.0, 7.0, 10.0, 8.0]);
+        let output: Vec<f64> = result.generator_power().iter().map(|p| p.as_kw()).collect();
+        assert_eq!(output, vec![0.0, 5.0, 5.0, 5.0, 0.0]);
+        assert_eq!(result.starts(), 1);
+        assert_abs_diff_eq!(result.run_hours(), 3.0, epsilon = EPSILON);
+        // 0.5 l/h plus 0.25 l/kWh at 5 kW for 3 hours.
+        assert_abs_diff_eq!(result.fuel_litres(), 3.0 * 1.75, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.unserved_energy().as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_minimum_run_time_and_minimum_loading() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(10.0), Power::zero()).expect("valid state");
+        let dispatch = GeneratorDispatch::new(
+            StartRule::NetLoad { start_above: kw!(4.0), stop_below: kw!(1.0) }, 3.0, false,
+        ).expect("valid dispatch");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(5.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.5)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.5)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.5)),
+        ];
+
+        let result = simulate_with_generator(&telemetry, &battery, initial_state, &test_generator(kw!(5.0)), &dispatch)
+            .expect("simulation should succeed");
+
+        // Held on at its 1.5 kW minimum until 3 hours have run, with the full battery
+        // unable to take the surplus.
+        let output: Vec<f64> = result.generator_power().iter().map(|p| p.as_kw()).collect();
+        assert_eq!(output, vec![5.0, 1.5, 1.5, 0.0]);
+        assert_abs_diff_eq!(result.curtailed_energy().as_kwh(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.run_hours(), 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.states()[4].state_of_charge_kwh(), 9.5, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_undersized_generator_leaves_load_unserved() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let dispatch = GeneratorDispatch::new(
+            StartRule::NetLoad { start_above: kw!(2.0), stop_below: kw!(1.0) }, 0.0, false,
+        ).expect("valid dispatch");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(3.0)); 2];
+
+        let result = simulate_with_generator(&telemetry, &battery, initial_state, &test_generator(kw!(1.0)), &dispatch)
+            .expect("simulation should succeed");
+
+        assert_abs_diff_eq!(result.unserved_energy().as_kwh(), 4.0, epsilon = EPSILON);
+        assert_eq!(result.starts(), 1);
+    }
+
+    #[test]
+    fn test_rejects_invalid_configuration() {
+        let fuel_curve = FuelCurve::new(0.5, 0.25).expect("valid fuel curve");
+        assert!(matches!(Generator::new(kw!(0.0), 0.3, fuel_curve), Err(GeneratorError::NonPositiveRatedPower)));
+        assert!(matches!(Generator::new(kw!(5.0), 1.3, fuel_curve), Err(GeneratorError::InvalidMinimumLoading(_))));
+        assert!(matches!(FuelCurve::new(-1.0, 0.25), Err(GeneratorError::InvalidFuelCurve(_))));
+        assert!(matches!(
+            GeneratorDispatch::new(StartRule::StateOfCharge { start_below: 0.8, stop_above: 0.2 }, 1.0, true),
+            Err(GeneratorError::InvalidThresholds { .. })
+        ));
+        assert!(matches!(
+            GeneratorDispatch::new(StartRule::NetLoad { start_above: kw!(1.0), stop_below: kw!(4.0) }, 1.0, true),
+            Err(GeneratorError::InvalidThresholds { .. })
+        ));
+        assert!(matches!(
+            GeneratorDispatch::new(StartRule::StateOfCharge { start_below: 0.2, stop_above: 0.8 }, 1.0, false),
+            Err(GeneratorError::StateOfChargeRuleWithoutCharging)
+        ));
+    }
+}
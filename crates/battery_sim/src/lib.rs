@@ -2,5 +2,26 @@ pub mod battery;
 pub mod types;
 pub mod simulation;
 pub mod data;
+pub mod optimiser;
+pub mod dynamic_programming;
+pub mod forecast;
+pub mod mpc;
+pub mod sweep;
+pub mod sizing;
+pub mod monte_carlo;
+pub mod summary;
+pub mod checkpoint;
+pub mod events;
+pub mod outage;
+pub mod outage_scenarios;
+pub mod generator;
+pub mod ev;
+pub mod controllable_load;
+pub mod three_phase;
+pub mod community;
+pub mod vpp;
+pub mod ancillary;
+pub mod envelope;
+mod stats;
 
 
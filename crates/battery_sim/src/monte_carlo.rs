@@ -0,0 +1,328 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal};
+use rayon::prelude::*;
+use crate::battery::{Battery, BatteryState};
+use crate::simulation::{LoadFollowingSimulation, SimulationError};
+use crate::stats::percentile;
+use crate::sweep::ScenarioKpis;
+use crate::types::{Energy, Power, TelemetryPoint};
+
+/// How each Monte Carlo sample is derived from the measured telemetry.
+#[derive(Debug, Clone)]
+pub enum Perturbation {
+    /// Rebuild the series from whole days drawn with replacement.
+    BlockBootstrap { steps_per_day: usize },
+    /// Scale every solar and load value by `1 + N(0, sigma)`, floored at zero.
+    MultiplicativeNoise { solar_sigma: f64, load_sigma: f64 },
+    /// Replace the solar channel with one of several alternative weather years,
+    /// each the same length as the telemetry.
+    WeatherYearSwap { solar_years: Vec<Vec<Power>> },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MonteCarloError {
+    #[error("At least one sample is required.")]
+    NoSamples,
+    #[error("Block bootstrap needs at least one full day of {0} steps.")]
+    NotEnoughDays(usize),
+    #[error("Noise standard deviation {0} must be finite and non-negative.")]
+    InvalidSigma(f64),
+    #[error("Weather year {index} has {actual} steps, expected {expected}.")]
+    WeatherYearLength { index: usize, expected: usize, actual: usize },
+    #[error("At least one weather year is required.")]
+    NoWeatherYears,
+    #[error("Percentile {0} must be between 0 and 1.")]
+    InvalidPercentile(f64),
+    #[error("Sample {1} failed to simulate.")]
+    ErrorSimulatingSample(#[source] SimulationError, usize),
+}
+
+impl Perturbation {
+    fn validate(&self, steps: usize) -> Result<(), MonteCarloError> {
+        match self {
+            Perturbation::BlockBootstrap { steps_per_day } => {
+                if *steps_per_day == 0 || steps < *steps_per_day {
+                    return Err(MonteCarloError::NotEnoughDays(*steps_per_day));
+                }
+            }
+            Perturbation::MultiplicativeNoise { solar_sigma, load_sigma } => {
+                for &sigma in [solar_sigma, load_sigma] {
+                    if !(sigma.is_finite() && sigma >= 0.0) {
+                        return Err(MonteCarloError::InvalidSigma(sigma));
+                    }
+                }
+            }
+            Perturbation::WeatherYearSwap { solar_years } => {
+                if solar_years.is_empty() {
+                    return Err(MonteCarloError::NoWeatherYears);
+                }
+                if let Some((index, year)) = solar_years.iter().enumerate().find(|(_, y)| y.len() != steps) {
+                    return Err(MonteCarloError::WeatherYearLength { index, expected: steps, actual: year.len() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn sample<R: Rng>(&self, telemetry_points: &[TelemetryPoint], rng: &mut R) -> Vec<TelemetryPoint> {
+        match self {
+            Perturbation::BlockBootstrap { steps_per_day } => {
+                let days = telemetry_points.len() / steps_per_day;
+                let mut sample = Vec::with_capacity(telemetry_points.len());
+                while sample.len() < telemetry_points.len() {
+                    let start = rng.random_range(0..days) * steps_per_day;
+                    let remaining = telemetry_points.len() - sample.len();
+                    for point in telemetry_points[start..start + steps_per_day].iter().take(remaining) {
+                        // Keep the original time grid and grid outages, only the powers move.
+                        let slot = &telemetry_points[sample.len()];
+                        sample.push(TelemetryPoint::new(slot.duration(), point.solar_power(), point.load_power())
+                            .with_grid_available(slot.grid_available()));
+                    }
+                }
+                sample
+            }
+            Perturbation::MultiplicativeNoise { solar_sigma, load_sigma } => {
+                let solar_noise = Normal::new(0.0, *solar_sigma).expect("validated sigma");
+                let load_noise = Normal::new(0.0, *load_sigma).expect("validated sigma");
+                telemetry_points.iter()
+                    .map(|point| TelemetryPoint::new(
+                        point.duration(),
+                        scale(point.solar_power(), 1.0 + solar_noise.sample(rng)),
+                        scale(point.load_power(), 1.0 + load_noise.sample(rng)),
+                    ).with_grid_available(point.grid_available()))
+                    .collect()
+            }
+            Perturbation::WeatherYearSwap { solar_years } => {
+                let year = &solar_years[rng.random_range(0..solar_years.len())];
+                telemetry_points.iter().zip(year)
+                    .map(|(point, &solar)| TelemetryPoint::new(point.duration(), solar, point.load_power())
+                        .with_grid_available(point.grid_available()))
+                    .collect()
+            }
+        }
+    }
+}
+
+// Noise can push a large power past the valid range, so the result is clamped to it.
+fn scale(power: Power, factor: f64) -> Power {
+    Power::from_kw_saturating(power.as_kw() * factor.max(0.0))
+}
+
+/// Results of every sample, with percentile summaries across samples.
+#[derive(Debug, Clone)]
+pub struct MonteCarloResult {
+    kpis: Vec<ScenarioKpis>,
+    soc_trajectories: Vec<Vec<Energy>>, // indexed by [sample][step], including the initial state
+}
+
+impl MonteCarloResult {
+    pub fn samples(&self) -> usize {
+        self.kpis.len()
+    }
+
+    pub fn kpis(&self) -> &[ScenarioKpis] {
+        &self.kpis
+    }
+
+    /// The given percentiles (in [0, 1]) of a KPI across samples,
+    /// e.g. `result.kpi_percentiles(|k| k.grid_import(), &[0.1, 0.5, 0.9])`.
+    pub fn kpi_percentiles<F>(&self, kpi: F, percentiles: &[f64]) -> Result<Vec<Energy>, MonteCarloError>
+    where
+        F: Fn(&ScenarioKpis) -> Energy,
+    {
+        let values: Vec<f64> = self.kpis.iter().map(|k| kpi(k).as_kwh()).collect();
+        percentiles.iter().map(|&q| energy_percentile(&values, q)).collect()
+    }
+
+    /// One state of charge trajectory per requested percentile, each with a value
+    /// per step taken across samples.
+    pub fn soc_percentiles(&self, percentiles: &[f64]) -> Result<Vec<Vec<Energy>>, MonteCarloError> {
+        let steps = self.soc_trajectories.first().map_or(0, Vec::len);
+        percentiles.iter()
+            .map(|&q| {
+                (0..steps)
+                    .map(|step| {
+                        let values: Vec<f64> = self.soc_trajectories.iter().map(|t| t[step].as_kwh()).collect();
+                        energy_percentile(&values, q)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn energy_percentile(values: &[f64], quantile: f64) -> Result<Energy, MonteCarloError> {
+    if !(0.0..=1.0).contains(&quantile) {
+        return Err(MonteCarloError::InvalidPercentile(quantile));
+    }
+    let value = percentile(values, quantile).expect("at least one sample");
+    Ok(Energy::from_kwh(value).expect("percentile of valid energies should be valid"))
+}
+
+/// Simulates load following over `samples` perturbed copies of the telemetry.
+///
+/// Sample `i` is drawn from a generator seeded with `seed` and `i`, so results are
+/// reproducible and independent of how the samples are spread across threads.
+pub fn run_monte_carlo(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    perturbation: &Perturbation,
+    samples: usize,
+    seed: u64,
+) -> Result<MonteCarloResult, MonteCarloError> {
+    if samples == 0 {
+        return Err(MonteCarloError::NoSamples);
+    }
+    perturbation.validate(telemetry_points.len())?;
+
+    let runs = (0..samples).into_par_iter()
+        .map(|i| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(i as u64);
+            let telemetry = perturbation.sample(telemetry_points, &mut rng);
+
+            let mut trajectory = Vec::with_capacity(telemetry.len() + 1);
+            trajectory.push(initial_state.state_of_charge());
+            let steps = LoadFollowingSimulation::new(telemetry, battery.clone(), initial_state)
+                .inspect(|step| {
+                    if let Ok(step) = step {
+                        trajectory.push(step.state().state_of_charge());
+                    }
+                });
+            let kpis = ScenarioKpis::from_steps(steps, initial_state.state_of_charge())
+                .map_err(|e| MonteCarloError::ErrorSimulatingSample(e, i))?;
+            Ok((kpis, trajectory))
+        })
+        .collect::<Result<Vec<_>, MonteCarloError>>()?;
+
+    let (kpis, soc_trajectories) = runs.into_iter().unzip();
+    Ok(MonteCarloResult { kpis, soc_trajectories })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    // Three "days" of two steps each, with a different solar day each time.
+    fn test_telemetry() -> Vec<TelemetryPoint> {
+        [(4.0, 1.0), (0.0, 2.0), (8.0, 1.0), (0.0, 2.0), (2.0, 1.0), (0.0, 2.0)].iter()
+            .map(|&(solar, load)| TelemetryPoint::new(
+                hour!(1.0), Power::from_kw(solar).expect("ok"), Power::from_kw(load).expect("ok"),
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn test_monte_carlo_is_reproducible_for_a_seed() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let perturbation = Perturbation::MultiplicativeNoise { solar_sigma: 0.2, load_sigma: 0.1 };
+
+        let a = run_monte_carlo(&test_telemetry(), &battery, initial_state, &perturbation, 20, 42)
+            .expect("run should succeed");
+        let b = run_monte_carlo(&test_telemetry(), &battery, initial_state, &perturbation, 20, 42)
+            .expect("run should succeed");
+        let c = run_monte_carlo(&test_telemetry(), &battery, initial_state, &perturbation, 20, 7)
+            .expect("run should succeed");
+
+        let imports = |r: &MonteCarloResult| -> Vec<f64> { r.kpis().iter().map(|k| k.grid_import().as_kwh()).collect() };
+        assert_eq!(imports(&a), imports(&b));
+        assert_ne!(imports(&a), imports(&c));
+    }
+
+    #[test]
+    fn test_block_bootstrap_reuses_whole_days() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let perturbation = Perturbation::BlockBootstrap { steps_per_day: 2 };
+
+        let result = run_monte_carlo(&test_telemetry(), &battery, initial_state, &perturbation, 50, 1)
+            .expect("run should succeed");
+
+        // Every day stores its excess then covers 2 kWh of night load from it, so
+        // only the day with 1 kWh excess (2 kW solar) leaves the night short by 1 kWh.
+        let import = result.kpi_percentiles(|k| k.grid_import(), &[0.0, 1.0]).expect("valid percentiles");
+        assert_abs_diff_eq!(import[0].as_kwh(), 0.0, epsilon = EPSILON);
+        assert!(import[1].as_kwh() <= 3.0 + EPSILON);
+        let bands = result.soc_percentiles(&[0.1, 0.5, 0.9]).expect("valid percentiles");
+        assert_eq!(bands.len(), 3);
+        assert_eq!(bands[0].len(), 7);
+        assert!(bands.iter().all(|b| b[0] == Energy::zero()));
+    }
+
+    #[test]
+    fn test_weather_year_swap_uses_alternative_solar() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let perturbation = Perturbation::WeatherYearSwap { solar_years: vec![vec![Power::zero(); 6]] };
+
+        let result = run_monte_carlo(&test_telemetry(), &battery, initial_state, &perturbation, 3, 0)
+            .expect("run should succeed");
+
+        // No sun at all: every kWh of load comes from the grid.
+        for kpis in result.kpis() {
+            assert_abs_diff_eq!(kpis.grid_import().as_kwh(), 9.0, epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_monte_carlo_rejects_invalid_setup() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+
+        let noise = Perturbation::MultiplicativeNoise { solar_sigma: -1.0, load_sigma: 0.0 };
+        assert!(matches!(
+            run_monte_carlo(&telemetry, &battery, initial_state, &noise, 5, 0),
+            Err(MonteCarloError::InvalidSigma(_))
+        ));
+        let bootstrap = Perturbation::BlockBootstrap { steps_per_day: 48 };
+        assert!(matches!(
+            run_monte_carlo(&telemetry, &battery, initial_state, &bootstrap, 5, 0),
+            Err(MonteCarloError::NotEnoughDays(48))
+        ));
+        let swap = Perturbation::WeatherYearSwap { solar_years: vec![vec![Power::zero(); 2]] };
+        assert!(matches!(
+            run_monte_carlo(&telemetry, &battery, initial_state, &swap, 5, 0),
+            Err(MonteCarloError::WeatherYearLength { index: 0, expected: 6, actual: 2 })
+        ));
+        let result = run_monte_carlo(&telemetry, &battery, initial_state, &noise, 0, 0);
+        assert!(matches!(result, Err(MonteCarloError::NoSamples)));
+    }
+
+    #[test]
+    fn test_perturbations_keep_grid_availability() {
+        let telemetry: Vec<TelemetryPoint> = test_telemetry().into_iter().enumerate()
+            .map(|(i, point)| point.with_grid_available(i != 3))
+            .collect();
+        let perturbations = [
+            Perturbation::BlockBootstrap { steps_per_day: 2 },
+            Perturbation::MultiplicativeNoise { solar_sigma: 0.2, load_sigma: 0.1 },
+            Perturbation::WeatherYearSwap { solar_years: vec![vec![Power::zero(); 6]] },
+        ];
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        for perturbation in &perturbations {
+            let sample = perturbation.sample(&telemetry, &mut rng);
+            let available: Vec<bool> = sample.iter().map(|point| point.grid_available()).collect();
+            assert_eq!(available, vec![true, true, true, false, true, true]);
+        }
+    }
+
+    #[test]
+    fn test_noise_clamps_scaled_power() {
+        assert_eq!(scale(kw!(9e5), 10.0).as_kw(), 1e6);
+        assert_eq!(scale(kw!(2.0), -1.0).as_kw(), 0.0);
+        assert_abs_diff_eq!(scale(kw!(2.0), 1.5).as_kw(), 3.0, epsilon = EPSILON);
+    }
+}
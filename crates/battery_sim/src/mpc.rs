@@ -0,0 +1,224 @@
+use crate::battery::{Battery, BatteryState, BatteryError};
+use crate::envelope::OperatingEnvelope;
+use crate::forecast::ForecastProvider;
+use crate::optimiser::{optimise_dispatch, optimise_dispatch_within_envelope, Objective, OptimiserError};
+use crate::types::TelemetryPoint;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MpcError {
+    #[error("The horizon must be at least one step.")]
+    ZeroHorizon,
+    #[error("Invalid objective for the telemetry.")]
+    InvalidObjective(#[source] OptimiserError),
+    #[error("The envelope has {actual} steps, expected {expected}.")]
+    EnvelopeLengthMismatch { expected: usize, actual: usize },
+    #[error("Forecast on step {step} has {actual} points, expected between 1 and {expected}.")]
+    InvalidForecastLength { step: usize, expected: usize, actual: usize },
+    #[error("Optimising the horizon failed on step {1}.")]
+    ErrorOptimising(#[source] OptimiserError, usize),
+    #[error("Applying the set point failed on step {1}.")]
+    ErrorApplyingSetPoint(#[source] BatteryError, usize),
+}
+
+/// Receding-horizon control: on every step, optimise `horizon` steps of forecast,
+/// apply only the first set point to the actual telemetry and roll forward.
+///
+/// Returns the battery states in the same shape as `simulate_load_following`, so the
+/// result can be compared directly against the heuristic and perfect-foresight runs.
+pub fn simulate_mpc<F: ForecastProvider>(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    objective: &Objective,
+    forecaster: &F,
+    horizon: usize,
+) -> Result<Vec<BatteryState>, MpcError> {
+    receding_horizon(telemetry_points, battery, initial_state, objective, forecaster, horizon, None)
+}
+
+/// As `simulate_mpc`, keeping the site inside a per-step operating envelope. Every
+/// horizon is planned with `optimise_dispatch_within_envelope`, and the applied set
+/// point is constrained to the step's envelope.
+pub fn simulate_mpc_within_envelope<F: ForecastProvider>(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    objective: &Objective,
+    forecaster: &F,
+    horizon: usize,
+    envelopes: &[OperatingEnvelope],
+) -> Result<Vec<BatteryState>, MpcError> {
+    if envelopes.len() != telemetry_points.len() {
+        return Err(MpcError::EnvelopeLengthMismatch { expected: telemetry_points.len(), actual: envelopes.len() });
+    }
+    receding_horizon(telemetry_points, battery, initial_state, objective, forecaster, horizon, Some(envelopes))
+}
+
+fn receding_horizon<F: ForecastProvider>(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    objective: &Objective,
+    forecaster: &F,
+    horizon: usize,
+    envelopes: Option<&[OperatingEnvelope]>,
+) -> Result<Vec<BatteryState>, MpcError> {
+    if horizon == 0 {
+        return Err(MpcError::ZeroHorizon);
+    }
+    objective.validate(telemetry_points.len()).map_err(MpcError::InvalidObjective)?;
+
+    telemetry_points.iter().enumerate().try_fold(
+        vec![initial_state],
+        |mut states, (i, point)| {
+            let expected = horizon.min(telemetry_points.len() - i);
+            let forecast = forecaster.forecast(i, &telemetry_points[..i], expected);
+            if forecast.is_empty() || forecast.len() > expected {
+                return Err(MpcError::InvalidForecastLength { step: i, expected, actual: forecast.len() });
+            }
+
+            let window = objective.window(i, forecast.len());
+            let planned = match envelopes {
+                Some(envelopes) => optimise_dispatch_within_envelope(
+                    &forecast, battery, states[i], &window, &envelopes[i..i + forecast.len()],
+                ),
+                None => optimise_dispatch(&forecast, battery, states[i], &window),
+            }.map_err(|e| MpcError::ErrorOptimising(e, i))?;
+
+            let set_point = match envelopes {
+                Some(envelopes) => envelopes[i].constrain(planned[1].power(), point),
+                None => planned[1].power(),
+            };
+            let new_state = battery.step(&states[i], set_point, point.duration())
+                .map_err(|e| MpcError::ErrorApplyingSetPoint(e, i))?;
+            states.push(new_state);
+            Ok(states)
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast::PerfectForecast;
+    use crate::types::{AsEfficiency, Duration, Energy, Power};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-6;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 0.81.fraction())
+            .expect("battery should be valid")
+    }
+
+    fn test_telemetry() -> Vec<TelemetryPoint> {
+        vec![
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(3.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(4.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(4.0)),
+        ]
+    }
+
+    // Forecasts no solar and no load at all.
+    struct BlindForecast;
+
+    impl ForecastProvider for BlindForecast {
+        fn forecast(&self, step: usize, _history: &[TelemetryPoint], horizon: usize) -> Vec<TelemetryPoint> {
+            (step..step + horizon)
+                .map(|_| TelemetryPoint::new(hour!(1.0), Power::zero(), Power::zero()))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_mpc_rejects_zero_horizon() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+
+        let result = simulate_mpc(
+            &telemetry, &battery, initial_state, &Objective::GridImport, &PerfectForecast::new(&telemetry), 0,
+        );
+        assert!(matches!(result, Err(MpcError::ZeroHorizon)));
+    }
+
+    #[test]
+    fn test_mpc_full_horizon_matches_perfect_foresight() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+
+        let mpc = simulate_mpc(
+            &telemetry, &battery, initial_state, &Objective::GridImport, &PerfectForecast::new(&telemetry), 4,
+        ).expect("simulation should succeed");
+        let optimal = optimise_dispatch(&telemetry, &battery, initial_state, &Objective::GridImport)
+            .expect("optimisation should succeed");
+
+        assert_eq!(mpc.len(), optimal.len());
+        for (a, b) in mpc.iter().zip(&optimal) {
+            assert_abs_diff_eq!(a.state_of_charge_kwh(), b.state_of_charge_kwh(), epsilon = EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_mpc_within_envelope_plans_around_import_limits() {
+        let battery = Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid");
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(0.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(5.0)),
+        ];
+        let objective = Objective::EnergyCost { import_price: vec![0.1, 0.2, 1.0], export_price: vec![0.0; 3] };
+        let envelope = |min, max| OperatingEnvelope::new(min, max).expect("valid envelope");
+        let envelopes = vec![envelope(kw!(-3.0), kw!(1.0)), envelope(kw!(-3.0), kw!(5.0)), envelope(kw!(-3.0), kw!(0.0))];
+
+        let states = simulate_mpc_within_envelope(
+            &telemetry, &battery, initial_state, &objective, &PerfectForecast::new(&telemetry), 3, &envelopes,
+        ).expect("simulation should succeed");
+
+        // The first step's import limit is planned around by topping up on the second.
+        let power: Vec<f64> = states[1..].iter().map(|state| state.power_kw()).collect();
+        for (actual, expected) in power.iter().zip([1.0, 4.0, -5.0]) {
+            assert_abs_diff_eq!(*actual, expected, epsilon = EPSILON);
+        }
+        assert!(matches!(
+            simulate_mpc_within_envelope(
+                &telemetry, &battery, initial_state, &objective, &PerfectForecast::new(&telemetry), 3, &envelopes[..2],
+            ),
+            Err(MpcError::EnvelopeLengthMismatch { expected: 3, actual: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_mpc_single_step_horizon_is_myopic() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+
+        let states = simulate_mpc(
+            &telemetry, &battery, initial_state, &Objective::GridImport, &PerfectForecast::new(&telemetry), 1,
+        ).expect("simulation should succeed");
+
+        // With no view past the current step there is no value in storing the excess.
+        assert_abs_diff_eq!(states[1].power_kw(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_mpc_poor_forecast_leaves_battery_idle() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+
+        let states = simulate_mpc(
+            &telemetry, &battery, initial_state, &Objective::GridImport, &BlindForecast, 4,
+        ).expect("simulation should succeed");
+
+        assert_eq!(states.len(), 5);
+        for state in &states[1..] {
+            assert_abs_diff_eq!(state.power_kw(), 0.0, epsilon = EPSILON);
+            assert_abs_diff_eq!(state.state_of_charge_kwh(), 5.0, epsilon = EPSILON);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use microlp::{ComparisonOp, OptimizationDirection, Problem};
+use crate::battery::{Battery, BatteryState, BatteryError};
+use crate::envelope::OperatingEnvelope;
+use crate::types::{Power, TelemetryPoint};
+
+// Small cost on battery throughput so the solver never charges and discharges in the same step.
+const THROUGHPUT_PENALTY: f64 = 1e-6;
+// Cost per kWh of load shed to stay inside an envelope, well above any energy price.
+const UNSERVED_PENALTY: f64 = 1e3;
+
+/// What the perfect-foresight dispatch is optimised for.
+#[derive(Debug, Clone)]
+pub enum Objective {
+    /// Minimise the energy imported from the grid.
+    GridImport,
+    /// Minimise the cost of imported energy less the value of exported energy.
+    /// Prices are per kWh, one per telemetry point.
+    EnergyCost {
+        import_price: Vec<f64>,
+        export_price: Vec<f64>,
+    },
+}
+
+impl Objective {
+    /// Cost of a single step, given the telemetry and the battery state it produced.
+    pub fn step_cost(&self, step: usize, telemetry_point: &TelemetryPoint, battery_state: &BatteryState) -> f64 {
+        let (import_coefficient, export_coefficient) = self.grid_coefficients(step);
+        let grid_kw = battery_state.power_kw() - telemetry_point.excess_pv().as_kw();
+        let grid_coefficient = if grid_kw > 0.0 { import_coefficient } else { -export_coefficient };
+        grid_coefficient * grid_kw * telemetry_point.duration().as_hour()
+    }
+
+    // The objective restricted to `len` steps starting at `start`, for rolling horizons.
+    pub(crate) fn window(&self, start: usize, len: usize) -> Objective {
+        match self {
+            Objective::GridImport => Objective::GridImport,
+            Objective::EnergyCost { import_price, export_price } => Objective::EnergyCost {
+                import_price: import_price[start..start + len].to_vec(),
+                export_price: export_price[start..start + len].to_vec(),
+            },
+        }
+    }
+
+    pub(crate) fn validate(&self, steps: usize) -> Result<(), OptimiserError> {
+        match self {
+            Objective::GridImport => Ok(()),
+            Objective::EnergyCost { import_price, export_price } => {
+                if import_price.len() != steps {
+                    Err(OptimiserError::PriceLengthMismatch(import_price.len(), steps))
+                } else if export_price.len() != steps {
+                    Err(OptimiserError::PriceLengthMismatch(export_price.len(), steps))
+                } else if let Some((step, &price)) = import_price.iter().chain(export_price).enumerate()
+                    .find(|(_, price)| !price.is_finite())
+                {
+                    Err(OptimiserError::InvalidPrice(price, step % steps))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    // Objective coefficients (per kWh) for grid import and export on a given step.
+    fn grid_coefficients(&self, step: usize) -> (f64, f64) {
+        match self {
+            Objective::GridImport => (1.0, 0.0),
+            Objective::EnergyCost { import_price, export_price } => {
+                (import_price[step], -export_price[step])
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OptimiserError {
+    #[error("Price series has {0} values but the telemetry has {1} points.")]
+    PriceLengthMismatch(usize, usize),
+    #[error("Price {0} on step {1} is not a finite number.")]
+    InvalidPrice(f64, usize),
+    #[error("Solving the dispatch linear program failed.")]
+    ErrorSolving(#[source] microlp::Error),
+    #[error("Applying the optimal dispatch failed on step {1}.")]
+    ErrorApplyingDispatch(#[source] BatteryError, usize),
+    #[error("The envelope has {0} steps but the telemetry has {1} points.")]
+    EnvelopeLengthMismatch(usize, usize),
+}
+
+/// Finds the dispatch over the whole telemetry horizon that minimises `objective`,
+/// assuming the load and solar are known in advance.
+///
+/// Returns the battery states in the same shape as `simulate_load_following`:
+/// the initial state followed by the state after every telemetry point.
+pub fn optimise_dispatch(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    objective: &Objective,
+) -> Result<Vec<BatteryState>, OptimiserError> {
+    dispatch(telemetry_points, battery, initial_state, objective, None)
+}
+
+/// As `optimise_dispatch`, keeping the site's grid power inside a per-step operating
+/// envelope. The plan charges ahead of import limits and discharges ahead of export
+/// limits, and only sheds load or curtails PV where the battery cannot avoid it.
+pub fn optimise_dispatch_within_envelope(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    objective: &Objective,
+    envelopes: &[OperatingEnvelope],
+) -> Result<Vec<BatteryState>, OptimiserError> {
+    if envelopes.len() != telemetry_points.len() {
+        return Err(OptimiserError::EnvelopeLengthMismatch(envelopes.len(), telemetry_points.len()));
+    }
+    dispatch(telemetry_points, battery, initial_state, objective, Some(envelopes))
+}
+
+fn dispatch(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    objective: &Objective,
+    envelopes: Option<&[OperatingEnvelope]>,
+) -> Result<Vec<BatteryState>, OptimiserError> {
+    objective.validate(telemetry_points.len())?;
+
+    if telemetry_points.is_empty() {
+        return Ok(vec![initial_state]);
+    }
+
+    let charge_powers = optimal_charge_powers(telemetry_points, battery, initial_state, objective, envelopes)?;
+
+    // Replay the optimal set points through the battery model so the returned states
+    // are exactly what the battery would do, free of solver round-off.
+    telemetry_points.iter().zip(charge_powers).enumerate().try_fold(
+        vec![initial_state],
+        |mut states, (i, (point, power))| {
+            let new_state = battery.step(&states[i], power, point.duration())
+                .map_err(|e| OptimiserError::ErrorApplyingDispatch(e, i))?;
+            states.push(new_state);
+            Ok(states)
+        }
+    )
+}
+
+// Builds and solves the linear program, returning the net battery power for every step
+// (positive is charging).
+//
+// Per step t, with duration dt:
+//   charge c_t, discharge d_t in [0, max_power]
+//   state of charge s_t in [0, capacity]
+//   grid import g_t in [0, load_t + max_power], grid export x_t in [0, solar_t + max_power]
+//   g_t - x_t - c_t + d_t = load_t - solar_t
+//   s_t - s_{t-1} - eff * dt * c_t + dt / eff * d_t = 0
+//
+// With an envelope, shed load u_t in [0, load_t] and curtailed PV v_t in [0, solar_t]
+// join the balance, and g_t - x_t is bounded by the envelope:
+//   g_t - x_t - c_t + d_t + u_t - v_t = load_t - solar_t
+//   min_t <= g_t - x_t <= max_t
+fn optimal_charge_powers(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    objective: &Objective,
+    envelopes: Option<&[OperatingEnvelope]>,
+) -> Result<Vec<Power>, OptimiserError> {
+    let max_power = battery.max_power().as_kw();
+    let capacity = battery.capacity().as_kwh();
+    let efficiency = battery.efficiency().as_fraction();
+
+    let mut problem = Problem::new(OptimizationDirection::Minimize);
+    let mut flows = Vec::with_capacity(telemetry_points.len());
+    let mut previous_soc = None;
+
+    for (i, point) in telemetry_points.iter().enumerate() {
+        let dt = point.duration().as_hour();
+        let (import_coefficient, export_coefficient) = objective.grid_coefficients(i);
+
+        let charge = problem.add_var(THROUGHPUT_PENALTY * dt, (0.0, max_power));
+        let discharge = problem.add_var(THROUGHPUT_PENALTY * dt, (0.0, max_power));
+        let soc = problem.add_var(0.0, (0.0, capacity));
+        // Bounded by the physical flows, so negative import prices or export prices above
+        // the import price cannot drive import and export up together without limit.
+        let max_import = point.load_power().as_kw().max(0.0) + max_power;
+        let max_export = point.solar_power().as_kw().max(0.0) + max_power;
+        let import = problem.add_var(import_coefficient * dt, (0.0, max_import));
+        let export = problem.add_var(export_coefficient * dt, (0.0, max_export));
+
+        let mut balance = vec![(import, 1.0), (export, -1.0), (charge, -1.0), (discharge, 1.0)];
+        if let Some(envelope) = envelopes.map(|envelopes| envelopes[i]) {
+            let shed = problem.add_var(UNSERVED_PENALTY * dt, (0.0, point.load_power().as_kw().max(0.0)));
+            let curtail = problem.add_var(THROUGHPUT_PENALTY * dt, (0.0, point.solar_power().as_kw().max(0.0)));
+            balance.extend([(shed, 1.0), (curtail, -1.0)]);
+            problem.add_constraint(
+                [(import, 1.0), (export, -1.0)], ComparisonOp::Le, envelope.max_site_power().as_kw(),
+            );
+            problem.add_constraint(
+                [(import, 1.0), (export, -1.0)], ComparisonOp::Ge, envelope.min_site_power().as_kw(),
+            );
+        }
+        problem.add_constraint(balance, ComparisonOp::Eq, -point.excess_pv().as_kw());
+
+        match previous_soc {
+            None => problem.add_constraint(
+                [(soc, 1.0), (charge, -efficiency * dt), (discharge, dt / efficiency)],
+                ComparisonOp::Eq,
+                initial_state.state_of_charge_kwh(),
+            ),
+            Some(previous_soc) => problem.add_constraint(
+                [(soc, 1.0), (previous_soc, -1.0), (charge, -efficiency * dt), (discharge, dt / efficiency)],
+                ComparisonOp::Eq,
+                0.0,
+            ),
+        }
+
+        previous_soc = Some(soc);
+        flows.push((charge, discharge));
+    }
+
+    let solution = problem.solve().map_err(OptimiserError::ErrorSolving)?;
+
+    Ok(flows.into_iter()
+        .map(|(charge, discharge)| {
+            // The solver values are always finite and bounded by max_power.
+            Power::from_kw(solution[charge] - solution[discharge])
+                .expect("solver power should be finite")
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::simulate_load_following;
+    use crate::types::{AsEfficiency, Energy, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-6;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 0.81.fraction())
+            .expect("battery should be valid")
+    }
+
+    fn grid_import(telemetry: &[TelemetryPoint], states: &[BatteryState]) -> f64 {
+        telemetry.iter().zip(&states[1..])
+            .map(|(point, state)| {
+                let grid_kw = state.power_kw() - point.excess_pv().as_kw();
+                grid_kw.max(0.0) * point.duration().as_hour()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_optimise_dispatch_empty_telemetry() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+
+        let states = optimise_dispatch(&[], &battery, initial_state, &Objective::GridImport)
+            .expect("optimisation should succeed");
+
+        assert_eq!(states.len(), 1);
+        assert_abs_diff_eq!(states[0].state_of_charge_kwh(), 5.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_optimise_dispatch_matches_load_following_for_grid_import() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)),  // +5 kW excess
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),  // -2 kW deficit
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(3.0)),  // -3 kW deficit
+        ];
+
+        let optimal = optimise_dispatch(&telemetry, &battery, initial_state, &Objective::GridImport)
+            .expect("optimisation should succeed");
+        let heuristic = simulate_load_following(telemetry.clone(), battery.clone(), initial_state).expect("simulation should succeed");
+
+        assert_eq!(optimal.len(), 4);
+        // Load following is already optimal here, so the benchmark must match it.
+        assert_abs_diff_eq!(
+            grid_import(&telemetry, &optimal),
+            grid_import(&telemetry, &heuristic),
+            epsilon = EPSILON
+        );
+    }
+
+    #[test]
+    fn test_optimise_dispatch_never_worse_than_load_following() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(2.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(0.5), kw!(8.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(0.5), kw!(1.0), kw!(9.0)),
+            TelemetryPoint::new(hour!(0.5), kw!(7.0), kw!(2.0)),
+            TelemetryPoint::new(hour!(0.5), kw!(0.0), kw!(4.0)),
+        ];
+
+        let optimal = optimise_dispatch(&telemetry, &battery, initial_state, &Objective::GridImport)
+            .expect("optimisation should succeed");
+        let heuristic = simulate_load_following(telemetry.clone(), battery.clone(), initial_state).expect("simulation should succeed");
+
+        assert!(grid_import(&telemetry, &optimal) <= grid_import(&telemetry, &heuristic) + EPSILON);
+    }
+
+    #[test]
+    fn test_optimise_dispatch_arbitrages_energy_cost() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        // No solar, flat load: the only way to save is to buy cheap and avoid the expensive step.
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+        ];
+        let objective = Objective::EnergyCost {
+            import_price: vec![0.10, 0.50],
+            export_price: vec![0.0, 0.0],
+        };
+
+        let states = optimise_dispatch(&telemetry, &battery, initial_state, &objective)
+            .expect("optimisation should succeed");
+
+        // Charge just enough in the cheap step to cover the whole 2 kW load later: 2 / 0.9 / 0.9 kW
+        assert_abs_diff_eq!(states[1].power_kw(), 2.0 / 0.81, epsilon = EPSILON);
+        assert_abs_diff_eq!(states[2].power_kw(), -2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(states[2].state_of_charge_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_optimise_dispatch_rejects_mismatched_prices() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0))];
+        let objective = Objective::EnergyCost {
+            import_price: vec![0.10, 0.50],
+            export_price: vec![0.0],
+        };
+
+        let result = optimise_dispatch(&telemetry, &battery, initial_state, &objective);
+        assert!(matches!(result, Err(OptimiserError::PriceLengthMismatch(2, 1))));
+    }
+
+    #[test]
+    fn test_optimise_dispatch_with_negative_import_price() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+        ];
+        let objective = Objective::EnergyCost {
+            import_price: vec![-0.10, 0.50],
+            export_price: vec![0.0, 0.0],
+        };
+
+        let states = optimise_dispatch(&telemetry, &battery, initial_state, &objective)
+            .expect("optimisation should succeed");
+
+        // Being paid to import, the battery stores enough to cover the dear step.
+        assert!(states[1].power_kw() >= 2.0 / 0.81 - EPSILON);
+        assert_abs_diff_eq!(states[2].power_kw(), -2.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_optimise_dispatch_with_export_above_import_price() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(1.0), kw!(2.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(3.0), kw!(1.0)),
+        ];
+        let objective = Objective::EnergyCost {
+            import_price: vec![0.10, 0.10],
+            export_price: vec![0.30, 0.30],
+        };
+
+        let states = optimise_dispatch(&telemetry, &battery, initial_state, &objective)
+            .expect("optimisation should succeed");
+
+        assert_eq!(states.len(), 3);
+        assert!(states.iter().all(|state| state.power_kw().abs() <= 5.0 + EPSILON));
+    }
+
+    #[test]
+    fn test_optimise_dispatch_rejects_nan_prices() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)); 2];
+        let objective = Objective::EnergyCost {
+            import_price: vec![0.10, 0.50],
+            export_price: vec![0.0, f64::NAN],
+        };
+
+        let result = optimise_dispatch(&telemetry, &battery, initial_state, &objective);
+        assert!(matches!(result, Err(OptimiserError::InvalidPrice(price, 1)) if price.is_nan()));
+    }
+}
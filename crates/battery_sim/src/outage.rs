@@ -0,0 +1,465 @@
+use crate::battery::{Battery, BatteryError, BatteryModel, BatteryState};
+use crate::stats::percentile;
+use crate::types::{Duration, Energy, Power, TelemetryPoint};
+
+// Shortfalls smaller than this are treated as rounding rather than lost supply.
+const UNSERVED_TOLERANCE_KWH: f64 = 1e-9;
+// Backup horizon left over from summing step durations that is only round-off.
+const HORIZON_TOLERANCE_HOURS: f64 = 1e-9;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OutageError {
+    #[error("Critical load fraction {0} must be between 0 and 1.")]
+    InvalidCriticalLoadFraction(f64),
+    #[error("Simulating islanded operation failed on step {1}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize),
+    #[error("Expected {expected} battery states for the telemetry, got {actual}.")]
+    LengthMismatch { expected: usize, actual: usize },
+    #[error("Backup horizon {0} hours must be finite and positive.")]
+    InvalidHorizon(f64),
+    #[error("Percentile {0} must be between 0 and 1.")]
+    InvalidPercentile(f64),
+}
+
+// Serves the critical load from PV and the battery alone for one step.
+// Returns the new state, the critical load demand beyond PV, the part of it that went
+// unserved, and the PV curtailed.
+pub(crate) fn island_step<M: BatteryModel>(
+    battery: &M,
+    state: &BatteryState,
+    solar_power: Power,
+    load_power: Power,
+    critical_load_fraction: f64,
+    duration: Duration,
+) -> Result<(BatteryState, Energy, Energy, Energy), BatteryError> {
+    let critical_load = Power::from_kw(load_power.as_kw() * critical_load_fraction)
+        .expect("fraction of a valid load should be valid");
+    let net_power = solar_power - critical_load;
+    let state = battery.step(state, net_power, duration)?;
+    if net_power > Power::zero() {
+        let curtailed = (net_power - state.power()) * duration;
+        Ok((state, Energy::zero(), Energy::zero(), curtailed))
+    } else {
+        let demand = -net_power * duration;
+        let shortfall = (state.power() - net_power) * duration;
+        Ok((state, demand, shortfall, Energy::zero()))
+    }
+}
+
+fn validate_fraction(critical_load_fraction: f64) -> Result<(), OutageError> {
+    if !(0.0..=1.0).contains(&critical_load_fraction) {
+        return Err(OutageError::InvalidCriticalLoadFraction(critical_load_fraction));
+    }
+    Ok(())
+}
+
+/// How the site fared over one contiguous outage.
+#[derive(Debug, Clone)]
+pub struct OutageReport {
+    start_step: usize,
+    state_of_charge_at_start: Energy,
+    duration_hours: f64,
+    survival_hours: f64,
+    unserved_energy: Energy,
+    supply_lost: bool,
+}
+
+impl OutageReport {
+    pub fn start_step(&self) -> usize {
+        self.start_step
+    }
+
+    pub fn state_of_charge_at_start(&self) -> Energy {
+        self.state_of_charge_at_start
+    }
+
+    pub fn duration_hours(&self) -> f64 {
+        self.duration_hours
+    }
+
+    /// Hours the critical load was fully served before the first shortfall. A step with
+    /// a shortfall counts for the fraction of its critical load that was served.
+    pub fn survival_hours(&self) -> f64 {
+        self.survival_hours
+    }
+
+    /// Critical load that could not be served during the outage.
+    pub fn unserved_energy(&self) -> Energy {
+        self.unserved_energy
+    }
+
+    /// Whether the critical load was served for the whole outage.
+    pub fn survived(&self) -> bool {
+        !self.supply_lost
+    }
+}
+
+/// States and resilience results of an islanding simulation.
+#[derive(Debug, Clone)]
+pub struct OutageSimulationResult {
+    states: Vec<BatteryState>,
+    outages: Vec<OutageReport>,
+    curtailed_pv: Energy,
+}
+
+impl OutageSimulationResult {
+    /// The initial state followed by the state after every telemetry point.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// One report per contiguous run of steps without the grid, in time order.
+    pub fn outages(&self) -> &[OutageReport] {
+        &self.outages
+    }
+
+    /// Critical load not served across all outages.
+    pub fn unserved_energy(&self) -> Energy {
+        self.outages.iter().fold(Energy::zero(), |total, outage| total + outage.unserved_energy)
+    }
+
+    /// Solar generation that had nowhere to go during outages.
+    pub fn curtailed_pv(&self) -> Energy {
+        self.curtailed_pv
+    }
+}
+
+/// Simulates load following while the grid is available and islanded operation while
+/// it is not.
+///
+/// During an outage only `critical_load_fraction` of the load is served, by PV and the
+/// battery alone: nothing is imported or exported, and PV the battery cannot absorb
+/// is curtailed.
+pub fn simulate_with_outages(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    critical_load_fraction: f64,
+) -> Result<OutageSimulationResult, OutageError> {
+    validate_fraction(critical_load_fraction)?;
+
+    let mut states = Vec::with_capacity(telemetry_points.len() + 1);
+    states.push(initial_state);
+    let mut outages: Vec<OutageReport> = Vec::new();
+    let mut curtailed_pv = Energy::zero();
+    let mut state = initial_state;
+    let mut in_outage = false;
+
+    for (step, telemetry_point) in telemetry_points.iter().enumerate() {
+        if telemetry_point.grid_available() {
+            state = battery.load_follow_step(&state, telemetry_point)
+                .map_err(|e| OutageError::ErrorSimulatingStep(e, step))?;
+            in_outage = false;
+            states.push(state);
+            continue;
+        }
+
+        if !in_outage {
+            outages.push(OutageReport {
+                start_step: step,
+                state_of_charge_at_start: state.state_of_charge(),
+                duration_hours: 0.0,
+                survival_hours: 0.0,
+                unserved_energy: Energy::zero(),
+                supply_lost: false,
+            });
+            in_outage = true;
+        }
+
+        let duration = telemetry_point.duration();
+        let (next, demand, shortfall, curtailed) = island_step(
+            battery, &state, telemetry_point.solar_power(), telemetry_point.load_power(),
+            critical_load_fraction, duration,
+        ).map_err(|e| OutageError::ErrorSimulatingStep(e, step))?;
+        state = next;
+        states.push(state);
+        curtailed_pv = curtailed_pv + curtailed;
+
+        let outage = outages.last_mut().expect("outage started above");
+        outage.duration_hours += duration.as_hour();
+        if shortfall.as_kwh() > UNSERVED_TOLERANCE_KWH {
+            outage.unserved_energy = outage.unserved_energy + shortfall;
+            if !outage.supply_lost {
+                outage.survival_hours += served_hours(duration, demand, shortfall);
+                outage.supply_lost = true;
+            }
+        } else if !outage.supply_lost {
+            outage.survival_hours += duration.as_hour();
+        }
+    }
+
+    Ok(OutageSimulationResult { states, outages, curtailed_pv })
+}
+
+// Time into a step with a shortfall that the critical load was still served for.
+fn served_hours(duration: Duration, demand: Energy, shortfall: Energy) -> f64 {
+    duration.as_hour() * (1.0 - shortfall.as_kwh() / demand.as_kwh())
+}
+
+/// Hours of autonomy at every step of a simulation.
+#[derive(Debug, Clone)]
+pub struct BackupDurations {
+    hours: Vec<f64>,
+    max_hours: f64,
+}
+
+impl BackupDurations {
+    /// Autonomy hours if the grid failed at the start of each step.
+    pub fn hours(&self) -> &[f64] {
+        &self.hours
+    }
+
+    /// Shortest autonomy across steps, or 0 without any steps.
+    pub fn min(&self) -> f64 {
+        self.hours.iter().copied().reduce(f64::min).unwrap_or(0.0)
+    }
+
+    /// Mean autonomy across steps, or 0 without any steps.
+    pub fn mean(&self) -> f64 {
+        if self.hours.is_empty() {
+            return 0.0;
+        }
+        self.hours.iter().sum::<f64>() / self.hours.len() as f64
+    }
+
+    /// Percentile (in [0, 1]) of the autonomy hours across steps.
+    pub fn percentile(&self, quantile: f64) -> Result<f64, OutageError> {
+        if !(0.0..=1.0).contains(&quantile) {
+            return Err(OutageError::InvalidPercentile(quantile));
+        }
+        Ok(percentile(&self.hours, quantile).unwrap_or(0.0))
+    }
+
+    /// Fraction of steps with at least `hours` of autonomy, or 0 without any steps.
+    pub fn fraction_at_least(&self, hours: f64) -> f64 {
+        if self.hours.is_empty() {
+            return 0.0;
+        }
+        let count = self.hours.iter().filter(|&&h| h >= hours - 1e-9).count();
+        count as f64 / self.hours.len() as f64
+    }
+
+    /// The horizon the estimates were capped at.
+    pub fn max_hours(&self) -> f64 {
+        self.max_hours
+    }
+}
+
+/// Estimates, for every step, how long PV and the battery could carry the critical load
+/// if the grid failed at the start of that step.
+///
+/// `states` is the output of a simulation over `telemetry_points`: the initial state
+/// followed by the state after every step, so step `i` starts from `states[i]`. Each
+/// estimate islands the site over the telemetry that follows, and is capped at
+/// `max_hours` and at the end of the telemetry.
+pub fn backup_durations(
+    telemetry_points: &[TelemetryPoint],
+    states: &[BatteryState],
+    battery: &Battery,
+    critical_load_fraction: f64,
+    max_hours: f64,
+) -> Result<BackupDurations, OutageError> {
+    validate_fraction(critical_load_fraction)?;
+    if !(max_hours.is_finite() && max_hours > 0.0) {
+        return Err(OutageError::InvalidHorizon(max_hours));
+    }
+    if states.len() != telemetry_points.len() + 1 {
+        return Err(OutageError::LengthMismatch {
+            expected: telemetry_points.len() + 1,
+            actual: states.len(),
+        });
+    }
+
+    let hours = (0..telemetry_points.len())
+        .map(|start| {
+            let mut state = states[start];
+            let mut survived = 0.0;
+            for (offset, point) in telemetry_points[start..].iter().enumerate() {
+                let remaining = max_hours - survived;
+                if remaining <= HORIZON_TOLERANCE_HOURS {
+                    break;
+                }
+                let duration = if point.duration().as_hour() > remaining {
+                    match Duration::from_hour(remaining) {
+                        Ok(duration) => duration,
+                        Err(_) => break,
+                    }
+                } else {
+                    point.duration()
+                };
+                let (next, demand, shortfall, _) = island_step(
+                    battery, &state, point.solar_power(), point.load_power(), critical_load_fraction, duration,
+                ).map_err(|e| OutageError::ErrorSimulatingStep(e, start + offset))?;
+                if shortfall.as_kwh() > UNSERVED_TOLERANCE_KWH {
+                    return Ok(survived + served_hours(duration, demand, shortfall));
+                }
+                survived += duration.as_hour();
+                state = next;
+            }
+            Ok(survived.min(max_hours))
+        })
+        .collect::<Result<Vec<f64>, OutageError>>()?;
+
+    Ok(BackupDurations { hours, max_hours })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    #[test]
+    fn test_outage_without_shortfall() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)).with_grid_available(false),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)).with_grid_available(false),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)),
+        ];
+
+        let result = simulate_with_outages(&telemetry, &battery, initial_state, 1.0)
+            .expect("simulation should succeed");
+
+        assert_eq!(result.outages().len(), 1);
+        let outage = &result.outages()[0];
+        assert_eq!(outage.start_step(), 1);
+        assert_abs_diff_eq!(outage.state_of_charge_at_start().as_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(outage.duration_hours(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(outage.survival_hours(), 2.0, epsilon = EPSILON);
+        assert!(outage.survived());
+        assert_abs_diff_eq!(result.states()[4].state_of_charge_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_outage_reports_unserved_critical_load() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(3.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(4.0)).with_grid_available(false),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(4.0)).with_grid_available(false),
+        ];
+
+        // Half the load is critical: 2 kWh an hour from 3 kWh stored.
+        let result = simulate_with_outages(&telemetry, &battery, initial_state, 0.5)
+            .expect("simulation should succeed");
+
+        let outage = &result.outages()[0];
+        assert!(!outage.survived());
+        assert_abs_diff_eq!(outage.unserved_energy().as_kwh(), 1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(outage.survival_hours(), 1.5, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.unserved_energy().as_kwh(), 1.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_outage_curtails_pv_and_does_not_export() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(8.0), Power::zero()).expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)).with_grid_available(false),
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(1.0)).with_grid_available(false),
+        ];
+
+        let result = simulate_with_outages(&telemetry, &battery, initial_state, 1.0)
+            .expect("simulation should succeed");
+
+        // 2 kWh fills the battery, the other 3 kWh is curtailed; full for the second outage.
+        assert_eq!(result.outages().len(), 2);
+        assert_abs_diff_eq!(result.outages()[1].state_of_charge_at_start().as_kwh(), 10.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.curtailed_pv().as_kwh(), 3.0 + 5.0, epsilon = EPSILON);
+        assert!(result.outages().iter().all(|outage| outage.survived()));
+    }
+
+    #[test]
+    fn test_rejects_invalid_critical_load_fraction() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(3.0), Power::zero()).expect("valid state");
+        let result = simulate_with_outages(&[], &battery, initial_state, 1.5);
+        assert!(matches!(result, Err(OutageError::InvalidCriticalLoadFraction(_))));
+    }
+
+    #[test]
+    fn test_backup_durations_follow_state_of_charge() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(4.0), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)); 6];
+        let states = crate::simulation::simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+
+        let backup = backup_durations(&telemetry, &states, &battery, 1.0, 24.0).expect("valid inputs");
+
+        // 4 kWh at 1 kW, less an hour for every step already taken.
+        assert_eq!(backup.hours(), &[4.0, 3.0, 2.0, 1.0, 0.0, 0.0]);
+        assert_abs_diff_eq!(backup.min(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(backup.mean(), 10.0 / 6.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(backup.percentile(0.5).expect("valid"), 1.5, epsilon = EPSILON);
+        assert_abs_diff_eq!(backup.fraction_at_least(2.0), 0.5, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_backup_durations_capped_by_horizon_and_telemetry() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(10.0), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(2.0), kw!(1.0), kw!(2.0)); 3];
+        let states = crate::simulation::simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+
+        let capped = backup_durations(&telemetry, &states, &battery, 1.0, 3.0).expect("valid inputs");
+        assert_eq!(capped.hours(), &[3.0, 3.0, 2.0]);
+
+        // Half the load is critical and PV covers it, so autonomy runs to the end of the data.
+        let critical = backup_durations(&telemetry, &states, &battery, 0.5, 100.0).expect("valid inputs");
+        assert_eq!(critical.hours(), &[6.0, 4.0, 2.0]);
+    }
+
+    #[test]
+    fn test_backup_durations_partial_step() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(3.0), Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(2.0), kw!(0.0), kw!(2.0))];
+        let states = vec![initial_state, initial_state];
+
+        let backup = backup_durations(&telemetry, &states, &battery, 1.0, 24.0).expect("valid inputs");
+
+        assert_abs_diff_eq!(backup.hours()[0], 1.5, epsilon = EPSILON);
+        assert!(matches!(
+            backup_durations(&telemetry, &states[..1], &battery, 1.0, 24.0),
+            Err(OutageError::LengthMismatch { expected: 2, actual: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_backup_durations_stop_at_round_off_horizon() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(10.0), Power::zero()).expect("valid state");
+        // Twenty 0.1 h steps sum to slightly less than the 1 h horizon.
+        let telemetry = vec![TelemetryPoint::new(hour!(0.1), kw!(0.0), kw!(1.0)); 20];
+        let states = vec![initial_state; 21];
+
+        let backup = backup_durations(&telemetry, &states, &battery, 1.0, 1.0).expect("valid inputs");
+
+        assert_abs_diff_eq!(backup.hours()[0], 1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(backup.hours()[19], 0.1, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_backup_durations_without_steps() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(10.0), Power::zero()).expect("valid state");
+
+        let backup = backup_durations(&[], &[initial_state], &battery, 1.0, 24.0).expect("valid inputs");
+
+        assert_eq!(backup.min(), 0.0);
+        assert_eq!(backup.mean(), 0.0);
+        assert_eq!(backup.fraction_at_least(1.0), 0.0);
+    }
+}
@@ -0,0 +1,353 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Exp, LogNormal};
+use rayon::prelude::*;
+use crate::battery::{Battery, BatteryState};
+use crate::outage::{simulate_with_outages, OutageError, OutageReport};
+use crate::types::TelemetryPoint;
+
+const HOURS_PER_YEAR: f64 = 8760.0;
+// Slack when comparing survival time against the requirement.
+const SURVIVAL_TOLERANCE_HOURS: f64 = 1e-9;
+
+/// Distribution of outage durations, in hours.
+#[derive(Debug, Clone)]
+pub enum DurationDistribution {
+    Fixed { hours: f64 },
+    Exponential { mean_hours: f64 },
+    LogNormal { median_hours: f64, sigma: f64 },
+    Uniform { min_hours: f64, max_hours: f64 },
+}
+
+/// How often outages happen and how long they last. Outages arrive as a Poisson process.
+#[derive(Debug, Clone)]
+pub enum OutageModel {
+    /// From utility reliability indices: SAIFI interruptions per year and SAIDI minutes
+    /// per year. Durations are exponential with mean SAIDI / SAIFI (CAIDI).
+    ReliabilityIndices { saifi: f64, saidi_minutes: f64 },
+    /// An explicit outage rate and duration distribution.
+    Explicit { outages_per_year: f64, duration: DurationDistribution },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OutageScenarioError {
+    #[error("Outage rate {0} must be finite and positive.")]
+    InvalidRate(f64),
+    #[error("Outage duration parameter {0} is invalid.")]
+    InvalidDuration(f64),
+    #[error("Required backup hours {0} must be finite and non-negative.")]
+    InvalidRequiredHours(f64),
+    #[error("At least one draw is required.")]
+    NoDraws,
+    #[error("Draw {1} failed to simulate.")]
+    ErrorSimulatingDraw(#[source] OutageError, usize),
+}
+
+impl DurationDistribution {
+    fn validate(&self) -> Result<(), OutageScenarioError> {
+        let positive = |value: f64| {
+            if value.is_finite() && value > 0.0 { Ok(()) } else { Err(OutageScenarioError::InvalidDuration(value)) }
+        };
+        match *self {
+            DurationDistribution::Fixed { hours } => positive(hours),
+            DurationDistribution::Exponential { mean_hours } => positive(mean_hours),
+            DurationDistribution::LogNormal { median_hours, sigma } => {
+                positive(median_hours)?;
+                if !(sigma.is_finite() && sigma >= 0.0) {
+                    return Err(OutageScenarioError::InvalidDuration(sigma));
+                }
+                Ok(())
+            }
+            DurationDistribution::Uniform { min_hours, max_hours } => {
+                positive(min_hours)?;
+                positive(max_hours)?;
+                if max_hours < min_hours {
+                    return Err(OutageScenarioError::InvalidDuration(max_hours));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match *self {
+            DurationDistribution::Fixed { hours } => hours,
+            DurationDistribution::Exponential { mean_hours } => {
+                Exp::new(1.0 / mean_hours).expect("validated mean").sample(rng)
+            }
+            DurationDistribution::LogNormal { median_hours, sigma } => {
+                LogNormal::new(median_hours.ln(), sigma).expect("validated parameters").sample(rng)
+            }
+            DurationDistribution::Uniform { min_hours, max_hours } => {
+                if max_hours > min_hours { rng.random_range(min_hours..max_hours) } else { min_hours }
+            }
+        }
+    }
+}
+
+impl OutageModel {
+    fn rate_and_duration(&self) -> Result<(f64, DurationDistribution), OutageScenarioError> {
+        let (rate, duration) = match self {
+            OutageModel::ReliabilityIndices { saifi, saidi_minutes } => {
+                if !(saifi.is_finite() && *saifi > 0.0) {
+                    return Err(OutageScenarioError::InvalidRate(*saifi));
+                }
+                let mean_hours = saidi_minutes / saifi / 60.0;
+                (*saifi, DurationDistribution::Exponential { mean_hours })
+            }
+            OutageModel::Explicit { outages_per_year, duration } => (*outages_per_year, duration.clone()),
+        };
+        if !(rate.is_finite() && rate > 0.0) {
+            return Err(OutageScenarioError::InvalidRate(rate));
+        }
+        duration.validate()?;
+        Ok((rate, duration))
+    }
+
+    /// Draws the outages over `horizon_hours`. Outages do not overlap; the last one may
+    /// run past the horizon.
+    pub fn generate<R: Rng>(&self, horizon_hours: f64, rng: &mut R) -> Result<Vec<SyntheticOutage>, OutageScenarioError> {
+        let (rate, duration) = self.rate_and_duration()?;
+        let arrivals = Exp::new(rate / HOURS_PER_YEAR).expect("validated rate");
+        let mut outages = Vec::new();
+        let mut time = arrivals.sample(rng);
+        while time < horizon_hours {
+            let duration_hours = duration.sample(rng);
+            outages.push(SyntheticOutage { start_hours: time, duration_hours });
+            time += duration_hours + arrivals.sample(rng);
+        }
+        Ok(outages)
+    }
+}
+
+/// An outage at a time measured from the start of the telemetry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyntheticOutage {
+    start_hours: f64,
+    duration_hours: f64,
+}
+
+impl SyntheticOutage {
+    pub fn new(start_hours: f64, duration_hours: f64) -> Self {
+        SyntheticOutage { start_hours, duration_hours }
+    }
+
+    pub fn start_hours(&self) -> f64 {
+        self.start_hours
+    }
+
+    pub fn duration_hours(&self) -> f64 {
+        self.duration_hours
+    }
+
+    fn overlaps(&self, start_hours: f64, end_hours: f64) -> bool {
+        self.start_hours < end_hours && self.start_hours + self.duration_hours > start_hours
+    }
+}
+
+/// Copies the telemetry, marking the grid unavailable on every step that overlaps an outage.
+/// Steps already marked unavailable stay that way.
+pub fn inject_outages(telemetry_points: &[TelemetryPoint], outages: &[SyntheticOutage]) -> Vec<TelemetryPoint> {
+    let mut elapsed_hours = 0.0;
+    telemetry_points.iter()
+        .map(|point| {
+            let end_hours = elapsed_hours + point.duration().as_hour();
+            let in_outage = outages.iter().any(|outage| outage.overlaps(elapsed_hours, end_hours));
+            elapsed_hours = end_hours;
+            point.clone().with_grid_available(point.grid_available() && !in_outage)
+        })
+        .collect()
+}
+
+/// One seeded draw of outages and how the site coped with them.
+#[derive(Debug, Clone)]
+pub struct OutageDraw {
+    outages: Vec<SyntheticOutage>,
+    reports: Vec<OutageReport>,
+    requirement_met: bool,
+}
+
+impl OutageDraw {
+    pub fn outages(&self) -> &[SyntheticOutage] {
+        &self.outages
+    }
+
+    /// Simulated outages, merged where generated outages share or touch a step.
+    pub fn reports(&self) -> &[OutageReport] {
+        &self.reports
+    }
+
+    pub fn requirement_met(&self) -> bool {
+        self.requirement_met
+    }
+}
+
+/// Results of a backup study over many outage draws.
+#[derive(Debug, Clone)]
+pub struct BackupStudy {
+    draws: Vec<OutageDraw>,
+}
+
+impl BackupStudy {
+    pub fn draws(&self) -> &[OutageDraw] {
+        &self.draws
+    }
+
+    /// Fraction of draws in which every outage met the backup requirement.
+    pub fn probability_of_meeting_requirement(&self) -> f64 {
+        let met = self.draws.iter().filter(|draw| draw.requirement_met).count();
+        met as f64 / self.draws.len() as f64
+    }
+}
+
+/// Simulates `draws` seeded outage scenarios over the telemetry, in parallel.
+///
+/// A draw meets the requirement when every outage either survives to its end or carries
+/// the critical load for at least `required_hours`. Draw `i` uses stream `i` of a
+/// ChaCha8 generator seeded with `seed`, so results do not depend on thread scheduling.
+#[allow(clippy::too_many_arguments)]
+pub fn run_backup_study(
+    telemetry_points: &[TelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    model: &OutageModel,
+    critical_load_fraction: f64,
+    required_hours: f64,
+    draws: usize,
+    seed: u64,
+) -> Result<BackupStudy, OutageScenarioError> {
+    if draws == 0 {
+        return Err(OutageScenarioError::NoDraws);
+    }
+    if !(required_hours.is_finite() && required_hours >= 0.0) {
+        return Err(OutageScenarioError::InvalidRequiredHours(required_hours));
+    }
+    model.rate_and_duration()?;
+    let horizon_hours: f64 = telemetry_points.iter().map(|point| point.duration().as_hour()).sum();
+
+    let draws = (0..draws).into_par_iter()
+        .map(|i| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(i as u64);
+            let outages = model.generate(horizon_hours, &mut rng)?;
+            let telemetry = inject_outages(telemetry_points, &outages);
+            let result = simulate_with_outages(&telemetry, battery, initial_state, critical_load_fraction)
+                .map_err(|e| OutageScenarioError::ErrorSimulatingDraw(e, i))?;
+            let reports = result.outages().to_vec();
+            let requirement_met = reports.iter().all(|report| {
+                report.survived() || report.survival_hours() >= required_hours - SURVIVAL_TOLERANCE_HOURS
+            });
+            Ok(OutageDraw { outages, reports, requirement_met })
+        })
+        .collect::<Result<Vec<_>, OutageScenarioError>>()?;
+
+    Ok(BackupStudy { draws })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration, Energy, Power};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+
+    fn year_of_hours() -> Vec<TelemetryPoint> {
+        vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)); 8760]
+    }
+
+    #[test]
+    fn test_reliability_indices_match_expected_rate_and_duration() {
+        let model = OutageModel::ReliabilityIndices { saifi: 2.0, saidi_minutes: 240.0 };
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let horizon = 1000.0 * HOURS_PER_YEAR;
+
+        let outages = model.generate(horizon, &mut rng).expect("valid model");
+
+        let per_year = outages.len() as f64 / 1000.0;
+        let mean_duration = outages.iter().map(|o| o.duration_hours()).sum::<f64>() / outages.len() as f64;
+        assert_abs_diff_eq!(per_year, 2.0, epsilon = 0.1);
+        assert_abs_diff_eq!(mean_duration, 2.0, epsilon = 0.1);
+        assert!(outages.windows(2).all(|w| w[1].start_hours() >= w[0].start_hours() + w[0].duration_hours()));
+    }
+
+    // Sunny 12 hour days that refill a 10 kWh battery, then 12 hour nights at 1 kW.
+    fn days_and_nights() -> Vec<TelemetryPoint> {
+        (0..8760)
+            .map(|hour| if hour % 24 < 12 {
+                TelemetryPoint::new(hour!(1.0), kw!(3.0), kw!(1.0))
+            } else {
+                TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inject_outages_marks_overlapping_steps() {
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(1.0)); 5];
+        let outages = [SyntheticOutage::new(1.5, 1.0)];
+
+        let injected = inject_outages(&telemetry, &outages);
+
+        let available: Vec<bool> = injected.iter().map(|p| p.grid_available()).collect();
+        assert_eq!(available, vec![true, false, false, true, true]);
+    }
+
+    #[test]
+    fn test_backup_study_is_reproducible() {
+        let battery = Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("valid battery");
+        let initial_state = battery.init_state(kwh!(10.0), Power::zero()).expect("valid state");
+        let model = OutageModel::Explicit {
+            outages_per_year: 4.0,
+            duration: DurationDistribution::Uniform { min_hours: 1.0, max_hours: 20.0 },
+        };
+        let telemetry = days_and_nights();
+
+        let first = run_backup_study(&telemetry, &battery, initial_state, &model, 1.0, 8.0, 16, 42)
+            .expect("study should succeed");
+        let second = run_backup_study(&telemetry, &battery, initial_state, &model, 1.0, 8.0, 16, 42)
+            .expect("study should succeed");
+
+        let probability = first.probability_of_meeting_requirement();
+        assert_eq!(probability, second.probability_of_meeting_requirement());
+        assert!(probability > 0.0 && probability < 1.0);
+        for (a, b) in first.draws().iter().zip(second.draws()) {
+            assert_eq!(a.outages(), b.outages());
+        }
+    }
+
+    #[test]
+    fn test_backup_requirement_against_battery_size() {
+        // No solar and a constant 1 kW load, so a battery lasts its capacity in hours.
+        let model = OutageModel::Explicit {
+            outages_per_year: 50.0,
+            duration: DurationDistribution::Fixed { hours: 24.0 },
+        };
+        let telemetry = year_of_hours();
+        let probability = |capacity_kwh: f64, required_hours: f64| {
+            let capacity = Energy::from_kwh(capacity_kwh).expect("valid capacity");
+            let battery = Battery::new(capacity, kw!(5.0), 1.0.fraction()).expect("valid battery");
+            let initial_state = battery.init_state(capacity, Power::zero()).expect("valid state");
+            run_backup_study(&telemetry, &battery, initial_state, &model, 1.0, required_hours, 8, 1)
+                .expect("study should succeed")
+                .probability_of_meeting_requirement()
+        };
+
+        assert_abs_diff_eq!(probability(0.5, 0.0), 1.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(probability(0.5, 1.0), 0.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(probability(1e5, 24.0), 1.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_backup_study_rejects_invalid_inputs() {
+        let battery = Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("valid battery");
+        let initial_state = battery.init_state(kwh!(10.0), Power::zero()).expect("valid state");
+        let telemetry = year_of_hours();
+        let model = OutageModel::ReliabilityIndices { saifi: 0.0, saidi_minutes: 60.0 };
+        let result = run_backup_study(&telemetry, &battery, initial_state, &model, 1.0, 4.0, 4, 1);
+        assert!(matches!(result, Err(OutageScenarioError::InvalidRate(_))));
+
+        let model = OutageModel::ReliabilityIndices { saifi: 1.0, saidi_minutes: 60.0 };
+        let result = run_backup_study(&telemetry, &battery, initial_state, &model, 1.0, 4.0, 0, 1);
+        assert!(matches!(result, Err(OutageScenarioError::NoDraws)));
+    }
+}
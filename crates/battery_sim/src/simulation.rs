@@ -1,11 +1,269 @@
-use crate::battery::{BatteryState, Battery, BatteryError};
-use crate::types::{TelemetryPoint};
+use std::borrow::Borrow;
+use crate::battery::{BatteryState, Battery, BatteryError, BatteryModel, BatteryStateError};
+use crate::checkpoint::Checkpoint;
+use crate::events::{EventDetector, StepObserver};
+use crate::outage::island_step;
+use crate::types::{Energy, Power, TelemetryPoint};
 
 
 #[derive(Debug, thiserror::Error)]
 pub enum SimulationError {
     #[error("Simulating load following failed on step {1}.")]
-    ErrorSimulatingLoadFollowing(#[source] BatteryError, usize)
+    ErrorSimulatingLoadFollowing(#[source] BatteryError, usize),
+    #[error("Energy balance violated by {0} on step {1}.")]
+    EnergyImbalance(Energy, usize),
+    #[error("Energy balance tolerance {0} kWh must be finite and not negative.")]
+    InvalidTolerance(f64),
+}
+
+/// The outcome of one simulated step: the telemetry it consumed and the battery state it produced.
+///
+/// The telemetry point is held as it was streamed in, so simulating over borrowed
+/// points, such as `&[TelemetryPoint]`, copies none of them.
+#[derive(Debug, Clone)]
+pub struct StepResult<P = TelemetryPoint> {
+    step: usize,
+    telemetry_point: P,
+    state: BatteryState,
+}
+
+impl<P: Borrow<TelemetryPoint>> StepResult<P> {
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn telemetry_point(&self) -> &TelemetryPoint {
+        self.telemetry_point.borrow()
+    }
+
+    pub fn state(&self) -> BatteryState {
+        self.state
+    }
+
+    /// Power drawn from the grid, negative when exporting. Zero on islanded steps.
+    pub fn grid_power(&self) -> Power {
+        if self.telemetry_point().grid_available() { self.site_balance() } else { Power::zero() }
+    }
+
+    /// Load neither PV nor the battery could serve on an islanded step.
+    pub fn unserved_power(&self) -> Power {
+        if self.telemetry_point().grid_available() { Power::zero() } else { self.site_balance().max(Power::zero()) }
+    }
+
+    /// PV the battery could not take on an islanded step.
+    pub fn curtailed_power(&self) -> Power {
+        if self.telemetry_point().grid_available() { Power::zero() } else { (-self.site_balance()).max(Power::zero()) }
+    }
+
+    // Power the site needs from outside, negative when it has power to spare.
+    fn site_balance(&self) -> Power {
+        self.state.power() - self.telemetry_point().excess_pv()
+    }
+}
+
+/// Lazily simulates load following, one telemetry point at a time.
+///
+/// Only the current battery state is held, so arbitrarily long telemetry streams
+/// can be simulated in constant memory. The iterator stops after the first error.
+///
+/// Steps without the grid are islanded as in `simulate_with_outages` with the whole
+/// load critical: nothing is imported or exported, and the step's result reports the
+/// load that went unserved and the PV that was curtailed instead.
+pub struct LoadFollowingSimulation<I, M = Battery, O = ()> {
+    telemetry_points: I,
+    battery: M,
+    state: BatteryState,
+    step: usize,
+    elapsed_hours: f64,
+    failed: bool,
+    balance_tolerance: Option<Energy>,
+    observer: O,
+    detector: Option<EventDetector>,
+}
+
+impl<I, M> LoadFollowingSimulation<I, M>
+where
+    I: Iterator,
+    I::Item: Borrow<TelemetryPoint>,
+    M: BatteryModel,
+{
+    pub fn new<T>(telemetry_points: T, battery: M, initial_state: BatteryState) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        LoadFollowingSimulation {
+            telemetry_points: telemetry_points.into_iter(),
+            battery,
+            state: initial_state,
+            step: 0,
+            elapsed_hours: 0.0,
+            failed: false,
+            balance_tolerance: None,
+            observer: (),
+            detector: None,
+        }
+    }
+
+    /// Reports every successful step, with the battery events it caused, to `observer`.
+    /// Events are detected from the current state onwards, so a resumed run does not
+    /// see a direction switch across idle steps from before its checkpoint.
+    pub fn with_observer<O: StepObserver>(self, observer: O) -> LoadFollowingSimulation<I, M, O> {
+        LoadFollowingSimulation {
+            telemetry_points: self.telemetry_points,
+            battery: self.battery,
+            state: self.state,
+            step: self.step,
+            elapsed_hours: self.elapsed_hours,
+            failed: self.failed,
+            balance_tolerance: self.balance_tolerance,
+            observer,
+            detector: Some(EventDetector::new(&self.state)),
+        }
+    }
+}
+
+impl<I, M, O> LoadFollowingSimulation<I, M, O>
+where
+    I: Iterator,
+    I::Item: Borrow<TelemetryPoint>,
+    M: BatteryModel,
+    O: StepObserver,
+{
+    /// Checks every step that the energy through the battery terminals, less losses,
+    /// matches the change in state of charge to within `tolerance`.
+    pub fn with_energy_balance_check(mut self, tolerance: Energy) -> Result<Self, SimulationError> {
+        let tolerance_kwh = tolerance.as_kwh();
+        if !(tolerance_kwh.is_finite() && tolerance_kwh >= 0.0) {
+            return Err(SimulationError::InvalidTolerance(tolerance_kwh));
+        }
+        self.balance_tolerance = Some(tolerance);
+        Ok(self)
+    }
+
+    /// The battery state after the most recently simulated step.
+    pub fn state(&self) -> BatteryState {
+        self.state
+    }
+
+    /// Simulated time so far, in hours.
+    pub fn elapsed_hours(&self) -> f64 {
+        self.elapsed_hours
+    }
+}
+
+impl<I> LoadFollowingSimulation<I>
+where
+    I: Iterator,
+    I::Item: Borrow<TelemetryPoint>,
+{
+    /// Carries on a simulation saved with `checkpoint`, over the telemetry that follows it.
+    /// Step numbers continue from where the checkpointed run stopped.
+    pub fn resume<T>(checkpoint: Checkpoint, telemetry_points: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        LoadFollowingSimulation {
+            telemetry_points: telemetry_points.into_iter(),
+            battery: checkpoint.battery().clone(),
+            state: checkpoint.state(),
+            step: checkpoint.step(),
+            elapsed_hours: checkpoint.elapsed_hours(),
+            failed: false,
+            balance_tolerance: checkpoint.balance_tolerance(),
+            observer: (),
+            detector: None,
+        }
+    }
+}
+
+impl<I, O> LoadFollowingSimulation<I, Battery, O> {
+    /// Snapshot of the simulation after the most recently simulated step.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint::new(
+            self.battery.clone(), self.state, self.step, self.elapsed_hours, self.balance_tolerance,
+        )
+    }
+}
+
+impl<I, M: BatteryModel, O: StepObserver> LoadFollowingSimulation<I, M, O> {
+    fn check_energy_balance(
+        &self,
+        state: &BatteryState,
+        telemetry_point: &TelemetryPoint,
+        step: usize,
+    ) -> Result<(), SimulationError> {
+        let Some(tolerance) = self.balance_tolerance else {
+            return Ok(());
+        };
+        let imbalance = self.battery.energy_imbalance(&self.state, state, telemetry_point.duration());
+        if imbalance.as_kwh().abs() > tolerance.as_kwh() {
+            return Err(SimulationError::EnergyImbalance(imbalance, step));
+        }
+        Ok(())
+    }
+
+    // The state the battery reaches by following the excess PV of `telemetry_point`,
+    // or by islanding the site if the grid is not available.
+    fn follow(&self, telemetry_point: &TelemetryPoint) -> Result<BatteryState, BatteryError> {
+        if !telemetry_point.grid_available() {
+            return island_step(
+                &self.battery, &self.state, telemetry_point.solar_power(), telemetry_point.load_power(),
+                1.0, telemetry_point.duration(),
+            ).map(|(state, ..)| state);
+        }
+        self.battery.step(&self.state, telemetry_point.excess_pv(), telemetry_point.duration())
+    }
+
+    // Reports a step that reached `result` from the current state to the observer.
+    fn notify<P: Borrow<TelemetryPoint>>(&mut self, result: &StepResult<P>) {
+        if let Some(detector) = &mut self.detector {
+            let point = result.telemetry_point();
+            let events = detector.detect(&self.battery, &self.state, point.excess_pv(), &result.state);
+            self.observer.on_step(result, &events);
+        }
+    }
+
+    // Moves the simulation on past `telemetry_point`, ending the step in `state`.
+    fn advance(&mut self, state: BatteryState, telemetry_point: &TelemetryPoint) {
+        self.state = state;
+        self.step += 1;
+        self.elapsed_hours += telemetry_point.duration().as_hour();
+    }
+}
+
+impl<I, M, O> Iterator for LoadFollowingSimulation<I, M, O>
+where
+    I: Iterator,
+    I::Item: Borrow<TelemetryPoint>,
+    M: BatteryModel,
+    O: StepObserver,
+{
+    type Item = Result<StepResult<I::Item>, SimulationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let telemetry_point = self.telemetry_points.next()?;
+        let point = telemetry_point.borrow();
+        let step = self.step;
+
+        let result = self.follow(point)
+            .map_err(|e| SimulationError::ErrorSimulatingLoadFollowing(e, step))
+            .and_then(|state| self.check_energy_balance(&state, point, step).map(|_| state));
+        match result {
+            Ok(state) => {
+                let result = StepResult { step, telemetry_point, state };
+                self.notify(&result);
+                self.advance(state, result.telemetry_point());
+                Some(Ok(result))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
 }
 
 pub fn simulate_load_following(
@@ -13,20 +271,166 @@ pub fn simulate_load_following(
     battery: Battery,
     initial_state: BatteryState,
 ) -> Result<Vec<BatteryState>, SimulationError> {
+    let len = telemetry_points.len();
+    collect_states(LoadFollowingSimulation::new(telemetry_points, battery, initial_state), initial_state, len)
+}
 
-    let states: Vec<BatteryState> = telemetry_points.iter().enumerate().try_fold(
-        vec![initial_state],
-        |mut states, (i, point)| {
-            let new_state = battery.load_follow_step(&states[i], point)
-                .map_err(|e| SimulationError::ErrorSimulatingLoadFollowing(e, i))?;
-            states.push(new_state);
-            Ok(states)
-        }
-    )?;
+/// As `simulate_load_following`, failing with `SimulationError::EnergyImbalance` on
+/// the first step whose energy flows, losses and state of charge change do not
+/// reconcile to within `tolerance`.
+pub fn simulate_load_following_verified(
+    telemetry_points: Vec<TelemetryPoint>,
+    battery: Battery,
+    initial_state: BatteryState,
+    tolerance: Energy,
+) -> Result<Vec<BatteryState>, SimulationError> {
+    let len = telemetry_points.len();
+    let simulation = LoadFollowingSimulation::new(telemetry_points, battery, initial_state)
+        .with_energy_balance_check(tolerance)?;
+    collect_states(simulation, initial_state, len)
+}
+
+fn collect_states<I: Iterator<Item = TelemetryPoint>>(
+    simulation: LoadFollowingSimulation<I>,
+    initial_state: BatteryState,
+    len: usize,
+) -> Result<Vec<BatteryState>, SimulationError> {
+    let mut states: Vec<BatteryState> = Vec::with_capacity(len + 1);
+    states.push(initial_state);
+    for result in simulation {
+        states.push(result?.state());
+    }
 
     Ok(states)
 }
 
+/// What a tolerant simulation does with a step that fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Record no state for the step and carry the previous state on to the next step.
+    Skip,
+    /// Record the previous state of charge at zero power for the step.
+    HoldState,
+}
+
+/// A step that failed in a tolerant simulation.
+#[derive(Debug)]
+pub struct StepDiagnostic {
+    step: usize,
+    telemetry_point: TelemetryPoint,
+    error: BatteryError,
+    hold_error: Option<BatteryStateError>,
+}
+
+impl StepDiagnostic {
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn telemetry_point(&self) -> &TelemetryPoint {
+        &self.telemetry_point
+    }
+
+    pub fn error(&self) -> &BatteryError {
+        &self.error
+    }
+
+    /// Why the model also rejected holding the previous state of charge at zero power,
+    /// if it did. The previous state was then carried forward unchanged.
+    pub fn hold_error(&self) -> Option<&BatteryStateError> {
+        self.hold_error.as_ref()
+    }
+}
+
+/// The states of a tolerant simulation, alongside the steps that failed.
+#[derive(Debug)]
+pub struct TolerantSimulationResult {
+    states: Vec<Option<BatteryState>>,
+    diagnostics: Vec<StepDiagnostic>,
+}
+
+impl TolerantSimulationResult {
+    /// The initial state followed by one entry per step, so entry `i + 1` belongs to
+    /// telemetry step `i`. Steps skipped under `FailurePolicy::Skip` are `None`.
+    pub fn states(&self) -> &[Option<BatteryState>] {
+        &self.states
+    }
+
+    pub fn diagnostics(&self) -> &[StepDiagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn into_parts(self) -> (Vec<Option<BatteryState>>, Vec<StepDiagnostic>) {
+        (self.states, self.diagnostics)
+    }
+}
+
+/// Simulates load following through to the end of the telemetry, dealing with failing
+/// steps according to `policy` instead of stopping.
+///
+/// Under `FailurePolicy::HoldState` a model that rejects the previous state of charge
+/// at zero power carries the previous state forward unchanged, and the step's
+/// diagnostic records why.
+pub fn simulate_load_following_tolerant<M, T>(
+    telemetry_points: T,
+    model: &M,
+    initial_state: BatteryState,
+    policy: FailurePolicy,
+) -> TolerantSimulationResult
+where
+    M: BatteryModel,
+    T: IntoIterator<Item = TelemetryPoint>,
+{
+    LoadFollowingSimulation::new(telemetry_points, model, initial_state).run_tolerant(policy)
+}
+
+impl<I, M, O> LoadFollowingSimulation<I, M, O>
+where
+    I: Iterator,
+    I::Item: Borrow<TelemetryPoint>,
+    M: BatteryModel,
+    O: StepObserver,
+{
+    /// Runs the rest of the telemetry as `simulate_load_following_tolerant` does. Only
+    /// steps that succeed are reported to the observer.
+    pub fn run_tolerant(mut self, policy: FailurePolicy) -> TolerantSimulationResult {
+        let mut states = vec![Some(self.state)];
+        let mut diagnostics = Vec::new();
+        while let Some(telemetry_point) = self.telemetry_points.next() {
+            let step = self.step;
+            match self.follow(telemetry_point.borrow()) {
+                Ok(state) => {
+                    let result = StepResult { step, telemetry_point, state };
+                    self.notify(&result);
+                    self.advance(state, result.telemetry_point());
+                    states.push(Some(state));
+                }
+                Err(error) => {
+                    let previous = self.state();
+                    let (next, hold_error) = match policy {
+                        FailurePolicy::HoldState => {
+                            let (held, hold_error) = match self.battery.init_state(previous.state_of_charge(), Power::zero()) {
+                                Ok(held) => (held, None),
+                                Err(hold_error) => (previous, Some(hold_error)),
+                            };
+                            states.push(Some(held));
+                            (held, hold_error)
+                        }
+                        FailurePolicy::Skip => {
+                            states.push(None);
+                            (previous, None)
+                        }
+                    };
+                    let telemetry_point = telemetry_point.borrow().clone();
+                    self.advance(next, &telemetry_point);
+                    diagnostics.push(StepDiagnostic { step, telemetry_point, error, hold_error });
+                }
+            }
+        }
+        TolerantSimulationResult { states, diagnostics }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,4 +500,275 @@ mod tests {
         let expected = 56.3 - (7.0 / 0.9);
         assert_abs_diff_eq!(states[3].state_of_charge().as_kwh(), expected, epsilon = EPSILON);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_load_following_simulation_streams_step_results() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(50.0), Power::zero())
+            .expect("valid state");
+
+        let telemetry = (0..3).map(|_| TelemetryPoint::new(hour!(1.0), kw!(10.0), kw!(3.0)));
+        let results: Vec<StepResult> = LoadFollowingSimulation::new(telemetry, battery, initial_state)
+            .collect::<Result<_, _>>()
+            .expect("simulation should succeed");
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2].step(), 2);
+        assert_abs_diff_eq!(results[0].state().state_of_charge_kwh(), 56.3, epsilon = EPSILON);
+        assert_abs_diff_eq!(results[2].state().state_of_charge_kwh(), 50.0 + 3.0 * 6.3, epsilon = EPSILON);
+        // All excess PV goes into the battery, so nothing flows to or from the grid.
+        assert_abs_diff_eq!(results[0].grid_power().as_kw(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_load_following_simulation_borrows_telemetry() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(50.0), Power::zero())
+            .expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), kw!(10.0), kw!(3.0)); 2];
+
+        let results: Vec<StepResult<&TelemetryPoint>> = LoadFollowingSimulation::new(&telemetry, battery, initial_state)
+            .collect::<Result<_, _>>()
+            .expect("simulation should succeed");
+
+        assert!(std::ptr::eq(results[1].telemetry_point(), &telemetry[1]));
+        assert_abs_diff_eq!(results[1].state().state_of_charge_kwh(), 50.0 + 2.0 * 6.3, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_load_following_simulation_matches_vec_simulation() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(95.0), Power::zero())
+            .expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(60.0), kw!(3.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(80.0)),
+            TelemetryPoint::new(hour!(0.5), kw!(2.0), kw!(9.0)),
+        ];
+
+        let states = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+        let mut simulation = LoadFollowingSimulation::new(telemetry, battery, initial_state);
+        for (result, state) in simulation.by_ref().zip(&states[1..]) {
+            let result = result.expect("step should succeed");
+            assert_abs_diff_eq!(result.state().state_of_charge_kwh(), state.state_of_charge_kwh(), epsilon = EPSILON);
+            assert_abs_diff_eq!(result.state().power_kw(), state.power_kw(), epsilon = EPSILON);
+        }
+        assert!(simulation.next().is_none());
+        assert_abs_diff_eq!(simulation.state().state_of_charge_kwh(), states[3].state_of_charge_kwh(), epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_verified_simulation_matches_unverified() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(95.0), Power::zero())
+            .expect("valid state");
+        // Charges into the capacity limit, then discharges down to empty.
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(60.0), kw!(3.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(80.0)),
+            TelemetryPoint::new(hour!(2.0), kw!(0.0), kw!(80.0)),
+            TelemetryPoint::new(hour!(0.5), kw!(2.0), kw!(9.0)),
+        ];
+
+        let states = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+        let verified = simulate_load_following_verified(telemetry, battery, initial_state, kwh!(1e-9))
+            .expect("energy should balance");
+
+        assert_eq!(states.len(), verified.len());
+        assert_abs_diff_eq!(verified[4].state_of_charge_kwh(), states[4].state_of_charge_kwh(), epsilon = EPSILON);
+        assert_abs_diff_eq!(verified[4].state_of_charge_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    // Loses a fixed amount of charge every step, on top of the battery's own losses.
+    struct Leaking(Battery, Energy);
+
+    impl BatteryModel for Leaking {
+        fn capacity(&self) -> Energy {
+            self.0.capacity()
+        }
+
+        fn max_power(&self) -> Power {
+            self.0.max_power()
+        }
+
+        fn init_state(&self, state_of_charge: Energy, power: Power) -> Result<BatteryState, BatteryStateError> {
+            self.0.init_state(state_of_charge, power)
+        }
+
+        fn step(&self, battery_state: &BatteryState, power: Power, duration: Duration) -> Result<BatteryState, BatteryError> {
+            let state = self.0.step(battery_state, power, duration)?;
+            BatteryState::try_new(state.state_of_charge() - self.1, state.power()).map_err(BatteryError::ErrorCharging)
+        }
+
+        fn energy_imbalance(&self, previous: &BatteryState, next: &BatteryState, duration: Duration) -> Energy {
+            self.0.energy_imbalance(previous, next, duration)
+        }
+    }
+
+    #[test]
+    fn test_energy_balance_check_reports_step_and_imbalance() {
+        let model = Leaking(test_battery(), kwh!(0.5));
+        let initial_state = model.init_state(kwh!(50.0), Power::zero())
+            .expect("valid state");
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(1.0), kw!(10.0), kw!(3.0)),
+            TelemetryPoint::new(hour!(1.0), kw!(10.0), kw!(3.0)),
+        ];
+        let mut simulation = LoadFollowingSimulation::new(telemetry, model, initial_state)
+            .with_energy_balance_check(kwh!(0.1))
+            .expect("valid tolerance");
+
+        match simulation.next() {
+            Some(Err(SimulationError::EnergyImbalance(imbalance, step))) => {
+                assert_eq!(step, 0);
+                assert_abs_diff_eq!(imbalance.as_kwh(), 0.5, epsilon = EPSILON);
+            }
+            other => panic!("expected an energy imbalance, got {:?}", other),
+        }
+        assert!(simulation.next().is_none());
+    }
+
+    #[test]
+    fn test_energy_balance_check_rejects_invalid_tolerance() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(50.0), Power::zero())
+            .expect("valid state");
+
+        assert!(matches!(
+            LoadFollowingSimulation::new(Vec::<TelemetryPoint>::new(), battery.clone(), initial_state)
+                .with_energy_balance_check(kwh!(-1.0)),
+            Err(SimulationError::InvalidTolerance(_))
+        ));
+        assert!(matches!(
+            simulate_load_following_verified(vec![], battery, initial_state, kwh!(-1.0)),
+            Err(SimulationError::InvalidTolerance(_))
+        ));
+    }
+
+    // Fails every charging step, standing in for a model that rejects some field data.
+    struct FailsWhenCharging(Battery);
+
+    impl BatteryModel for FailsWhenCharging {
+        fn capacity(&self) -> Energy {
+            self.0.capacity()
+        }
+
+        fn max_power(&self) -> Power {
+            self.0.max_power()
+        }
+
+        fn init_state(&self, state_of_charge: Energy, power: Power) -> Result<BatteryState, BatteryStateError> {
+            self.0.init_state(state_of_charge, power)
+        }
+
+        fn step(&self, battery_state: &BatteryState, power: Power, duration: Duration) -> Result<BatteryState, BatteryError> {
+            if power > Power::zero() {
+                return Err(BatteryError::ErrorCharging(BatteryStateError::PowerGreaterThanMax));
+            }
+            self.0.step(battery_state, power, duration)
+        }
+    }
+
+    fn mixed_telemetry() -> Vec<TelemetryPoint> {
+        vec![
+            TelemetryPoint::new(hour!(1.0), kw!(2.0), kw!(9.0)),   // -7 kW discharge
+            TelemetryPoint::new(hour!(1.0), kw!(10.0), kw!(3.0)),  // +7 kW charge, fails
+            TelemetryPoint::new(hour!(1.0), kw!(2.0), kw!(9.0)),   // -7 kW discharge
+        ]
+    }
+
+    #[test]
+    fn test_tolerant_simulation_holds_state_on_failure() {
+        let model = FailsWhenCharging(test_battery());
+        let initial_state = model.init_state(kwh!(50.0), kw!(1.0)).expect("valid state");
+
+        let result = simulate_load_following_tolerant(mixed_telemetry(), &model, initial_state, FailurePolicy::HoldState);
+
+        let states: Vec<BatteryState> = result.states().iter().map(|state| state.expect("held steps have a state")).collect();
+        assert_eq!(states.len(), 4);
+        let after_first = 50.0 - 7.0 / 0.9;
+        assert_abs_diff_eq!(states[2].state_of_charge_kwh(), after_first, epsilon = EPSILON);
+        assert_abs_diff_eq!(states[2].power_kw(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(states[3].state_of_charge_kwh(), after_first - 7.0 / 0.9, epsilon = EPSILON);
+
+        let diagnostics = result.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].step(), 1);
+        assert_abs_diff_eq!(diagnostics[0].telemetry_point().excess_pv().as_kw(), 7.0, epsilon = EPSILON);
+        assert!(matches!(diagnostics[0].error(), BatteryError::ErrorCharging(_)));
+        assert!(diagnostics[0].hold_error().is_none());
+    }
+
+    // Fails charging steps and rejects every state it is asked to build.
+    struct RejectsStates(Battery);
+
+    impl BatteryModel for RejectsStates {
+        fn capacity(&self) -> Energy {
+            self.0.capacity()
+        }
+
+        fn max_power(&self) -> Power {
+            self.0.max_power()
+        }
+
+        fn init_state(&self, _: Energy, _: Power) -> Result<BatteryState, BatteryStateError> {
+            Err(BatteryStateError::PowerGreaterThanMax)
+        }
+
+        fn step(&self, battery_state: &BatteryState, power: Power, duration: Duration) -> Result<BatteryState, BatteryError> {
+            FailsWhenCharging(self.0.clone()).step(battery_state, power, duration)
+        }
+    }
+
+    #[test]
+    fn test_tolerant_simulation_carries_state_forward_when_hold_is_rejected() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(50.0), Power::zero()).expect("valid state");
+        let model = RejectsStates(battery);
+
+        let result = simulate_load_following_tolerant(mixed_telemetry(), &model, initial_state, FailurePolicy::HoldState);
+
+        let states: Vec<BatteryState> = result.states().iter().map(|state| state.expect("held steps have a state")).collect();
+        assert_eq!(states.len(), 4);
+        assert_abs_diff_eq!(states[2].state_of_charge_kwh(), states[1].state_of_charge_kwh(), epsilon = EPSILON);
+        assert_abs_diff_eq!(states[2].power_kw(), states[1].power_kw(), epsilon = EPSILON);
+        assert_eq!(result.diagnostics().len(), 1);
+        assert!(matches!(result.diagnostics()[0].hold_error(), Some(BatteryStateError::PowerGreaterThanMax)));
+    }
+
+    #[test]
+    fn test_tolerant_simulation_skips_failed_steps() {
+        let model = FailsWhenCharging(test_battery());
+        let initial_state = model.init_state(kwh!(50.0), Power::zero()).expect("valid state");
+
+        let (states, diagnostics) = simulate_load_following_tolerant(
+            mixed_telemetry(), &model, initial_state, FailurePolicy::Skip,
+        ).into_parts();
+
+        assert_eq!(states.len(), 4);
+        assert!(states[2].is_none());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].step(), 1);
+        let last = states[3].expect("the last step succeeds");
+        assert_abs_diff_eq!(last.state_of_charge_kwh(), 50.0 - 14.0 / 0.9, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_tolerant_simulation_matches_strict_without_failures() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(95.0), Power::zero()).expect("valid state");
+        let telemetry = mixed_telemetry();
+
+        let strict = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+        let tolerant = simulate_load_following_tolerant(telemetry, &battery, initial_state, FailurePolicy::Skip);
+
+        assert!(tolerant.diagnostics().is_empty());
+        for (strict, tolerant) in strict.iter().zip(tolerant.states()) {
+            let tolerant = tolerant.expect("no step was skipped");
+            assert_abs_diff_eq!(strict.state_of_charge_kwh(), tolerant.state_of_charge_kwh(), epsilon = EPSILON);
+        }
+    }
+}
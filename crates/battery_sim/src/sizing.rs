@@ -0,0 +1,392 @@
+use crate::battery::{Battery, BatteryError};
+use crate::sweep::{run_scenarios, ScenarioKpis, SweepError};
+use crate::types::{Efficiency, Energy, Power, TelemetryPoint};
+
+const HOURS_PER_YEAR: f64 = 8760.0;
+
+/// A flat import/export tariff, per kWh.
+#[derive(Debug, Clone, Copy)]
+pub struct FlatTariff {
+    import_price: f64,
+    export_price: f64,
+}
+
+impl FlatTariff {
+    pub fn new(import_price: f64, export_price: f64) -> Self {
+        FlatTariff { import_price, export_price }
+    }
+
+    pub fn bill(&self, grid_import: Energy, grid_export: Energy) -> f64 {
+        self.import_price * grid_import.as_kwh() - self.export_price * grid_export.as_kwh()
+    }
+}
+
+/// Installed cost of a battery built to an arbitrary size.
+#[derive(Debug, Clone, Copy)]
+pub struct CostModel {
+    fixed: f64,
+    per_kwh: f64,
+    per_kw: f64,
+}
+
+impl CostModel {
+    pub fn new(fixed: f64, per_kwh: f64, per_kw: f64) -> Self {
+        CostModel { fixed, per_kwh, per_kw }
+    }
+
+    pub fn cost(&self, capacity: Energy, max_power: Power) -> f64 {
+        self.fixed + self.per_kwh * capacity.as_kwh() + self.per_kw * max_power.as_kw()
+    }
+}
+
+/// A purchasable battery product.
+#[derive(Debug, Clone)]
+pub struct Product {
+    name: String,
+    battery: Battery,
+    price: f64,
+}
+
+impl Product {
+    pub fn new(name: impl Into<String>, battery: Battery, price: f64) -> Self {
+        Product { name: name.into(), battery, price }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The set of battery sizes to consider.
+#[derive(Debug, Clone)]
+pub enum SizingSearch {
+    /// Every combination of capacity and power, priced with a cost model.
+    Grid {
+        capacities: Vec<Energy>,
+        max_powers: Vec<Power>,
+        round_trip_efficiency: Efficiency,
+        cost_model: CostModel,
+    },
+    /// Only the products in a catalogue.
+    Catalogue(Vec<Product>),
+}
+
+impl SizingSearch {
+    // (name, battery, capital cost) for every candidate.
+    fn candidates(&self) -> Result<Vec<(Option<String>, Battery, f64)>, BatteryError> {
+        match self {
+            SizingSearch::Grid { capacities, max_powers, round_trip_efficiency, cost_model } => {
+                let mut candidates = Vec::with_capacity(capacities.len() * max_powers.len());
+                for &capacity in capacities {
+                    for &max_power in max_powers {
+                        let battery = Battery::new(capacity, max_power, *round_trip_efficiency)?;
+                        candidates.push((None, battery, cost_model.cost(capacity, max_power)));
+                    }
+                }
+                Ok(candidates)
+            }
+            SizingSearch::Catalogue(products) => Ok(products.iter()
+                .map(|p| (Some(p.name.clone()), p.battery.clone(), p.price))
+                .collect()),
+        }
+    }
+}
+
+/// What makes one battery size better than another.
+#[derive(Debug, Clone, Copy)]
+pub enum SizingObjective {
+    /// Net present value of the bill savings less the capital cost.
+    NetPresentValue { discount_rate: f64, lifetime_years: u32 },
+    /// Fraction of the load met without importing from the grid.
+    SelfSufficiency,
+    /// Yearly reduction in the electricity bill.
+    BillSavings,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SizingError {
+    #[error("Cannot size a battery against empty telemetry.")]
+    EmptyTelemetry,
+    #[error("Invalid battery in the sizing search.")]
+    InvalidBattery(#[source] BatteryError),
+    #[error("No candidate fits within the budget of {0}.")]
+    NothingWithinBudget(f64),
+    #[error("Simulating the candidates failed.")]
+    ErrorSimulating(#[source] SweepError),
+}
+
+/// A simulated candidate and the figures it was judged on.
+#[derive(Debug, Clone)]
+pub struct SizingCandidate {
+    name: Option<String>,
+    battery: Battery,
+    capital_cost: f64,
+    kpis: ScenarioKpis,
+    self_sufficiency: f64,
+    annual_bill_savings: f64,
+    net_present_value: Option<f64>,
+    objective_value: f64,
+}
+
+impl SizingCandidate {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn battery(&self) -> &Battery {
+        &self.battery
+    }
+
+    pub fn capital_cost(&self) -> f64 {
+        self.capital_cost
+    }
+
+    pub fn kpis(&self) -> &ScenarioKpis {
+        &self.kpis
+    }
+
+    pub fn self_sufficiency(&self) -> f64 {
+        self.self_sufficiency
+    }
+
+    pub fn annual_bill_savings(&self) -> f64 {
+        self.annual_bill_savings
+    }
+
+    /// Only available when sizing for net present value.
+    pub fn net_present_value(&self) -> Option<f64> {
+        self.net_present_value
+    }
+
+    pub fn objective_value(&self) -> f64 {
+        self.objective_value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SizingResult {
+    candidates: Vec<SizingCandidate>,
+    best: usize,
+    pareto: Vec<usize>,
+}
+
+impl SizingResult {
+    /// Every candidate within budget, in search order.
+    pub fn candidates(&self) -> &[SizingCandidate] {
+        &self.candidates
+    }
+
+    /// The candidate with the highest objective value, cheapest first on ties.
+    pub fn best(&self) -> &SizingCandidate {
+        &self.candidates[self.best]
+    }
+
+    /// Candidates that no other candidate beats on both capital cost and objective,
+    /// ordered by capital cost.
+    pub fn pareto_front(&self) -> Vec<&SizingCandidate> {
+        self.pareto.iter().map(|&i| &self.candidates[i]).collect()
+    }
+}
+
+/// Simulates load following for every candidate size and ranks them by `objective`.
+///
+/// Savings are measured against the same site with no battery and scaled from the
+/// telemetry period to a full year. Candidates costing more than `budget` are skipped.
+pub fn optimise_size(
+    telemetry_points: &[TelemetryPoint],
+    search: &SizingSearch,
+    tariff: &FlatTariff,
+    objective: SizingObjective,
+    budget: Option<f64>,
+) -> Result<SizingResult, SizingError> {
+    if telemetry_points.is_empty() {
+        return Err(SizingError::EmptyTelemetry);
+    }
+
+    let (candidates, capital_costs): (Vec<_>, Vec<_>) = search.candidates()
+        .map_err(SizingError::InvalidBattery)?
+        .into_iter()
+        .filter(|(_, _, cost)| budget.is_none_or(|budget| *cost <= budget))
+        .map(|(name, battery, cost)| ((name, battery), cost))
+        .unzip();
+    if candidates.is_empty() {
+        return Err(SizingError::NothingWithinBudget(budget.unwrap_or(f64::INFINITY)));
+    }
+
+    let (names, batteries): (Vec<_>, Vec<_>) = candidates.into_iter().unzip();
+    let scenarios = run_scenarios(telemetry_points, batteries, 0.0)
+        .map_err(SizingError::ErrorSimulating)?;
+
+    let hours: f64 = telemetry_points.iter().map(|p| p.duration().as_hour()).sum();
+    let years_per_period = hours / HOURS_PER_YEAR;
+    let (load, baseline_import, baseline_export) = baseline_energy(telemetry_points);
+    let baseline_bill = tariff.bill(baseline_import, baseline_export);
+
+    let candidates: Vec<SizingCandidate> = scenarios.into_iter()
+        .zip(names)
+        .zip(capital_costs)
+        .map(|((scenario, name), capital_cost)| {
+            let kpis = *scenario.kpis();
+            let self_sufficiency = if load > Energy::zero() {
+                1.0 - kpis.grid_import().as_kwh() / load.as_kwh()
+            } else {
+                1.0
+            };
+            let bill = tariff.bill(kpis.grid_import(), kpis.grid_export());
+            let annual_bill_savings = (baseline_bill - bill) / years_per_period;
+            let net_present_value = match objective {
+                SizingObjective::NetPresentValue { discount_rate, lifetime_years } => Some(
+                    net_present_value(annual_bill_savings, capital_cost, discount_rate, lifetime_years)
+                ),
+                _ => None,
+            };
+            let objective_value = match objective {
+                SizingObjective::NetPresentValue { .. } => net_present_value.unwrap_or_default(),
+                SizingObjective::SelfSufficiency => self_sufficiency,
+                SizingObjective::BillSavings => annual_bill_savings,
+            };
+            SizingCandidate {
+                name,
+                battery: scenario.battery().clone(),
+                capital_cost,
+                kpis,
+                self_sufficiency,
+                annual_bill_savings,
+                net_present_value,
+                objective_value,
+            }
+        })
+        .collect();
+
+    let best = (0..candidates.len())
+        .max_by(|&a, &b| {
+            candidates[a].objective_value.total_cmp(&candidates[b].objective_value)
+                .then(candidates[b].capital_cost.total_cmp(&candidates[a].capital_cost))
+        })
+        .expect("at least one candidate");
+    let pareto = pareto_front(&candidates);
+
+    Ok(SizingResult { candidates, best, pareto })
+}
+
+// (load, grid import, grid export) of the site with no battery.
+fn baseline_energy(telemetry_points: &[TelemetryPoint]) -> (Energy, Energy, Energy) {
+    telemetry_points.iter().fold(
+        (Energy::zero(), Energy::zero(), Energy::zero()),
+        |(load, import, export), point| {
+            let excess = point.excess_pv() * point.duration();
+            let load = load + point.load_power() * point.duration();
+            if excess > Energy::zero() {
+                (load, import, export + excess)
+            } else {
+                (load, import - excess, export)
+            }
+        }
+    )
+}
+
+fn net_present_value(annual_savings: f64, capital_cost: f64, discount_rate: f64, lifetime_years: u32) -> f64 {
+    let discounted: f64 = (1..=lifetime_years)
+        .map(|year| annual_savings / (1.0 + discount_rate).powi(year as i32))
+        .sum();
+    discounted - capital_cost
+}
+
+fn pareto_front(candidates: &[SizingCandidate]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| {
+        candidates[a].capital_cost.total_cmp(&candidates[b].capital_cost)
+            .then(candidates[b].objective_value.total_cmp(&candidates[a].objective_value))
+    });
+
+    // Walking up in cost, keep only candidates that improve on everything cheaper.
+    let mut front = Vec::new();
+    let mut best_so_far = f64::NEG_INFINITY;
+    for i in order {
+        if candidates[i].objective_value > best_so_far {
+            best_so_far = candidates[i].objective_value;
+            front.push(i);
+        }
+    }
+    front
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    // One day of 12 hour steps: 8 kWh excess in the day, 6 kWh deficit at night.
+    fn test_telemetry() -> Vec<TelemetryPoint> {
+        vec![
+            TelemetryPoint::new(hour!(12.0), kw!(1.0), kw!(1.0 / 3.0)),
+            TelemetryPoint::new(hour!(12.0), kw!(0.0), kw!(0.5)),
+        ]
+    }
+
+    fn test_grid() -> SizingSearch {
+        SizingSearch::Grid {
+            capacities: vec![kwh!(2.0), kwh!(6.0), kwh!(10.0)],
+            max_powers: vec![kw!(5.0)],
+            round_trip_efficiency: 1.0.fraction(),
+            cost_model: CostModel::new(100.0, 50.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_self_sufficiency_prefers_cheapest_full_coverage() {
+        let tariff = FlatTariff::new(0.30, 0.05);
+        let result = optimise_size(&test_telemetry(), &test_grid(), &tariff, SizingObjective::SelfSufficiency, None)
+            .expect("sizing should succeed");
+
+        // 6 and 10 kWh both cover the whole night, so the cheaper 6 kWh wins.
+        assert_eq!(result.best().battery().capacity(), kwh!(6.0));
+        assert_abs_diff_eq!(result.best().self_sufficiency(), 1.0, epsilon = EPSILON);
+        // The 10 kWh unit costs more for no gain, so it is off the Pareto front.
+        let front: Vec<f64> = result.pareto_front().iter().map(|c| c.battery().capacity().as_kwh()).collect();
+        assert_eq!(front, vec![2.0, 6.0]);
+    }
+
+    #[test]
+    fn test_bill_savings_are_annualised() {
+        let tariff = FlatTariff::new(0.30, 0.05);
+        let result = optimise_size(&test_telemetry(), &test_grid(), &tariff, SizingObjective::BillSavings, None)
+            .expect("sizing should succeed");
+
+        // Shifting 6 kWh a day from export to self-use saves 6 * (0.30 - 0.05) a day.
+        assert_abs_diff_eq!(result.best().annual_bill_savings(), 6.0 * 0.25 * 365.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_net_present_value_respects_budget() {
+        let tariff = FlatTariff::new(0.30, 0.05);
+        let objective = SizingObjective::NetPresentValue { discount_rate: 0.0, lifetime_years: 10 };
+        let result = optimise_size(&test_telemetry(), &test_grid(), &tariff, objective, Some(250.0))
+            .expect("sizing should succeed");
+
+        // Only the 2 kWh unit (200) is within budget.
+        assert_eq!(result.candidates().len(), 1);
+        let expected = 2.0 * 0.25 * 365.0 * 10.0 - 200.0;
+        assert_abs_diff_eq!(result.best().net_present_value().expect("npv"), expected, epsilon = 1e-6);
+
+        let result = optimise_size(&test_telemetry(), &test_grid(), &tariff, objective, Some(50.0));
+        assert!(matches!(result, Err(SizingError::NothingWithinBudget(_))));
+    }
+
+    #[test]
+    fn test_catalogue_search_reports_product_names() {
+        let tariff = FlatTariff::new(0.30, 0.05);
+        let catalogue = SizingSearch::Catalogue(vec![
+            Product::new("Small", Battery::new(kwh!(3.0), kw!(3.0), 1.0.fraction()).expect("valid"), 300.0),
+            Product::new("Large", Battery::new(kwh!(13.5), kw!(5.0), 1.0.fraction()).expect("valid"), 900.0),
+        ]);
+        let result = optimise_size(&test_telemetry(), &catalogue, &tariff, SizingObjective::SelfSufficiency, None)
+            .expect("sizing should succeed");
+
+        assert_eq!(result.best().name(), Some("Large"));
+        assert_eq!(result.pareto_front().len(), 2);
+    }
+}
@@ -0,0 +1,31 @@
+/// Linearly interpolated percentile of `values`, with `quantile` in [0, 1].
+/// Returns `None` for an empty slice.
+pub(crate) fn percentile(values: &[f64], quantile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    let position = quantile.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let weight = position - lower as f64;
+    Some(sorted[lower] * (1.0 - weight) + sorted[upper] * weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-12;
+
+    #[test]
+    fn test_percentile_interpolates() {
+        let values = [4.0, 1.0, 3.0, 2.0];
+        assert_abs_diff_eq!(percentile(&values, 0.0).expect("non-empty"), 1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(percentile(&values, 0.5).expect("non-empty"), 2.5, epsilon = EPSILON);
+        assert_abs_diff_eq!(percentile(&values, 1.0).expect("non-empty"), 4.0, epsilon = EPSILON);
+        assert!(percentile(&[], 0.5).is_none());
+    }
+}
@@ -0,0 +1,499 @@
+use std::borrow::Borrow;
+use crate::battery::{Battery, BatteryState};
+use crate::simulation::StepResult;
+use crate::stats::percentile;
+use crate::types::{Duration, Energy, Power, TelemetryPoint};
+
+// How close to empty or full the battery must be to count as empty or full.
+const SOC_TOLERANCE_KWH: f64 = 1e-6;
+const DAYS_PER_MONTH: [usize; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SummaryError {
+    #[error("Expected {expected} battery states for the telemetry, got {actual}.")]
+    LengthMismatch { expected: usize, actual: usize },
+    #[error("Percentile {0} must be between 0 and 1.")]
+    InvalidPercentile(f64),
+}
+
+/// Calendar periods for summary breakdowns.
+///
+/// Telemetry is assumed to start at midnight on 1 January of a 365-day year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryPeriod {
+    Day,
+    Month,
+}
+
+impl SummaryPeriod {
+    // Index of the period containing the given time since the start of the telemetry.
+    fn index(&self, elapsed_hours: f64) -> usize {
+        let day = (elapsed_hours / 24.0).floor() as usize;
+        match self {
+            SummaryPeriod::Day => day,
+            SummaryPeriod::Month => {
+                let (year, mut day_of_year) = (day / 365, day % 365);
+                let mut month = 0;
+                while day_of_year >= DAYS_PER_MONTH[month] {
+                    day_of_year -= DAYS_PER_MONTH[month];
+                    month += 1;
+                }
+                year * 12 + month
+            }
+        }
+    }
+}
+
+/// Headline metrics of a simulation run.
+#[derive(Debug, Clone)]
+pub struct SimulationSummary {
+    capacity: Energy,
+    max_power: Power,
+    hours: f64,
+    solar: Energy,
+    load: Energy,
+    grid_import: Energy,
+    grid_export: Energy,
+    unserved: Energy,           // load not served on islanded steps
+    curtailed: Energy,          // PV curtailed on islanded steps
+    battery_charge: Energy,     // energy into the battery terminals
+    battery_discharge: Energy,  // energy out of the battery terminals
+    soc_throughput: Energy,     // sum of absolute changes in stored energy
+    soc_change: Energy,         // final minus initial stored energy
+    soc_hours: f64,             // time integral of the state of charge, in kWh * h
+    soc_samples: Vec<f64>,      // state of charge after every step, in kWh
+    time_at_full: f64,
+    time_at_empty: f64,
+    peak_import: Power,
+    peak_export: Power,
+    period_index: Option<usize>,
+}
+
+impl SimulationSummary {
+    fn empty(battery: &Battery) -> Self {
+        SimulationSummary {
+            capacity: battery.capacity(),
+            max_power: battery.max_power(),
+            hours: 0.0,
+            solar: Energy::zero(),
+            load: Energy::zero(),
+            grid_import: Energy::zero(),
+            grid_export: Energy::zero(),
+            unserved: Energy::zero(),
+            curtailed: Energy::zero(),
+            battery_charge: Energy::zero(),
+            battery_discharge: Energy::zero(),
+            soc_throughput: Energy::zero(),
+            soc_change: Energy::zero(),
+            soc_hours: 0.0,
+            soc_samples: Vec::new(),
+            time_at_full: 0.0,
+            time_at_empty: 0.0,
+            peak_import: Power::zero(),
+            peak_export: Power::zero(),
+            period_index: None,
+        }
+    }
+
+    fn add_step(&mut self, telemetry_point: &TelemetryPoint, previous: &BatteryState, state: &BatteryState) {
+        let duration: Duration = telemetry_point.duration();
+        let hours = duration.as_hour();
+        let site_power = state.power() - telemetry_point.excess_pv();
+        let battery_energy = state.power() * duration;
+        let soc_delta = state.state_of_charge() - previous.state_of_charge();
+        let soc_kwh = state.state_of_charge_kwh();
+
+        self.hours += hours;
+        self.solar = self.solar + telemetry_point.solar_power() * duration;
+        self.load = self.load + telemetry_point.load_power() * duration;
+        if !telemetry_point.grid_available() {
+            // Islanded, as `LoadFollowingSimulation` runs steps without the grid.
+            if site_power > Power::zero() {
+                self.unserved = self.unserved + site_power * duration;
+            } else {
+                self.curtailed = self.curtailed - site_power * duration;
+            }
+        } else if site_power > Power::zero() {
+            self.grid_import = self.grid_import + site_power * duration;
+            self.peak_import = if site_power > self.peak_import { site_power } else { self.peak_import };
+        } else {
+            self.grid_export = self.grid_export - site_power * duration;
+            self.peak_export = if -site_power > self.peak_export { -site_power } else { self.peak_export };
+        }
+        if battery_energy > Energy::zero() {
+            self.battery_charge = self.battery_charge + battery_energy;
+        } else {
+            self.battery_discharge = self.battery_discharge - battery_energy;
+        }
+        self.soc_throughput = self.soc_throughput + soc_delta.max(Energy::zero() - soc_delta);
+        self.soc_change = self.soc_change + soc_delta;
+        self.soc_hours += soc_kwh * hours;
+        self.soc_samples.push(soc_kwh);
+        if soc_kwh >= self.capacity.as_kwh() - SOC_TOLERANCE_KWH {
+            self.time_at_full += hours;
+        }
+        if soc_kwh <= SOC_TOLERANCE_KWH {
+            self.time_at_empty += hours;
+        }
+    }
+
+    /// Summarises streamed step results, as produced by `LoadFollowingSimulation`.
+    pub fn from_steps<I, S, P>(battery: &Battery, initial_state: BatteryState, steps: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Borrow<StepResult<P>>,
+        P: Borrow<TelemetryPoint>,
+    {
+        let mut summary = SimulationSummary::empty(battery);
+        let mut previous = initial_state;
+        for step in steps {
+            let step = step.borrow();
+            summary.add_step(step.telemetry_point(), &previous, &step.state());
+            previous = step.state();
+        }
+        summary
+    }
+
+    /// Summarises the output of `simulate_load_following`: the initial state followed
+    /// by the state after every telemetry point.
+    pub fn from_states(
+        battery: &Battery,
+        telemetry_points: &[TelemetryPoint],
+        states: &[BatteryState],
+    ) -> Result<Self, SummaryError> {
+        check_lengths(telemetry_points, states)?;
+        let mut summary = SimulationSummary::empty(battery);
+        for (point, pair) in telemetry_points.iter().zip(states.windows(2)) {
+            summary.add_step(point, &pair[0], &pair[1]);
+        }
+        Ok(summary)
+    }
+
+    /// One summary per calendar period with telemetry, in time order. Periods a long
+    /// step jumps over have no summary, so each carries its `period_index`.
+    pub fn by_period(
+        battery: &Battery,
+        telemetry_points: &[TelemetryPoint],
+        states: &[BatteryState],
+        period: SummaryPeriod,
+    ) -> Result<Vec<Self>, SummaryError> {
+        check_lengths(telemetry_points, states)?;
+        let mut summaries: Vec<SimulationSummary> = Vec::new();
+        let mut current_period = None;
+        let mut elapsed_hours = 0.0;
+        for (point, pair) in telemetry_points.iter().zip(states.windows(2)) {
+            let index = period.index(elapsed_hours);
+            if current_period != Some(index) {
+                summaries.push(SimulationSummary { period_index: Some(index), ..SimulationSummary::empty(battery) });
+                current_period = Some(index);
+            }
+            summaries.last_mut().expect("pushed above").add_step(point, &pair[0], &pair[1]);
+            elapsed_hours += point.duration().as_hour();
+        }
+        Ok(summaries)
+    }
+
+    /// Which day or month since the start of the telemetry a `by_period` summary
+    /// covers, counting from zero. `None` for a summary of the whole run.
+    pub fn period_index(&self) -> Option<usize> {
+        self.period_index
+    }
+
+    /// Total simulated time, in hours.
+    pub fn hours(&self) -> f64 {
+        self.hours
+    }
+
+    pub fn solar(&self) -> Energy {
+        self.solar
+    }
+
+    pub fn load(&self) -> Energy {
+        self.load
+    }
+
+    pub fn grid_import(&self) -> Energy {
+        self.grid_import
+    }
+
+    pub fn grid_export(&self) -> Energy {
+        self.grid_export
+    }
+
+    /// Load that went unserved on steps without the grid.
+    pub fn unserved_energy(&self) -> Energy {
+        self.unserved
+    }
+
+    /// PV curtailed on steps without the grid.
+    pub fn curtailed_pv(&self) -> Energy {
+        self.curtailed
+    }
+
+    /// Fraction of solar generation used on site rather than exported or curtailed.
+    pub fn self_consumption(&self) -> f64 {
+        if self.solar <= Energy::zero() {
+            return 0.0;
+        }
+        ((self.solar - self.grid_export - self.curtailed).as_kwh() / self.solar.as_kwh()).clamp(0.0, 1.0)
+    }
+
+    /// Fraction of the load met on site, without importing from the grid or going unserved.
+    pub fn self_sufficiency(&self) -> f64 {
+        if self.load <= Energy::zero() {
+            return 1.0;
+        }
+        ((self.load - self.grid_import - self.unserved).as_kwh() / self.load.as_kwh()).clamp(0.0, 1.0)
+    }
+
+    pub fn battery_charge(&self) -> Energy {
+        self.battery_charge
+    }
+
+    pub fn battery_discharge(&self) -> Energy {
+        self.battery_discharge
+    }
+
+    /// Stored energy moved in and out, in multiples of a full charge plus discharge.
+    pub fn equivalent_full_cycles(&self) -> f64 {
+        self.soc_throughput.as_kwh() / (2.0 * self.capacity.as_kwh())
+    }
+
+    /// Conversion losses: energy into the terminals not accounted for by energy out
+    /// of the terminals or a rise in the state of charge.
+    pub fn losses(&self) -> Energy {
+        self.battery_charge - self.battery_discharge - self.soc_change
+    }
+
+    /// Time-weighted average state of charge.
+    pub fn average_soc(&self) -> Energy {
+        let average = if self.hours > 0.0 { self.soc_hours / self.hours } else { 0.0 };
+        Energy::from_kwh(average).expect("average of valid energies should be valid")
+    }
+
+    /// Percentile (in [0, 1]) of the state of charge over all steps.
+    pub fn soc_percentile(&self, quantile: f64) -> Result<Energy, SummaryError> {
+        if !(0.0..=1.0).contains(&quantile) {
+            return Err(SummaryError::InvalidPercentile(quantile));
+        }
+        let value = percentile(&self.soc_samples, quantile).unwrap_or(0.0);
+        Ok(Energy::from_kwh(value).expect("percentile of valid energies should be valid"))
+    }
+
+    /// Hours spent full.
+    pub fn time_at_full(&self) -> f64 {
+        self.time_at_full
+    }
+
+    /// Hours spent empty.
+    pub fn time_at_empty(&self) -> f64 {
+        self.time_at_empty
+    }
+
+    pub fn peak_import(&self) -> Power {
+        self.peak_import
+    }
+
+    pub fn peak_export(&self) -> Power {
+        self.peak_export
+    }
+
+    /// Average absolute battery power as a fraction of its maximum power.
+    pub fn utilisation(&self) -> f64 {
+        if self.hours <= 0.0 {
+            return 0.0;
+        }
+        let throughput = (self.battery_charge + self.battery_discharge).as_kwh();
+        throughput / (self.max_power.as_kw() * self.hours)
+    }
+}
+
+fn check_lengths(telemetry_points: &[TelemetryPoint], states: &[BatteryState]) -> Result<(), SummaryError> {
+    if states.len() != telemetry_points.len() + 1 {
+        return Err(SummaryError::LengthMismatch {
+            expected: telemetry_points.len() + 1,
+            actual: states.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::{simulate_load_following, LoadFollowingSimulation};
+    use crate::types::AsEfficiency;
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 0.81.fraction()).expect("battery should be valid")
+    }
+
+    fn test_telemetry() -> Vec<TelemetryPoint> {
+        vec![
+            TelemetryPoint::new(hour!(2.0), kw!(8.0), kw!(1.0)),  // 7 kW excess, 5 kW charge limit
+            TelemetryPoint::new(hour!(2.0), kw!(0.0), kw!(3.0)),  // 3 kW deficit
+            TelemetryPoint::new(hour!(2.0), kw!(0.0), kw!(5.0)),  // 5 kW deficit, runs empty
+        ]
+    }
+
+    #[test]
+    fn test_summary_energy_totals() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+        let states = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+
+        let summary = SimulationSummary::from_states(&battery, &telemetry, &states).expect("valid states");
+
+        assert_abs_diff_eq!(summary.hours(), 6.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.solar().as_kwh(), 16.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.load().as_kwh(), 18.0, epsilon = EPSILON);
+        // Charge: 5 kW for 2 h stores 9 kWh, 4 kWh of excess exported.
+        assert_abs_diff_eq!(summary.battery_charge().as_kwh(), 10.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.grid_export().as_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.peak_export().as_kw(), 2.0, epsilon = EPSILON);
+        // Discharge: 6 kWh out of 9 stored costs 6 / 0.9, the remaining 2.33 kWh stored gives 2.1 kWh.
+        let stored_after_night = 9.0 - 6.0 / 0.9;
+        let delivered = 6.0 + stored_after_night * 0.9;
+        assert_abs_diff_eq!(summary.battery_discharge().as_kwh(), delivered, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.grid_import().as_kwh(), 16.0 - delivered, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.peak_import().as_kw(), (10.0 - stored_after_night * 0.9) / 2.0, epsilon = EPSILON);
+        // Everything stored was drawn out again, so losses are input less output.
+        assert_abs_diff_eq!(summary.losses().as_kwh(), 10.0 - delivered, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.self_consumption(), 12.0 / 16.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.self_sufficiency(), 1.0 - (16.0 - delivered) / 18.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_summary_islands_steps_without_grid() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry: Vec<TelemetryPoint> = test_telemetry().into_iter()
+            .map(|point| point.with_grid_available(false))
+            .collect();
+        let steps: Vec<StepResult> = LoadFollowingSimulation::new(telemetry.clone(), battery.clone(), initial_state)
+            .collect::<Result<_, _>>()
+            .expect("simulation should succeed");
+
+        let summary = SimulationSummary::from_steps(&battery, initial_state, &steps);
+
+        // The same flows as `test_summary_energy_totals`, none of them through the grid.
+        let delivered = 6.0 + (9.0 - 6.0 / 0.9) * 0.9;
+        assert_abs_diff_eq!(summary.grid_import().as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.grid_export().as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.peak_import().as_kw(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.curtailed_pv().as_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.unserved_energy().as_kwh(), 16.0 - delivered, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.self_consumption(), 12.0 / 16.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.self_sufficiency(), 1.0 - (16.0 - delivered) / 18.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(steps[0].curtailed_power().as_kw(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(steps[0].grid_power().as_kw(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(steps[2].unserved_power().as_kw(), summary.unserved_energy().as_kwh() / 2.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_summary_soc_statistics() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+        let states = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+
+        let summary = SimulationSummary::from_states(&battery, &telemetry, &states).expect("valid states");
+
+        let socs = [9.0, 9.0 - 6.0 / 0.9, 0.0];
+        assert_abs_diff_eq!(summary.average_soc().as_kwh(), socs.iter().sum::<f64>() / 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.soc_percentile(0.5).expect("valid").as_kwh(), socs[1], epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.time_at_empty(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(summary.time_at_full(), 0.0, epsilon = EPSILON);
+        // 9 kWh in and 9 kWh out of a 10 kWh battery.
+        assert_abs_diff_eq!(summary.equivalent_full_cycles(), 0.9, epsilon = EPSILON);
+        assert!(matches!(summary.soc_percentile(1.5), Err(SummaryError::InvalidPercentile(_))));
+    }
+
+    #[test]
+    fn test_summary_from_steps_matches_from_states() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(kwh!(5.0), Power::zero()).expect("valid state");
+        let telemetry = test_telemetry();
+        let states = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+        let steps: Vec<StepResult> = LoadFollowingSimulation::new(telemetry.clone(), battery.clone(), initial_state)
+            .collect::<Result<_, _>>()
+            .expect("simulation should succeed");
+
+        let from_states = SimulationSummary::from_states(&battery, &telemetry, &states).expect("valid states");
+        let from_steps = SimulationSummary::from_steps(&battery, initial_state, &steps);
+        let streamed = SimulationSummary::from_steps(
+            &battery, initial_state,
+            LoadFollowingSimulation::new(&telemetry, battery.clone(), initial_state).map(|step| step.expect("valid step")),
+        );
+
+        assert_abs_diff_eq!(from_states.grid_import().as_kwh(), from_steps.grid_import().as_kwh(), epsilon = EPSILON);
+        assert_abs_diff_eq!(streamed.grid_import().as_kwh(), from_steps.grid_import().as_kwh(), epsilon = EPSILON);
+        assert_eq!(from_steps.period_index(), None);
+        assert_abs_diff_eq!(from_states.losses().as_kwh(), from_steps.losses().as_kwh(), epsilon = EPSILON);
+        assert_abs_diff_eq!(from_states.utilisation(), from_steps.utilisation(), epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_summary_by_period_splits_days_and_months() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        // 40 days of 12 hour steps: a sunny half-day then a night.
+        let telemetry: Vec<TelemetryPoint> = (0..80)
+            .map(|i| if i % 2 == 0 {
+                TelemetryPoint::new(hour!(12.0), kw!(1.0), kw!(0.5))
+            } else {
+                TelemetryPoint::new(hour!(12.0), kw!(0.0), kw!(0.5))
+            })
+            .collect();
+        let states = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+
+        let days = SimulationSummary::by_period(&battery, &telemetry, &states, SummaryPeriod::Day)
+            .expect("valid states");
+        let months = SimulationSummary::by_period(&battery, &telemetry, &states, SummaryPeriod::Month)
+            .expect("valid states");
+        let total = SimulationSummary::from_states(&battery, &telemetry, &states).expect("valid states");
+
+        assert_eq!(days.len(), 40);
+        assert_eq!(days[39].period_index(), Some(39));
+        assert_abs_diff_eq!(days[0].hours(), 24.0, epsilon = EPSILON);
+        assert_eq!(months.len(), 2);
+        assert_abs_diff_eq!(months[0].hours(), 31.0 * 24.0, epsilon = EPSILON);
+        let monthly_import: f64 = months.iter().map(|m| m.grid_import().as_kwh()).sum();
+        assert_abs_diff_eq!(monthly_import, total.grid_import().as_kwh(), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_summary_by_period_indexes_skipped_periods() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        // A two day step jumps over the second day.
+        let telemetry = vec![
+            TelemetryPoint::new(hour!(48.0), kw!(0.0), kw!(0.5)),
+            TelemetryPoint::new(hour!(24.0), kw!(0.0), kw!(0.5)),
+        ];
+        let states = simulate_load_following(telemetry.clone(), battery.clone(), initial_state)
+            .expect("simulation should succeed");
+
+        let days = SimulationSummary::by_period(&battery, &telemetry, &states, SummaryPeriod::Day)
+            .expect("valid states");
+
+        let indexes: Vec<Option<usize>> = days.iter().map(|day| day.period_index()).collect();
+        assert_eq!(indexes, vec![Some(0), Some(2)]);
+    }
+
+    #[test]
+    fn test_summary_rejects_mismatched_states() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let result = SimulationSummary::from_states(&battery, &test_telemetry(), &[initial_state]);
+        assert!(matches!(result, Err(SummaryError::LengthMismatch { expected: 4, actual: 1 })));
+    }
+}
@@ -0,0 +1,243 @@
+use std::borrow::Borrow;
+use rayon::prelude::*;
+use crate::battery::{Battery, BatteryError, BatteryStateError};
+use crate::simulation::{LoadFollowingSimulation, SimulationError, StepResult};
+use crate::types::{Efficiency, Energy, Power, TelemetryPoint};
+
+/// The cartesian product of battery parameters to sweep over.
+#[derive(Debug, Clone, Default)]
+pub struct ParameterGrid {
+    capacities: Vec<Energy>,
+    max_powers: Vec<Power>,
+    round_trip_efficiencies: Vec<Efficiency>,
+}
+
+impl ParameterGrid {
+    pub fn new(
+        capacities: Vec<Energy>,
+        max_powers: Vec<Power>,
+        round_trip_efficiencies: Vec<Efficiency>,
+    ) -> Self {
+        ParameterGrid {
+            capacities,
+            max_powers,
+            round_trip_efficiencies,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.capacities.len() * self.max_powers.len() * self.round_trip_efficiencies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every combination as a `Battery`, capacity varying slowest.
+    pub fn batteries(&self) -> Result<Vec<Battery>, BatteryError> {
+        let mut batteries = Vec::with_capacity(self.len());
+        for &capacity in &self.capacities {
+            for &max_power in &self.max_powers {
+                for &efficiency in &self.round_trip_efficiencies {
+                    batteries.push(Battery::new(capacity, max_power, efficiency)?);
+                }
+            }
+        }
+        Ok(batteries)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SweepError {
+    #[error("Initial state of charge fraction {0} must be between 0 and 1.")]
+    InvalidInitialSoc(f64),
+    #[error("Invalid battery in the parameter grid.")]
+    InvalidBattery(#[source] BatteryError),
+    #[error("Failed to initialise scenario {1}.")]
+    ErrorInitialisingScenario(#[source] BatteryStateError, usize),
+    #[error("Scenario {1} failed.")]
+    ErrorSimulatingScenario(#[source] SimulationError, usize),
+}
+
+/// Headline results of a single scenario.
+#[derive(Debug, Clone, Copy)]
+pub struct ScenarioKpis {
+    grid_import: Energy,
+    grid_export: Energy,
+    battery_charge: Energy,     // energy delivered into the battery terminals
+    battery_discharge: Energy,  // energy delivered out of the battery terminals
+    final_state_of_charge: Energy,
+}
+
+impl ScenarioKpis {
+    pub(crate) fn from_steps<I, P>(steps: I, initial_state_of_charge: Energy) -> Result<Self, SimulationError>
+    where
+        I: IntoIterator<Item = Result<StepResult<P>, SimulationError>>,
+        P: Borrow<TelemetryPoint>,
+    {
+        let mut kpis = ScenarioKpis {
+            grid_import: Energy::zero(),
+            grid_export: Energy::zero(),
+            battery_charge: Energy::zero(),
+            battery_discharge: Energy::zero(),
+            final_state_of_charge: initial_state_of_charge,
+        };
+        for step in steps {
+            let step = step?;
+            let duration = step.telemetry_point().duration();
+            let grid_energy = step.grid_power() * duration;
+            let battery_energy = step.state().power() * duration;
+            if grid_energy > Energy::zero() {
+                kpis.grid_import = kpis.grid_import + grid_energy;
+            } else {
+                kpis.grid_export = kpis.grid_export - grid_energy;
+            }
+            if battery_energy > Energy::zero() {
+                kpis.battery_charge = kpis.battery_charge + battery_energy;
+            } else {
+                kpis.battery_discharge = kpis.battery_discharge - battery_energy;
+            }
+            kpis.final_state_of_charge = step.state().state_of_charge();
+        }
+        Ok(kpis)
+    }
+
+    pub fn grid_import(&self) -> Energy {
+        self.grid_import
+    }
+
+    pub fn grid_export(&self) -> Energy {
+        self.grid_export
+    }
+
+    pub fn battery_charge(&self) -> Energy {
+        self.battery_charge
+    }
+
+    pub fn battery_discharge(&self) -> Energy {
+        self.battery_discharge
+    }
+
+    pub fn final_state_of_charge(&self) -> Energy {
+        self.final_state_of_charge
+    }
+}
+
+/// One row of the sweep results table.
+#[derive(Debug, Clone)]
+pub struct ScenarioResult {
+    battery: Battery,
+    kpis: ScenarioKpis,
+}
+
+impl ScenarioResult {
+    pub fn battery(&self) -> &Battery {
+        &self.battery
+    }
+
+    pub fn kpis(&self) -> &ScenarioKpis {
+        &self.kpis
+    }
+}
+
+/// Simulates load following for every battery in `grid` against the same telemetry,
+/// spreading scenarios across all CPU cores.
+///
+/// Every scenario starts at `initial_soc_fraction` of its own capacity. The telemetry
+/// is borrowed by all scenarios rather than copied. Results are in the order of
+/// `ParameterGrid::batteries`.
+pub fn run_sweep(
+    telemetry_points: &[TelemetryPoint],
+    grid: &ParameterGrid,
+    initial_soc_fraction: f64,
+) -> Result<Vec<ScenarioResult>, SweepError> {
+    let batteries = grid.batteries().map_err(SweepError::InvalidBattery)?;
+    run_scenarios(telemetry_points, batteries, initial_soc_fraction)
+}
+
+/// As `run_sweep`, for an arbitrary list of batteries.
+pub fn run_scenarios(
+    telemetry_points: &[TelemetryPoint],
+    batteries: Vec<Battery>,
+    initial_soc_fraction: f64,
+) -> Result<Vec<ScenarioResult>, SweepError> {
+    if !(0.0..=1.0).contains(&initial_soc_fraction) {
+        return Err(SweepError::InvalidInitialSoc(initial_soc_fraction));
+    }
+
+    batteries.into_par_iter()
+        .enumerate()
+        .map(|(i, battery)| {
+            let initial_soc = Energy::from_kwh(battery.capacity().as_kwh() * initial_soc_fraction)
+                .expect("fraction of a valid capacity should be valid");
+            let initial_state = battery.init_state(initial_soc, Power::zero())
+                .map_err(|e| SweepError::ErrorInitialisingScenario(e, i))?;
+            let steps = LoadFollowingSimulation::new(telemetry_points, battery.clone(), initial_state);
+            let kpis = ScenarioKpis::from_steps(steps, initial_soc)
+                .map_err(|e| SweepError::ErrorSimulatingScenario(e, i))?;
+            Ok(ScenarioResult { battery, kpis })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AsEfficiency, Duration};
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_telemetry() -> Vec<TelemetryPoint> {
+        vec![
+            TelemetryPoint::new(hour!(1.0), kw!(10.0), kw!(2.0)),  // +8 kW excess
+            TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(6.0)),   // -6 kW deficit
+        ]
+    }
+
+    #[test]
+    fn test_parameter_grid_is_cartesian_product() {
+        let grid = ParameterGrid::new(
+            vec![kwh!(5.0), kwh!(10.0)],
+            vec![kw!(2.0), kw!(4.0), kw!(8.0)],
+            vec![1.0.fraction()],
+        );
+        let batteries = grid.batteries().expect("valid grid");
+        assert_eq!(grid.len(), 6);
+        assert_eq!(batteries.len(), 6);
+        assert_eq!(batteries[0].capacity(), kwh!(5.0));
+        assert_eq!(batteries[2].max_power(), kw!(8.0));
+        assert_eq!(batteries[3].capacity(), kwh!(10.0));
+    }
+
+    #[test]
+    fn test_run_sweep_reports_kpis_per_scenario() {
+        let telemetry = test_telemetry();
+        let grid = ParameterGrid::new(vec![kwh!(4.0), kwh!(20.0)], vec![kw!(10.0)], vec![1.0.fraction()]);
+
+        let results = run_sweep(&telemetry, &grid, 0.0).expect("sweep should succeed");
+
+        assert_eq!(results.len(), 2);
+        // 4 kWh battery: stores 4 of the 8 kWh excess, exports 4, then covers 4 of the 6 kWh deficit.
+        let small = results[0].kpis();
+        assert_abs_diff_eq!(small.grid_export().as_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(small.grid_import().as_kwh(), 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(small.battery_charge().as_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(small.battery_discharge().as_kwh(), 4.0, epsilon = EPSILON);
+        // 20 kWh battery: stores all 8 kWh and covers the whole deficit.
+        let large = results[1].kpis();
+        assert_abs_diff_eq!(large.grid_export().as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(large.grid_import().as_kwh(), 0.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(large.final_state_of_charge().as_kwh(), 2.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_run_sweep_rejects_invalid_inputs() {
+        let telemetry = test_telemetry();
+        let grid = ParameterGrid::new(vec![kwh!(4.0)], vec![kw!(10.0)], vec![1.0.fraction()]);
+        assert!(matches!(run_sweep(&telemetry, &grid, 1.5), Err(SweepError::InvalidInitialSoc(_))));
+
+        let grid = ParameterGrid::new(vec![kwh!(-4.0)], vec![kw!(10.0)], vec![1.0.fraction()]);
+        assert!(matches!(run_sweep(&telemetry, &grid, 0.5), Err(SweepError::InvalidBattery(_))));
+    }
+}
@@ -0,0 +1,271 @@
+use crate::battery::{Battery, BatteryError, BatteryState};
+use crate::types::{Duration, Energy, Power, TelemetryPoint};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ThreePhaseError {
+    #[error("Simulating the three-phase site failed on step {1}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize),
+}
+
+/// One phase of a three-phase supply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    L1,
+    L2,
+    L3,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 3] = [Phase::L1, Phase::L2, Phase::L3];
+
+    fn index(self) -> usize {
+        match self {
+            Phase::L1 => 0,
+            Phase::L2 => 1,
+            Phase::L3 => 2,
+        }
+    }
+}
+
+/// Which phases the battery inverter is connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryConnection {
+    /// All battery power flows on one phase.
+    SinglePhase(Phase),
+    /// Battery power is shared equally across the three phases.
+    ThreePhase,
+}
+
+/// How the meter nets the flows of the three phases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetMetering {
+    /// Export on one phase offsets import on another within the same step.
+    PhaseSummed,
+    /// Each phase is billed on its own flow.
+    PerPhase,
+}
+
+/// Solar and load on each phase over one step.
+#[derive(Debug, Clone)]
+pub struct PhaseTelemetryPoint {
+    duration: Duration,
+    solar_power: [Power; 3],
+    load_power: [Power; 3],
+}
+
+impl PhaseTelemetryPoint {
+    pub fn new(duration: Duration, solar_power: [Power; 3], load_power: [Power; 3]) -> Self {
+        PhaseTelemetryPoint { duration, solar_power, load_power }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn solar_power(&self, phase: Phase) -> Power {
+        self.solar_power[phase.index()]
+    }
+
+    pub fn load_power(&self, phase: Phase) -> Power {
+        self.load_power[phase.index()]
+    }
+
+    pub fn excess_pv(&self, phase: Phase) -> Power {
+        self.solar_power(phase) - self.load_power(phase)
+    }
+
+    /// The site as a single channel, with the phases summed.
+    pub fn to_telemetry_point(&self) -> TelemetryPoint {
+        let sum = |powers: &[Power; 3]| powers.iter().fold(Power::zero(), |total, &power| total + power);
+        TelemetryPoint::new(self.duration, sum(&self.solar_power), sum(&self.load_power))
+    }
+}
+
+/// Battery states and per-phase grid flows of a three-phase simulation.
+#[derive(Debug, Clone)]
+pub struct ThreePhaseResult {
+    states: Vec<BatteryState>,
+    grid_power: Vec<[Power; 3]>,
+    phase_import: [Energy; 3],
+    phase_export: [Energy; 3],
+    grid_import: Energy,
+    grid_export: Energy,
+}
+
+impl ThreePhaseResult {
+    /// The initial state followed by the battery state after every step.
+    pub fn states(&self) -> &[BatteryState] {
+        &self.states
+    }
+
+    /// Power drawn from the grid on `phase` on every step, negative when exporting.
+    pub fn grid_power(&self, phase: Phase) -> Vec<Power> {
+        self.grid_power.iter().map(|powers| powers[phase.index()]).collect()
+    }
+
+    /// Energy imported on `phase`, before any netting between phases.
+    pub fn phase_import(&self, phase: Phase) -> Energy {
+        self.phase_import[phase.index()]
+    }
+
+    /// Energy exported on `phase`, before any netting between phases.
+    pub fn phase_export(&self, phase: Phase) -> Energy {
+        self.phase_export[phase.index()]
+    }
+
+    /// Energy imported as billed under the chosen net metering.
+    pub fn grid_import(&self) -> Energy {
+        self.grid_import
+    }
+
+    /// Energy exported as billed under the chosen net metering.
+    pub fn grid_export(&self) -> Energy {
+        self.grid_export
+    }
+}
+
+/// Load-follows a battery on a three-phase site, metering each phase.
+///
+/// The battery follows the net flow its controller can act on: the whole site under
+/// phase-summed metering, and only the phases it is connected to under per-phase
+/// metering.
+pub fn simulate_three_phase(
+    telemetry_points: &[PhaseTelemetryPoint],
+    battery: &Battery,
+    initial_state: BatteryState,
+    connection: BatteryConnection,
+    metering: NetMetering,
+) -> Result<ThreePhaseResult, ThreePhaseError> {
+    let mut states = Vec::with_capacity(telemetry_points.len() + 1);
+    states.push(initial_state);
+    let mut grid_power = Vec::with_capacity(telemetry_points.len());
+    let mut phase_import = [Energy::zero(); 3];
+    let mut phase_export = [Energy::zero(); 3];
+    let (mut grid_import, mut grid_export) = (Energy::zero(), Energy::zero());
+    let mut state = initial_state;
+
+    for (step, point) in telemetry_points.iter().enumerate() {
+        let requested = match (metering, connection) {
+            (NetMetering::PerPhase, BatteryConnection::SinglePhase(phase)) => point.excess_pv(phase),
+            _ => Phase::ALL.iter().fold(Power::zero(), |total, &phase| total + point.excess_pv(phase)),
+        };
+        state = battery.step(&state, requested, point.duration)
+            .map_err(|e| ThreePhaseError::ErrorSimulatingStep(e, step))?;
+        states.push(state);
+
+        let mut flows = [Power::zero(); 3];
+        let mut net = Power::zero();
+        for phase in Phase::ALL {
+            let battery_power = match connection {
+                BatteryConnection::SinglePhase(connected) if connected == phase => state.power(),
+                BatteryConnection::SinglePhase(_) => Power::zero(),
+                BatteryConnection::ThreePhase => Power::from_kw(state.power().as_kw() / 3.0)
+                    .expect("a third of battery power should be valid"),
+            };
+            let flow = battery_power - point.excess_pv(phase);
+            let energy = flow * point.duration;
+            if flow > Power::zero() {
+                phase_import[phase.index()] = phase_import[phase.index()] + energy;
+            } else {
+                phase_export[phase.index()] = phase_export[phase.index()] - energy;
+            }
+            flows[phase.index()] = flow;
+            net = net + flow;
+        }
+        grid_power.push(flows);
+
+        if metering == NetMetering::PhaseSummed {
+            let energy = net * point.duration;
+            if net > Power::zero() {
+                grid_import = grid_import + energy;
+            } else {
+                grid_export = grid_export - energy;
+            }
+        }
+    }
+
+    if metering == NetMetering::PerPhase {
+        grid_import = phase_import.iter().fold(Energy::zero(), |total, &energy| total + energy);
+        grid_export = phase_export.iter().fold(Energy::zero(), |total, &energy| total + energy);
+    }
+    Ok(ThreePhaseResult { states, grid_power, phase_import, phase_export, grid_import, grid_export })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::simulate_load_following;
+    use crate::types::AsEfficiency;
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn test_battery() -> Battery {
+        Battery::new(kwh!(10.0), kw!(5.0), 1.0.fraction()).expect("battery should be valid")
+    }
+
+    // PV on L1 during the day, load on L2 at night.
+    fn split_site() -> Vec<PhaseTelemetryPoint> {
+        vec![
+            PhaseTelemetryPoint::new(hour!(1.0), [kw!(4.0), kw!(0.0), kw!(0.0)], [kw!(0.0), kw!(1.0), kw!(0.0)]),
+            PhaseTelemetryPoint::new(hour!(1.0), [kw!(0.0), kw!(0.0), kw!(0.0)], [kw!(0.0), kw!(2.0), kw!(0.0)]),
+        ]
+    }
+
+    #[test]
+    fn test_phase_summed_matches_single_channel() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+        let telemetry = split_site();
+
+        let result = simulate_three_phase(
+            &telemetry, &battery, initial_state, BatteryConnection::SinglePhase(Phase::L1), NetMetering::PhaseSummed,
+        ).expect("simulation should succeed");
+        let single = simulate_load_following(
+            telemetry.iter().map(|point| point.to_telemetry_point()).collect(), battery, initial_state,
+        ).expect("simulation should succeed");
+
+        let soc = |states: &[BatteryState]| states.iter().map(|s| s.state_of_charge_kwh()).collect::<Vec<_>>();
+        assert_eq!(soc(result.states()), soc(&single));
+        assert_abs_diff_eq!(result.grid_import().as_kwh(), 0.0, epsilon = EPSILON);
+        // The phases still carry their own flows.
+        assert_abs_diff_eq!(result.phase_import(Phase::L2).as_kwh(), 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.phase_export(Phase::L1).as_kwh(), 3.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_single_phase_battery_cannot_offset_other_phases() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+
+        let result = simulate_three_phase(
+            &split_site(), &battery, initial_state, BatteryConnection::SinglePhase(Phase::L1), NetMetering::PerPhase,
+        ).expect("simulation should succeed");
+
+        // The battery stores all of L1's PV but nothing on L1 ever draws it back.
+        assert_abs_diff_eq!(result.states()[2].state_of_charge_kwh(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.grid_import().as_kwh(), 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.phase_import(Phase::L2).as_kwh(), 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.grid_export().as_kwh(), 0.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_three_phase_battery_shares_power() {
+        let battery = test_battery();
+        let initial_state = battery.init_state(Energy::zero(), Power::zero()).expect("valid state");
+
+        let result = simulate_three_phase(
+            &split_site(), &battery, initial_state, BatteryConnection::ThreePhase, NetMetering::PerPhase,
+        ).expect("simulation should succeed");
+
+        // Charging 3 kW takes 1 kW from each phase while all PV sits on L1.
+        let l1: Vec<f64> = result.grid_power(Phase::L1).iter().map(|p| p.as_kw()).collect();
+        let l2: Vec<f64> = result.grid_power(Phase::L2).iter().map(|p| p.as_kw()).collect();
+        assert_abs_diff_eq!(l1[0], -3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(l2[0], 2.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(l2[1], 4.0 / 3.0, epsilon = EPSILON);
+        // Under per-phase metering the unbalance is billed on both sides.
+        assert_abs_diff_eq!(result.grid_import().as_kwh(), 2.0 + 1.0 + 4.0 / 3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.grid_export().as_kwh(), 3.0 + 2.0 / 3.0 * 2.0, epsilon = EPSILON);
+    }
+}
@@ -1,4 +1,6 @@
+use std::borrow::Cow;
 use std::fmt;
+use std::sync::{Arc, OnceLock};
 use std::ops::{Add, Sub, Mul, Div, Neg};
 
 const MIN_VALUE: f64 = 1e-10;
@@ -100,6 +102,16 @@ impl Power {
         }
     }
 
+    /// Power clamped to the representable range, with NaN read as zero. For values
+    /// computed from models rather than measured.
+    pub fn from_kw_saturating(power_kw: f64) -> Self {
+        if power_kw.is_nan() {
+            Self(0.0)
+        } else {
+            Self(power_kw.clamp(-MAX_VALUE, MAX_VALUE))
+        }
+    }
+
     pub const fn from_kw_const(power_kw: f64) -> Self {
         if power_kw.is_infinite() || power_kw.is_nan() || power_kw > MAX_VALUE {
             panic!("Invalid power value.")
@@ -121,6 +133,10 @@ impl Power {
 
     }
 
+    pub fn max(self, other: Power) -> Power {
+        Power(self.0.max(other.0))
+    }
+
     pub fn zero() -> Self {
         Self(0.0)
     }
@@ -139,6 +155,14 @@ impl Neg for Power {
     }
 }
 
+impl Add for Power {
+    type Output = Power;
+
+    fn add(self, rhs: Power) -> Power {
+        Power(self.0 + rhs.0)
+    }
+}
+
 impl Sub for Power {
     type Output = Power;
 
@@ -379,32 +403,151 @@ impl Mul<Energy> for Efficiency {
 }
 
 
+/// Whether a telemetry channel produces or consumes power.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    Generation,
+    Consumption,
+}
+
+/// A named generation or consumption source, such as one PV array or load sub-circuit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryChannel {
+    name: Arc<str>,
+    kind: ChannelKind,
+    power: Power,
+}
+
+impl TelemetryChannel {
+    pub fn new(name: impl Into<Arc<str>>, kind: ChannelKind, power: Power) -> Self {
+        TelemetryChannel { name: name.into(), kind, power }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> ChannelKind {
+        self.kind
+    }
+
+    pub fn power(&self) -> Power {
+        self.power
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChannelError {
+    #[error("Channel {0} appears more than once.")]
+    DuplicateName(Arc<str>),
+    #[error("Total {kind:?} power {total} kW is out of range.")]
+    TotalOutOfRange { kind: ChannelKind, total: f64 },
+}
+
+// The channel names of points built from plain solar and load, shared by every point.
+fn solar_load_names() -> &'static (Arc<str>, Arc<str>) {
+    static NAMES: OnceLock<(Arc<str>, Arc<str>)> = OnceLock::new();
+    NAMES.get_or_init(|| (Arc::from("solar"), Arc::from("load")))
+}
+
+#[derive(Debug, Clone)]
 pub struct TelemetryPoint {
     duration: Duration,
     solar_power: Power,
     load_power: Power,
+    grid_available: bool,
+    // `None` for points built from plain solar and load.
+    channels: Option<Vec<TelemetryChannel>>,
 }
 
 impl TelemetryPoint {
+    /// A point with a single "solar" generation channel and a single "load"
+    /// consumption channel.
     pub fn new(duration: Duration, solar_power: Power, load_power: Power) -> Self {
         TelemetryPoint{
             duration,
             solar_power,
             load_power,
+            grid_available: true,
+            channels: None,
+        }
+    }
+
+    /// Builds a point from named channels. `solar_power` and `load_power` are then the
+    /// totals of the generation and consumption channels. Channel names must be unique,
+    /// and each total must be a valid `Power`.
+    pub fn from_channels(duration: Duration, channels: Vec<TelemetryChannel>) -> Result<Self, ChannelError> {
+        let duplicate = channels.iter().enumerate()
+            .find(|(i, channel)| channels[..*i].iter().any(|other| other.name == channel.name));
+        if let Some((_, channel)) = duplicate {
+            return Err(ChannelError::DuplicateName(channel.name.clone()));
         }
+        let total = |kind: ChannelKind| {
+            let total: f64 = channels.iter()
+                .filter(|channel| channel.kind == kind)
+                .map(|channel| channel.power.0)
+                .sum();
+            Power::from_kw(total).map_err(|total| ChannelError::TotalOutOfRange { kind, total })
+        };
+        Ok(TelemetryPoint {
+            duration,
+            solar_power: total(ChannelKind::Generation)?,
+            load_power: total(ChannelKind::Consumption)?,
+            grid_available: true,
+            channels: Some(channels),
+        })
+    }
+
+    /// Marks whether the grid was available over this point. `LoadFollowingSimulation`
+    /// and `simulate_with_outages` island the site when it was not.
+    pub fn with_grid_available(mut self, grid_available: bool) -> Self {
+        self.grid_available = grid_available;
+        self
     }
 
     pub fn duration(&self) -> Duration {
         self.duration
     }
 
+    /// Total generation over all generation channels.
     pub fn solar_power(&self) -> Power {
         self.solar_power
     }
+
+    /// Total consumption over all consumption channels.
     pub fn load_power(&self) -> Power {
         self.load_power
     }
 
+    pub fn grid_available(&self) -> bool {
+        self.grid_available
+    }
+
+    /// The named channels, which always add up to `solar_power` and `load_power`.
+    /// Points built with `new` have their "solar" and "load" channels built here.
+    pub fn channels(&self) -> Cow<'_, [TelemetryChannel]> {
+        match &self.channels {
+            Some(channels) => Cow::Borrowed(channels),
+            None => {
+                let (solar, load) = solar_load_names();
+                Cow::Owned(vec![
+                    TelemetryChannel::new(solar.clone(), ChannelKind::Generation, self.solar_power),
+                    TelemetryChannel::new(load.clone(), ChannelKind::Consumption, self.load_power),
+                ])
+            }
+        }
+    }
+
+    pub fn channel_power(&self, name: &str) -> Option<Power> {
+        match &self.channels {
+            Some(channels) => channels.iter().find(|channel| channel.name() == name).map(|channel| channel.power),
+            None if name == "solar" => Some(self.solar_power),
+            None if name == "load" => Some(self.load_power),
+            None => None,
+        }
+    }
+
+    /// Total generation less total consumption.
     pub fn excess_pv(&self) -> Power {
         self.solar_power - self.load_power
     }
@@ -540,6 +683,14 @@ mod tests {
         assert_abs_diff_eq!(p.0, 0.0, epsilon = EPSILON);
     }
 
+    #[test]
+    fn test_power_from_kw_saturating_clamps() {
+        assert_eq!(Power::from_kw_saturating(2.0 * MAX_VALUE).as_kw(), MAX_VALUE);
+        assert_eq!(Power::from_kw_saturating(f64::NEG_INFINITY).as_kw(), -MAX_VALUE);
+        assert_eq!(Power::from_kw_saturating(f64::NAN).as_kw(), 0.0);
+        assert_eq!(Power::from_kw_saturating(-3.0).as_kw(), -3.0);
+    }
+
     #[test]
     fn test_power_to_kw() {
         let p = Power::from_kw(123.45).expect("finite values should be accepted");
@@ -933,4 +1084,52 @@ mod tests {
         let tp = TelemetryPoint::new(hour!(0.25), kw!(10.0), kw!(5.0));
         assert_abs_diff_eq!(tp.duration().as_hour(), 0.25, epsilon = EPSILON);
     }
+
+    #[test]
+    fn test_telemetry_point_from_channels() {
+        let tp = TelemetryPoint::from_channels(hour!(0.5), vec![
+            TelemetryChannel::new("pv_east", ChannelKind::Generation, kw!(3.0)),
+            TelemetryChannel::new("pv_west", ChannelKind::Generation, kw!(2.0)),
+            TelemetryChannel::new("wind", ChannelKind::Generation, kw!(1.0)),
+            TelemetryChannel::new("critical", ChannelKind::Consumption, kw!(1.5)),
+            TelemetryChannel::new("pool", ChannelKind::Consumption, kw!(2.5)),
+        ]).expect("channel names should be unique");
+        assert_abs_diff_eq!(tp.solar_power().as_kw(), 6.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(tp.load_power().as_kw(), 4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(tp.excess_pv().as_kw(), 2.0, epsilon = EPSILON);
+        assert_eq!(tp.channel_power("wind"), Some(kw!(1.0)));
+        assert_eq!(tp.channel_power("missing"), None);
+        assert_eq!(tp.channels().len(), 5);
+    }
+
+    #[test]
+    fn test_telemetry_point_new_has_solar_and_load_channels() {
+        let tp = TelemetryPoint::new(hour!(1.0), kw!(3.0), kw!(1.0));
+        assert_eq!(tp.channels().len(), 2);
+        assert_eq!(tp.channel_power("solar"), Some(kw!(3.0)));
+        assert_eq!(tp.channel_power("load"), Some(kw!(1.0)));
+        assert_eq!(tp.channels()[1].kind(), ChannelKind::Consumption);
+    }
+
+    #[test]
+    fn test_telemetry_point_from_channels_rejects_duplicate_names() {
+        let result = TelemetryPoint::from_channels(hour!(1.0), vec![
+            TelemetryChannel::new("pv", ChannelKind::Generation, kw!(3.0)),
+            TelemetryChannel::new("house", ChannelKind::Consumption, kw!(1.0)),
+            TelemetryChannel::new("pv", ChannelKind::Consumption, kw!(2.0)),
+        ]);
+        assert!(matches!(result, Err(ChannelError::DuplicateName(name)) if &*name == "pv"));
+    }
+
+    #[test]
+    fn test_telemetry_point_from_channels_rejects_out_of_range_totals() {
+        let result = TelemetryPoint::from_channels(hour!(1.0), vec![
+            TelemetryChannel::new("pv_east", ChannelKind::Generation, kw!(600_000.0)),
+            TelemetryChannel::new("pv_west", ChannelKind::Generation, kw!(600_000.0)),
+        ]);
+        assert!(matches!(
+            result,
+            Err(ChannelError::TotalOutOfRange { kind: ChannelKind::Generation, total }) if total == 1_200_000.0
+        ));
+    }
 }
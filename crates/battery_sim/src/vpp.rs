@@ -0,0 +1,249 @@
+use rayon::prelude::*;
+use crate::battery::{Battery, BatteryError, BatteryState};
+use crate::types::{Duration, Energy, Power, TelemetryPoint};
+
+#[derive(Debug, thiserror::Error)]
+pub enum VppError {
+    #[error("A portfolio needs at least one site.")]
+    NoSites,
+    #[error("Site {site} has {actual} telemetry points, expected {expected}.")]
+    LengthMismatch { site: usize, expected: usize, actual: usize },
+    #[error("The dispatch signal has {actual} steps, expected {expected}.")]
+    SignalLengthMismatch { expected: usize, actual: usize },
+    #[error("Site {site} has a different step duration from the first site on step {step}.")]
+    MisalignedStep { site: usize, step: usize },
+    #[error("Simulating site {1} failed on step {2}.")]
+    ErrorSimulatingStep(#[source] BatteryError, usize, usize),
+}
+
+/// A home battery in the fleet, with its own telemetry.
+#[derive(Debug, Clone)]
+pub struct VppSite {
+    battery: Battery,
+    initial_state: BatteryState,
+    telemetry: Vec<TelemetryPoint>,
+}
+
+impl VppSite {
+    pub fn new(battery: Battery, initial_state: BatteryState, telemetry: Vec<TelemetryPoint>) -> Self {
+        VppSite { battery, initial_state, telemetry }
+    }
+
+    pub fn battery(&self) -> &Battery {
+        &self.battery
+    }
+
+    pub fn telemetry(&self) -> &[TelemetryPoint] {
+        &self.telemetry
+    }
+}
+
+/// How the fleet dispatch is split between sites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisaggregationPolicy {
+    /// Every site gets the same power.
+    Equal,
+    /// Sites get power in proportion to their battery capacity.
+    ProRataCapacity,
+    /// Sites get power in proportion to how far they can still move in the requested
+    /// direction, so the fleet only falls short once every site is at its limit.
+    ProRataHeadroom,
+}
+
+/// Fleet and site results of a portfolio simulation.
+#[derive(Debug, Clone)]
+pub struct PortfolioResult {
+    site_states: Vec<Vec<BatteryState>>,
+    requested: Vec<Power>,
+    delivered: Vec<Power>,
+    durations: Vec<Duration>,
+}
+
+impl PortfolioResult {
+    pub fn sites(&self) -> usize {
+        self.site_states.len()
+    }
+
+    /// The initial state followed by the site's battery state after every step.
+    pub fn site_states(&self, site: usize) -> &[BatteryState] {
+        &self.site_states[site]
+    }
+
+    /// The fleet power requested by the aggregator on every step.
+    pub fn requested(&self) -> &[Power] {
+        &self.requested
+    }
+
+    /// The fleet power delivered on top of self-consumption on every step.
+    pub fn delivered(&self) -> &[Power] {
+        &self.delivered
+    }
+
+    /// Requested energy the fleet did not deliver, in either direction.
+    pub fn shortfall(&self) -> Energy {
+        self.requested.iter().zip(&self.delivered).zip(&self.durations)
+            .fold(Energy::zero(), |total, ((&requested, &delivered), &duration)| {
+                total + (requested - delivered).abs() * duration
+            })
+    }
+}
+
+// A site's self-consumption power and how far it can move up and down from it.
+struct SiteLimits {
+    baseline: Power,
+    up: Power,
+    down: Power,
+}
+
+fn site_limits(site: &VppSite, state: &BatteryState, point: &TelemetryPoint) -> SiteLimits {
+    let max_charge = site.battery.max_achievable_charge_power(state, point.duration());
+    let max_discharge = site.battery.max_achievable_discharge_power(state, point.duration());
+    let baseline = point.excess_pv().min(max_charge).max(-max_discharge);
+    SiteLimits { baseline, up: max_charge - baseline, down: max_discharge + baseline }
+}
+
+/// Simulates a fleet of home batteries in lockstep.
+///
+/// Every site load-follows its own telemetry, and on top of that the fleet follows
+/// `signal`, the extra battery power the aggregator requests on every step, positive
+/// to charge. The request is split between sites by `policy` and each site's part is
+/// capped at what its battery can still do.
+pub fn simulate_portfolio(
+    sites: &[VppSite],
+    signal: &[Power],
+    policy: DisaggregationPolicy,
+) -> Result<PortfolioResult, VppError> {
+    let first = sites.first().ok_or(VppError::NoSites)?;
+    let steps = first.telemetry.len();
+    if signal.len() != steps {
+        return Err(VppError::SignalLengthMismatch { expected: steps, actual: signal.len() });
+    }
+    for (k, site) in sites.iter().enumerate() {
+        if site.telemetry.len() != steps {
+            return Err(VppError::LengthMismatch { site: k, expected: steps, actual: site.telemetry.len() });
+        }
+        if let Some(step) = (0..steps).find(|&i| site.telemetry[i].duration() != first.telemetry[i].duration()) {
+            return Err(VppError::MisalignedStep { site: k, step });
+        }
+    }
+
+    let total_capacity: f64 = sites.iter().map(|site| site.battery.capacity().as_kwh()).sum();
+    let mut site_states: Vec<Vec<BatteryState>> = sites.iter()
+        .map(|site| {
+            let mut states = Vec::with_capacity(steps + 1);
+            states.push(site.initial_state);
+            states
+        })
+        .collect();
+    let mut delivered = Vec::with_capacity(steps);
+
+    for (step, &request) in signal.iter().enumerate() {
+        let limits: Vec<SiteLimits> = sites.par_iter().zip(&site_states)
+            .map(|(site, states)| site_limits(site, &states[step], &site.telemetry[step]))
+            .collect();
+        let headroom = |limit: &SiteLimits| if request > Power::zero() { limit.up } else { limit.down };
+        let total_headroom: f64 = limits.iter().map(|limit| headroom(limit).as_kw()).sum();
+
+        let steps_taken: Vec<Result<(BatteryState, Power), BatteryError>> = sites.par_iter()
+            .zip(&site_states)
+            .zip(&limits)
+            .map(|((site, states), limit)| {
+                let weight = match policy {
+                    DisaggregationPolicy::Equal => 1.0 / sites.len() as f64,
+                    DisaggregationPolicy::ProRataCapacity => site.battery.capacity().as_kwh() / total_capacity,
+                    DisaggregationPolicy::ProRataHeadroom if total_headroom > 0.0 => {
+                        headroom(limit).as_kw() / total_headroom
+                    }
+                    DisaggregationPolicy::ProRataHeadroom => 0.0,
+                };
+                let share = (request.as_kw() * weight).min(limit.up.as_kw()).max(-limit.down.as_kw());
+                let set_point = limit.baseline + Power::from_kw(share).expect("site share should be valid");
+                let state = site.battery.step(&states[step], set_point, site.telemetry[step].duration())?;
+                Ok((state, state.power() - limit.baseline))
+            })
+            .collect();
+
+        let mut fleet = Power::zero();
+        for (k, taken) in steps_taken.into_iter().enumerate() {
+            let (state, response) = taken.map_err(|e| VppError::ErrorSimulatingStep(e, k, step))?;
+            site_states[k].push(state);
+            fleet = fleet + response;
+        }
+        delivered.push(fleet);
+    }
+
+    let durations = first.telemetry.iter().map(|point| point.duration()).collect();
+    Ok(PortfolioResult { site_states, requested: signal.to_vec(), delivered, durations })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AsEfficiency;
+    use crate::{hour, kw, kwh};
+    use approx::assert_abs_diff_eq;
+    const EPSILON: f64 = 1e-9;
+
+    fn site(capacity: Energy, soc: Energy, excess: Power) -> VppSite {
+        let battery = Battery::new(capacity, kw!(5.0), 1.0.fraction()).expect("battery should be valid");
+        let initial_state = battery.init_state(soc, Power::zero()).expect("valid state");
+        let telemetry = vec![TelemetryPoint::new(hour!(1.0), excess.max(Power::zero()), (-excess).max(Power::zero())); 2];
+        VppSite::new(battery, initial_state, telemetry)
+    }
+
+    fn kws(values: &[Power]) -> Vec<f64> {
+        values.iter().map(|p| p.as_kw()).collect()
+    }
+
+    #[test]
+    fn test_no_signal_is_plain_self_consumption() {
+        let sites = [site(kwh!(10.0), kwh!(5.0), kw!(2.0)), site(kwh!(10.0), kwh!(5.0), kw!(-1.0))];
+
+        let result = simulate_portfolio(&sites, &[Power::zero(); 2], DisaggregationPolicy::Equal)
+            .expect("simulation should succeed");
+
+        assert_eq!(kws(result.delivered()), vec![0.0, 0.0]);
+        assert_abs_diff_eq!(result.site_states(0)[2].state_of_charge_kwh(), 9.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.site_states(1)[2].state_of_charge_kwh(), 3.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_fleet_discharges_on_top_of_self_consumption() {
+        let sites = [site(kwh!(10.0), kwh!(5.0), kw!(2.0)), site(kwh!(10.0), kwh!(5.0), kw!(-1.0))];
+
+        let result = simulate_portfolio(&sites, &[kw!(-6.0), kw!(-6.0)], DisaggregationPolicy::Equal)
+            .expect("simulation should succeed");
+
+        // Each site discharges 3 kW below its self-consumption power.
+        assert_abs_diff_eq!(result.site_states(0)[1].power_kw(), -1.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.site_states(1)[1].power_kw(), -4.0, epsilon = EPSILON);
+        assert_eq!(kws(result.delivered())[0], -6.0);
+        // The second site is then down to the 1 kWh its own load needs.
+        assert_abs_diff_eq!(result.delivered()[1].as_kw(), -3.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(result.shortfall().as_kwh(), 3.0, epsilon = EPSILON);
+    }
+
+    #[test]
+    fn test_headroom_policy_uses_the_whole_fleet() {
+        let sites = [site(kwh!(10.0), kwh!(1.0), kw!(0.0)), site(kwh!(10.0), kwh!(9.0), kw!(0.0))];
+
+        let equal = simulate_portfolio(&sites, &[kw!(-6.0); 2], DisaggregationPolicy::Equal)
+            .expect("simulation should succeed");
+        let headroom = simulate_portfolio(&sites, &[kw!(-6.0); 2], DisaggregationPolicy::ProRataHeadroom)
+            .expect("simulation should succeed");
+
+        assert_abs_diff_eq!(equal.delivered()[0].as_kw(), -4.0, epsilon = EPSILON);
+        assert_abs_diff_eq!(headroom.delivered()[0].as_kw(), -6.0, epsilon = EPSILON);
+        assert!(headroom.shortfall() < equal.shortfall());
+    }
+
+    #[test]
+    fn test_rejects_misaligned_inputs() {
+        let sites = [site(kwh!(10.0), kwh!(5.0), kw!(0.0))];
+        assert!(matches!(simulate_portfolio(&[], &[], DisaggregationPolicy::Equal), Err(VppError::NoSites)));
+        assert!(matches!(
+            simulate_portfolio(&sites, &[Power::zero()], DisaggregationPolicy::Equal),
+            Err(VppError::SignalLengthMismatch { expected: 2, actual: 1 })
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use battery_sim::battery::{BatteryError, BatteryModel, BatteryState, BatteryStateError};
+use battery_sim::simulation::{simulate_load_following_tolerant, FailurePolicy};
+use battery_sim::types::{Duration, Energy, Power, TelemetryPoint};
+use battery_sim::{hour, kw, kwh};
+
+// A lossless battery with no power limit, written against the public API only.
+struct Bucket {
+    capacity: Energy,
+}
+
+impl BatteryModel for Bucket {
+    fn capacity(&self) -> Energy {
+        self.capacity
+    }
+
+    fn max_power(&self) -> Power {
+        kw!(1000.0)
+    }
+
+    fn init_state(&self, state_of_charge: Energy, power: Power) -> Result<BatteryState, BatteryStateError> {
+        if state_of_charge > self.capacity {
+            return Err(BatteryStateError::StateOfChargeGreaterThanCapacity(state_of_charge, self.capacity));
+        }
+        BatteryState::try_new(state_of_charge, power)
+    }
+
+    fn step(&self, battery_state: &BatteryState, power: Power, duration: Duration) -> Result<BatteryState, BatteryError> {
+        let target = (battery_state.state_of_charge_kwh() + (power * duration).as_kwh())
+            .clamp(0.0, self.capacity.as_kwh());
+        let state_of_charge = Energy::from_kwh(target).expect("a clamped state of charge should be valid");
+        let power = Power::from_kw((target - battery_state.state_of_charge_kwh()) / duration.as_hour())
+            .expect("power within the step should be valid");
+        BatteryState::try_new(state_of_charge, power).map_err(BatteryError::ErrorCharging)
+    }
+}
+
+#[test]
+fn test_custom_model_from_public_api() {
+    let model = Bucket { capacity: kwh!(5.0) };
+    let initial_state = model.init_state(kwh!(1.0), Power::zero()).expect("valid state");
+    let telemetry = vec![
+        TelemetryPoint::new(hour!(1.0), kw!(6.0), kw!(0.0)),
+        TelemetryPoint::new(hour!(1.0), kw!(0.0), kw!(2.0)),
+    ];
+
+    let result = simulate_load_following_tolerant(telemetry, &model, initial_state, FailurePolicy::Skip);
+
+    assert!(result.diagnostics().is_empty());
+    let states: Vec<BatteryState> = result.states().iter().map(|state| state.expect("no step was skipped")).collect();
+    let soc: Vec<f64> = states.iter().map(|state| state.state_of_charge_kwh()).collect();
+    assert_eq!(soc, vec![1.0, 5.0, 3.0]);
+    assert_eq!(states[1].power_kw(), 4.0);
+}
+
+#[test]
+fn test_try_new_rejects_negative_state_of_charge() {
+    let negative = Energy::from_kwh(-1.0).expect("energy should be valid");
+    assert!(matches!(
+        BatteryState::try_new(negative, Power::zero()),
+        Err(BatteryStateError::NegativeStateOfCharge)
+    ));
+    let state = BatteryState::try_new(kwh!(2.0), kw!(-1.0)).expect("valid state");
+    assert_eq!(state.state_of_charge_kwh(), 2.0);
+    assert_eq!(state.power_kw(), -1.0);
+}
@@ -1,11 +1,17 @@
 use pyo3::prelude::*;
 use pyo3::exceptions::{PyValueError, PyRuntimeError};
 use numpy::{PyReadonlyArray1, PyArray1};
+use std::collections::BTreeMap;
 
 // Import from the core library - use :: prefix to avoid ambiguity with the pymodule name
 use ::battery_sim::battery::Battery;
+use ::battery_sim::events::{simulate_load_following_observed, BatteryEvent, EventLog, StepEvent};
+use ::battery_sim::outage::{backup_durations, OutageError};
 use ::battery_sim::simulation::simulate_load_following;
-use ::battery_sim::types::{Duration, Power, Energy, Efficiency, TelemetryPoint};
+use ::battery_sim::summary::{SimulationSummary, SummaryPeriod};
+use ::battery_sim::types::{
+    ChannelKind, Duration, Power, Energy, Efficiency, TelemetryChannel, TelemetryPoint,
+};
 
 // ============================================================================
 // PyBattery Class
@@ -77,6 +83,199 @@ impl PyBattery {
     }
 }
 
+// ============================================================================
+// PySimulationSummary Class
+// ============================================================================
+
+/// Headline metrics of a load following simulation.
+#[pyclass(name = "SimulationSummary")]
+pub struct PySimulationSummary {
+    inner: SimulationSummary,
+}
+
+#[pymethods]
+impl PySimulationSummary {
+    /// Total simulated time in hours.
+    #[getter]
+    fn hours(&self) -> f64 {
+        self.inner.hours()
+    }
+
+    /// Day or month index of a per-period summary, counting from zero; None for a whole run.
+    #[getter]
+    fn period_index(&self) -> Option<usize> {
+        self.inner.period_index()
+    }
+
+    /// Total solar generation in kWh.
+    #[getter]
+    fn solar_kwh(&self) -> f64 {
+        self.inner.solar().as_kwh()
+    }
+
+    /// Total load in kWh.
+    #[getter]
+    fn load_kwh(&self) -> f64 {
+        self.inner.load().as_kwh()
+    }
+
+    /// Energy imported from the grid in kWh.
+    #[getter]
+    fn grid_import_kwh(&self) -> f64 {
+        self.inner.grid_import().as_kwh()
+    }
+
+    /// Energy exported to the grid in kWh.
+    #[getter]
+    fn grid_export_kwh(&self) -> f64 {
+        self.inner.grid_export().as_kwh()
+    }
+
+    /// Fraction of solar generation used on site.
+    #[getter]
+    fn self_consumption(&self) -> f64 {
+        self.inner.self_consumption()
+    }
+
+    /// Fraction of the load met without grid import.
+    #[getter]
+    fn self_sufficiency(&self) -> f64 {
+        self.inner.self_sufficiency()
+    }
+
+    /// Energy into the battery terminals in kWh.
+    #[getter]
+    fn battery_charge_kwh(&self) -> f64 {
+        self.inner.battery_charge().as_kwh()
+    }
+
+    /// Energy out of the battery terminals in kWh.
+    #[getter]
+    fn battery_discharge_kwh(&self) -> f64 {
+        self.inner.battery_discharge().as_kwh()
+    }
+
+    /// Equivalent full charge/discharge cycles.
+    #[getter]
+    fn equivalent_full_cycles(&self) -> f64 {
+        self.inner.equivalent_full_cycles()
+    }
+
+    /// Battery conversion losses in kWh.
+    #[getter]
+    fn losses_kwh(&self) -> f64 {
+        self.inner.losses().as_kwh()
+    }
+
+    /// Time-weighted average state of charge in kWh.
+    #[getter]
+    fn average_soc_kwh(&self) -> f64 {
+        self.inner.average_soc().as_kwh()
+    }
+
+    /// Hours spent full.
+    #[getter]
+    fn time_at_full_hours(&self) -> f64 {
+        self.inner.time_at_full()
+    }
+
+    /// Hours spent empty.
+    #[getter]
+    fn time_at_empty_hours(&self) -> f64 {
+        self.inner.time_at_empty()
+    }
+
+    /// Peak grid import in kW.
+    #[getter]
+    fn peak_import_kw(&self) -> f64 {
+        self.inner.peak_import().as_kw()
+    }
+
+    /// Peak grid export in kW.
+    #[getter]
+    fn peak_export_kw(&self) -> f64 {
+        self.inner.peak_export().as_kw()
+    }
+
+    /// Average absolute battery power as a fraction of max power.
+    #[getter]
+    fn utilisation(&self) -> f64 {
+        self.inner.utilisation()
+    }
+
+    /// State of charge percentile in kWh.
+    ///
+    /// Parameters
+    /// ----------
+    /// quantile : float
+    ///     Quantile between 0 and 1.
+    ///
+    /// Raises
+    /// ------
+    /// ValueError
+    ///     If the quantile is outside [0, 1].
+    fn soc_percentile_kwh(&self, quantile: f64) -> PyResult<f64> {
+        self.inner.soc_percentile(quantile)
+            .map(|soc| soc.as_kwh())
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SimulationSummary(hours={:.1}, grid_import={:.1} kWh, grid_export={:.1} kWh, self_sufficiency={:.1}%)",
+            self.hours(),
+            self.grid_import_kwh(),
+            self.grid_export_kwh(),
+            self.self_sufficiency() * 100.0
+        )
+    }
+}
+
+// ============================================================================
+// PyBatteryEvent Class
+// ============================================================================
+
+/// A battery constraint activation or change of direction during a simulation.
+#[pyclass(name = "BatteryEvent")]
+pub struct PyBatteryEvent {
+    step: usize,
+    kind: &'static str,
+    requested_kw: Option<f64>,
+    delivered_kw: Option<f64>,
+}
+
+#[pymethods]
+impl PyBatteryEvent {
+    /// Index of the step the event happened on.
+    #[getter]
+    fn step(&self) -> usize {
+        self.step
+    }
+
+    /// One of "hit_full", "hit_empty", "power_limited", "energy_limited",
+    /// "switched_to_charging" or "switched_to_discharging".
+    #[getter]
+    fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// Requested battery power in kW for limit events, otherwise None.
+    #[getter]
+    fn requested_kw(&self) -> Option<f64> {
+        self.requested_kw
+    }
+
+    /// Delivered battery power in kW for limit events, otherwise None.
+    #[getter]
+    fn delivered_kw(&self) -> Option<f64> {
+        self.delivered_kw
+    }
+
+    fn __repr__(&self) -> String {
+        format!("BatteryEvent(step={}, kind='{}')", self.step, self.kind)
+    }
+}
+
 // ============================================================================
 // Helper Functions (internal, pure Rust)
 // ============================================================================
@@ -123,6 +322,34 @@ fn build_telemetry_points(
         .collect()
 }
 
+/// Builds TelemetryPoints from named channel columns, each as long as `duration`.
+fn build_channel_telemetry_points(
+    duration: &[f64],
+    channels: &[(String, ChannelKind, Vec<f64>)],
+) -> Result<Vec<TelemetryPoint>, String> {
+    if duration.is_empty() {
+        return Err("arrays must be at least 1 long.".to_string());
+    }
+    if let Some((name, _, _)) = channels.iter().find(|(_, _, values)| values.len() != duration.len()) {
+        return Err(format!("channel {} must have the same length as duration_hours", name));
+    }
+    duration.iter()
+        .enumerate()
+        .map(|(i, &d)| -> Result<TelemetryPoint, String> {
+            let duration = Duration::from_hour(d)
+                .map_err(|v| format!("invalid duration: {}", v))?;
+            let channels = channels.iter()
+                .map(|(name, kind, values)| {
+                    let power = Power::from_kw(values[i])
+                        .map_err(|v| format!("invalid {}: {}", name, v))?;
+                    Ok(TelemetryChannel::new(name.as_str(), *kind, power))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            TelemetryPoint::from_channels(duration, channels).map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
 /// Converts f64 values to validated BatteryState.
 fn build_initial_state(
     battery: &Battery,
@@ -145,8 +372,67 @@ fn extract_results(states: &[BatteryState]) -> (Vec<f64>, Vec<f64>) {
     (soc, power)
 }
 
+/// Converts a core event into its Python representation.
+fn convert_event(event: &StepEvent) -> PyBatteryEvent {
+    let (kind, requested_kw, delivered_kw) = match event.event() {
+        BatteryEvent::HitFull => ("hit_full", None, None),
+        BatteryEvent::HitEmpty => ("hit_empty", None, None),
+        BatteryEvent::PowerLimited { requested, delivered } =>
+            ("power_limited", Some(requested.as_kw()), Some(delivered.as_kw())),
+        BatteryEvent::EnergyLimited { requested, delivered } =>
+            ("energy_limited", Some(requested.as_kw()), Some(delivered.as_kw())),
+        BatteryEvent::SwitchedToCharging => ("switched_to_charging", None, None),
+        BatteryEvent::SwitchedToDischarging => ("switched_to_discharging", None, None),
+    };
+    PyBatteryEvent { step: event.step(), kind, requested_kw, delivered_kw }
+}
+
+/// Parses a summary period name.
+fn parse_period(period: &str) -> Result<SummaryPeriod, String> {
+    match period {
+        "day" => Ok(SummaryPeriod::Day),
+        "month" => Ok(SummaryPeriod::Month),
+        other => Err(format!("period must be 'day' or 'month', got '{}'", other)),
+    }
+}
+
+/// Validates the numpy inputs and builds the telemetry and initial state.
+fn build_inputs(
+    duration_hours: &PyReadonlyArray1<'_, f64>,
+    solar_power_kw: &PyReadonlyArray1<'_, f64>,
+    load_power_kw: &PyReadonlyArray1<'_, f64>,
+    battery: &Battery,
+    initial_soc_kwh: f64,
+    initial_power_kw: f64,
+) -> PyResult<(Vec<TelemetryPoint>, BatteryState)> {
+    // 1. Validate array lengths match
+    validate_array_lengths(
+        duration_hours.len()?,
+        solar_power_kw.len()?,
+        load_power_kw.len()?,
+    ).map_err(PyValueError::new_err)?;
+
+    // 2. Get slices from numpy arrays
+    let duration = duration_hours.as_slice()
+        .map_err(|e| PyValueError::new_err(format!("Failed to read duration array: {}", e)))?;
+    let solar = solar_power_kw.as_slice()
+        .map_err(|e| PyValueError::new_err(format!("Failed to read solar_power array: {}", e)))?;
+    let load = load_power_kw.as_slice()
+        .map_err(|e| PyValueError::new_err(format!("Failed to read load_power array: {}", e)))?;
+
+    // 3. Build TelemetryPoints with validation
+    let telemetry = build_telemetry_points(duration, solar, load)
+        .map_err(PyValueError::new_err)?;
+
+    // 4. Build initial state
+    let initial_state = build_initial_state(battery, initial_soc_kwh, initial_power_kw)
+        .map_err(PyValueError::new_err)?;
+
+    Ok((telemetry, initial_state))
+}
+
 // ============================================================================
-// Python Function
+// Python Functions
 // ============================================================================
 type DoublePyArray = PyArray1<f64>;
 
@@ -189,34 +475,80 @@ fn simulate_load_following_py<'py>(
     initial_soc_kwh: f64,
     initial_power_kw: f64,
 ) -> PyResult<(Bound<'py, DoublePyArray>, Bound<'py, DoublePyArray>)> {
-    // 1. Validate array lengths match
-    validate_array_lengths(
-        duration_hours.len()?,
-        solar_power_kw.len()?,
-        load_power_kw.len()?,
-    ).map_err(PyValueError::new_err)?;
+    let (telemetry, initial_state) = build_inputs(
+        &duration_hours, &solar_power_kw, &load_power_kw,
+        &battery.inner, initial_soc_kwh, initial_power_kw,
+    )?;
 
-    // 2. Get slices from numpy arrays
+    // Run simulation
+    let states = simulate_load_following(telemetry, battery.inner.clone(), initial_state)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+
+    // Convert results to numpy arrays
+    let (soc, power) = extract_results(&states);
+    Ok((
+        PyArray1::from_vec(py, soc),
+        PyArray1::from_vec(py, power),
+    ))
+}
+
+/// Simulate battery load following on named generation and consumption channels.
+///
+/// Parameters
+/// ----------
+/// duration_hours : numpy.ndarray
+///     Duration of each time step in hours.
+/// generation_kw : dict[str, numpy.ndarray]
+///     Generation channels such as PV arrays or wind, by name, in kW.
+/// consumption_kw : dict[str, numpy.ndarray]
+///     Consumption channels such as load sub-circuits, by name, in kW.
+/// battery : Battery
+///     Battery object with capacity, max power, and efficiency.
+/// initial_soc_kwh : float
+///     Initial state of charge in kWh.
+/// initial_power_kw : float
+///     Initial power in kW.
+///
+/// Returns
+/// -------
+/// tuple[numpy.ndarray, numpy.ndarray]
+///     Tuple of (state_of_charge_kwh, power_kw) arrays.
+///
+/// Raises
+/// ------
+/// ValueError
+///     If inputs are invalid (mismatched array lengths, etc.)
+/// RuntimeError
+///     If simulation fails during execution.
+#[pyfunction]
+#[pyo3(name = "simulate_load_following_channels")]
+fn simulate_load_following_channels_py<'py>(
+    py: Python<'py>,
+    duration_hours: PyReadonlyArray1<'py, f64>,
+    generation_kw: BTreeMap<String, PyReadonlyArray1<'py, f64>>,
+    consumption_kw: BTreeMap<String, PyReadonlyArray1<'py, f64>>,
+    battery: &PyBattery,
+    initial_soc_kwh: f64,
+    initial_power_kw: f64,
+) -> PyResult<(Bound<'py, DoublePyArray>, Bound<'py, DoublePyArray>)> {
     let duration = duration_hours.as_slice()
         .map_err(|e| PyValueError::new_err(format!("Failed to read duration array: {}", e)))?;
-    let solar = solar_power_kw.as_slice()
-        .map_err(|e| PyValueError::new_err(format!("Failed to read solar_power array: {}", e)))?;
-    let load = load_power_kw.as_slice()
-        .map_err(|e| PyValueError::new_err(format!("Failed to read load_power array: {}", e)))?;
-
-    // 3. Build TelemetryPoints with validation
-    let telemetry = build_telemetry_points(duration, solar, load)
+    let mut channels = Vec::with_capacity(generation_kw.len() + consumption_kw.len());
+    for (kind, columns) in [(ChannelKind::Generation, &generation_kw), (ChannelKind::Consumption, &consumption_kw)] {
+        for (name, values) in columns {
+            let values = values.as_slice()
+                .map_err(|e| PyValueError::new_err(format!("Failed to read {} array: {}", name, e)))?;
+            channels.push((name.clone(), kind, values.to_vec()));
+        }
+    }
+    let telemetry = build_channel_telemetry_points(duration, &channels)
         .map_err(PyValueError::new_err)?;
-
-    // 4. Build initial state
     let initial_state = build_initial_state(&battery.inner, initial_soc_kwh, initial_power_kw)
         .map_err(PyValueError::new_err)?;
 
-    // 5. Run simulation
     let states = simulate_load_following(telemetry, battery.inner.clone(), initial_state)
         .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
 
-    // 6. Convert results to numpy arrays
     let (soc, power) = extract_results(&states);
     Ok((
         PyArray1::from_vec(py, soc),
@@ -224,6 +556,235 @@ fn simulate_load_following_py<'py>(
     ))
 }
 
+/// Simulate battery load following and record battery events.
+///
+/// Parameters
+/// ----------
+/// duration_hours : numpy.ndarray
+///     Duration of each time step in hours.
+/// solar_power_kw : numpy.ndarray
+///     Solar power generation at each time step in kW.
+/// load_power_kw : numpy.ndarray
+///     Load power consumption at each time step in kW.
+/// battery : Battery
+///     Battery object with capacity, max power, and efficiency.
+/// initial_soc_kwh : float
+///     Initial state of charge in kWh.
+/// initial_power_kw : float
+///     Initial power in kW.
+///
+/// Returns
+/// -------
+/// tuple[numpy.ndarray, numpy.ndarray, list[BatteryEvent]]
+///     Tuple of (state_of_charge_kwh, power_kw, events), with events in step order.
+///
+/// Raises
+/// ------
+/// ValueError
+///     If inputs are invalid (mismatched array lengths, etc.)
+/// RuntimeError
+///     If simulation fails during execution.
+#[pyfunction]
+#[pyo3(name = "simulate_load_following_with_events")]
+fn simulate_load_following_with_events_py<'py>(
+    py: Python<'py>,
+    duration_hours: PyReadonlyArray1<'py, f64>,
+    solar_power_kw: PyReadonlyArray1<'py, f64>,
+    load_power_kw: PyReadonlyArray1<'py, f64>,
+    battery: &PyBattery,
+    initial_soc_kwh: f64,
+    initial_power_kw: f64,
+) -> PyResult<(Bound<'py, DoublePyArray>, Bound<'py, DoublePyArray>, Vec<PyBatteryEvent>)> {
+    let (telemetry, initial_state) = build_inputs(
+        &duration_hours, &solar_power_kw, &load_power_kw,
+        &battery.inner, initial_soc_kwh, initial_power_kw,
+    )?;
+
+    let mut log = EventLog::new();
+    let states = simulate_load_following_observed(telemetry, battery.inner.clone(), initial_state, &mut log)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+
+    let (soc, power) = extract_results(&states);
+    Ok((
+        PyArray1::from_vec(py, soc),
+        PyArray1::from_vec(py, power),
+        log.events().iter().map(convert_event).collect(),
+    ))
+}
+
+/// Estimate backup hours at every step of a load following simulation.
+///
+/// For each step, the hours the battery and PV could carry the critical load if the
+/// grid failed at the start of that step.
+///
+/// Parameters
+/// ----------
+/// duration_hours : numpy.ndarray
+///     Duration of each time step in hours.
+/// solar_power_kw : numpy.ndarray
+///     Solar power generation at each time step in kW.
+/// load_power_kw : numpy.ndarray
+///     Load power consumption at each time step in kW.
+/// battery : Battery
+///     Battery object with capacity, max power, and efficiency.
+/// initial_soc_kwh : float
+///     Initial state of charge in kWh.
+/// initial_power_kw : float
+///     Initial power in kW.
+/// critical_load_fraction : float, optional
+///     Fraction of the load that must be served during an outage. Defaults to 1.
+/// max_hours : float, optional
+///     Longest autonomy to look for. Defaults to one week.
+///
+/// Returns
+/// -------
+/// numpy.ndarray
+///     Backup hours at each time step, capped at max_hours and at the end of the data.
+///
+/// Raises
+/// ------
+/// ValueError
+///     If inputs are invalid (mismatched array lengths, etc.)
+/// RuntimeError
+///     If simulation fails during execution.
+#[pyfunction]
+#[pyo3(
+    name = "estimate_backup_hours",
+    signature = (
+        duration_hours, solar_power_kw, load_power_kw, battery, initial_soc_kwh, initial_power_kw,
+        critical_load_fraction = 1.0, max_hours = 168.0,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+fn estimate_backup_hours_py<'py>(
+    py: Python<'py>,
+    duration_hours: PyReadonlyArray1<'py, f64>,
+    solar_power_kw: PyReadonlyArray1<'py, f64>,
+    load_power_kw: PyReadonlyArray1<'py, f64>,
+    battery: &PyBattery,
+    initial_soc_kwh: f64,
+    initial_power_kw: f64,
+    critical_load_fraction: f64,
+    max_hours: f64,
+) -> PyResult<Bound<'py, DoublePyArray>> {
+    let (telemetry, initial_state) = build_inputs(
+        &duration_hours, &solar_power_kw, &load_power_kw,
+        &battery.inner, initial_soc_kwh, initial_power_kw,
+    )?;
+    let states = simulate_load_following(telemetry.clone(), battery.inner.clone(), initial_state)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    let backup = backup_durations(&telemetry, &states, &battery.inner, critical_load_fraction, max_hours)
+        .map_err(|e| match e {
+            OutageError::ErrorSimulatingStep(..) => PyRuntimeError::new_err(e.to_string()),
+            _ => PyValueError::new_err(e.to_string()),
+        })?;
+    Ok(PyArray1::from_vec(py, backup.hours().to_vec()))
+}
+
+/// Simulate load following and summarise the run.
+///
+/// Parameters
+/// ----------
+/// duration_hours : numpy.ndarray
+///     Duration of each time step in hours.
+/// solar_power_kw : numpy.ndarray
+///     Solar power generation at each time step in kW.
+/// load_power_kw : numpy.ndarray
+///     Load power consumption at each time step in kW.
+/// battery : Battery
+///     Battery object with capacity, max power, and efficiency.
+/// initial_soc_kwh : float
+///     Initial state of charge in kWh.
+/// initial_power_kw : float
+///     Initial power in kW.
+///
+/// Returns
+/// -------
+/// SimulationSummary
+///     Metrics for the whole run.
+///
+/// Raises
+/// ------
+/// ValueError
+///     If inputs are invalid (mismatched array lengths, etc.)
+/// RuntimeError
+///     If simulation fails during execution.
+#[pyfunction]
+#[pyo3(name = "summarise_load_following")]
+fn summarise_load_following_py(
+    duration_hours: PyReadonlyArray1<'_, f64>,
+    solar_power_kw: PyReadonlyArray1<'_, f64>,
+    load_power_kw: PyReadonlyArray1<'_, f64>,
+    battery: &PyBattery,
+    initial_soc_kwh: f64,
+    initial_power_kw: f64,
+) -> PyResult<PySimulationSummary> {
+    let (telemetry, initial_state) = build_inputs(
+        &duration_hours, &solar_power_kw, &load_power_kw,
+        &battery.inner, initial_soc_kwh, initial_power_kw,
+    )?;
+    let states = simulate_load_following(telemetry.clone(), battery.inner.clone(), initial_state)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    let inner = SimulationSummary::from_states(&battery.inner, &telemetry, &states)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    Ok(PySimulationSummary { inner })
+}
+
+/// Simulate load following and summarise each day or month of the run.
+///
+/// Telemetry is assumed to start at midnight on 1 January of a 365-day year.
+///
+/// Parameters
+/// ----------
+/// duration_hours : numpy.ndarray
+///     Duration of each time step in hours.
+/// solar_power_kw : numpy.ndarray
+///     Solar power generation at each time step in kW.
+/// load_power_kw : numpy.ndarray
+///     Load power consumption at each time step in kW.
+/// battery : Battery
+///     Battery object with capacity, max power, and efficiency.
+/// initial_soc_kwh : float
+///     Initial state of charge in kWh.
+/// initial_power_kw : float
+///     Initial power in kW.
+/// period : str
+///     Either "day" or "month".
+///
+/// Returns
+/// -------
+/// list[SimulationSummary]
+///     One summary per period with telemetry, in time order, each with its period_index.
+///
+/// Raises
+/// ------
+/// ValueError
+///     If inputs are invalid (mismatched array lengths, unknown period, etc.)
+/// RuntimeError
+///     If simulation fails during execution.
+#[pyfunction]
+#[pyo3(name = "summarise_load_following_by_period")]
+fn summarise_load_following_by_period_py(
+    duration_hours: PyReadonlyArray1<'_, f64>,
+    solar_power_kw: PyReadonlyArray1<'_, f64>,
+    load_power_kw: PyReadonlyArray1<'_, f64>,
+    battery: &PyBattery,
+    initial_soc_kwh: f64,
+    initial_power_kw: f64,
+    period: &str,
+) -> PyResult<Vec<PySimulationSummary>> {
+    let period = parse_period(period).map_err(PyValueError::new_err)?;
+    let (telemetry, initial_state) = build_inputs(
+        &duration_hours, &solar_power_kw, &load_power_kw,
+        &battery.inner, initial_soc_kwh, initial_power_kw,
+    )?;
+    let states = simulate_load_following(telemetry.clone(), battery.inner.clone(), initial_state)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    let summaries = SimulationSummary::by_period(&battery.inner, &telemetry, &states, period)
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    Ok(summaries.into_iter().map(|inner| PySimulationSummary { inner }).collect())
+}
+
 // ============================================================================
 // Python Module
 // ============================================================================
@@ -231,7 +792,14 @@ fn simulate_load_following_py<'py>(
 #[pymodule]
 fn battery_sim(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyBattery>()?;
+    m.add_class::<PySimulationSummary>()?;
+    m.add_class::<PyBatteryEvent>()?;
     m.add_function(wrap_pyfunction!(simulate_load_following_py, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate_load_following_channels_py, m)?)?;
+    m.add_function(wrap_pyfunction!(simulate_load_following_with_events_py, m)?)?;
+    m.add_function(wrap_pyfunction!(summarise_load_following_py, m)?)?;
+    m.add_function(wrap_pyfunction!(estimate_backup_hours_py, m)?)?;
+    m.add_function(wrap_pyfunction!(summarise_load_following_by_period_py, m)?)?;
     Ok(())
 }
 
@@ -338,6 +906,45 @@ mod tests {
         }
     }
 
+    // -------------------------------------------------------------------------
+    // build_channel_telemetry_points tests
+    // -------------------------------------------------------------------------
+
+    #[test]
+    fn test_build_channel_telemetry_sums_channels() {
+        let channels = vec![
+            ("pv_east".to_string(), ChannelKind::Generation, vec![2.0, 1.0]),
+            ("pv_west".to_string(), ChannelKind::Generation, vec![1.0, 0.0]),
+            ("critical".to_string(), ChannelKind::Consumption, vec![0.5, 2.0]),
+        ];
+
+        let telemetry = build_channel_telemetry_points(&[1.0, 1.0], &channels).unwrap();
+        assert_eq!(telemetry[0].solar_power().as_kw(), 3.0);
+        assert_eq!(telemetry[1].excess_pv().as_kw(), -1.0);
+        assert_eq!(telemetry[1].channel_power("pv_east").map(|p| p.as_kw()), Some(1.0));
+    }
+
+    #[test]
+    fn test_build_channel_telemetry_length_mismatch() {
+        let channels = vec![("wind".to_string(), ChannelKind::Generation, vec![1.0])];
+        match build_channel_telemetry_points(&[1.0, 1.0], &channels) {
+            Err(e) => assert!(e.contains("wind"), "expected channel name, got: {}", e),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+
+    #[test]
+    fn test_build_channel_telemetry_duplicate_name() {
+        let channels = vec![
+            ("pool".to_string(), ChannelKind::Generation, vec![1.0]),
+            ("pool".to_string(), ChannelKind::Consumption, vec![1.0]),
+        ];
+        match build_channel_telemetry_points(&[1.0], &channels) {
+            Err(e) => assert!(e.contains("pool"), "expected channel name, got: {}", e),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+
     // -------------------------------------------------------------------------
     // build_initial_state tests
     // -------------------------------------------------------------------------
@@ -415,4 +1022,45 @@ mod tests {
         assert!((soc[0] - 50.0).abs() < 1e-9);
         assert!((power[0] - 10.0).abs() < 1e-9);
     }
+
+    // -------------------------------------------------------------------------
+    // parse_period tests
+    // -------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_period_valid() {
+        assert_eq!(parse_period("day"), Ok(SummaryPeriod::Day));
+        assert_eq!(parse_period("month"), Ok(SummaryPeriod::Month));
+    }
+
+    #[test]
+    fn test_parse_period_invalid() {
+        match parse_period("week") {
+            Err(e) => assert!(e.contains("'day' or 'month'"), "expected period error, got: {}", e),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+
+    // -------------------------------------------------------------------------
+    // convert_event tests
+    // -------------------------------------------------------------------------
+
+    #[test]
+    fn test_convert_event_limit() {
+        let mut log = EventLog::new();
+        let battery = Battery::new(
+            Energy::from_kwh(10.0).unwrap(),
+            Power::from_kw(5.0).unwrap(),
+            Efficiency::from_fraction(1.0).unwrap(),
+        ).unwrap();
+        let telemetry = build_telemetry_points(&[1.0], &[8.0], &[1.0]).unwrap();
+        let state = build_initial_state(&battery, 0.0, 0.0).unwrap();
+        simulate_load_following_observed(telemetry, battery, state, &mut log).unwrap();
+
+        let event = convert_event(&log.events()[0]);
+        assert_eq!(event.step, 0);
+        assert_eq!(event.kind, "power_limited");
+        assert_eq!(event.requested_kw, Some(7.0));
+        assert_eq!(event.delivered_kw, Some(5.0));
+    }
 }
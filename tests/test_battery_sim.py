@@ -3,7 +3,15 @@
 import numpy as np
 import pytest
 
-from battery_sim import Battery, simulate_load_following
+from battery_sim import (
+    Battery,
+    estimate_backup_hours,
+    simulate_load_following,
+    simulate_load_following_channels,
+    simulate_load_following_with_events,
+    summarise_load_following,
+    summarise_load_following_by_period,
+)
 
 
 # ============================================================================
@@ -208,3 +216,203 @@ class TestSimulationBehavior:
             initial_power_kw=0.0,
         )
         assert power_discharge[0] < 0, "Discharging power should be negative"
+
+
+# ============================================================================
+# SimulationSummary Tests
+# ============================================================================
+
+
+class TestSimulationSummary:
+    """Tests for summarise_load_following and summarise_load_following_by_period."""
+
+    def test_summary_energy_totals(self):
+        """Verify grid totals and self-sufficiency for a lossless battery."""
+        battery = Battery(capacity_kwh=20.0, max_power_kw=10.0, efficiency=1.0)
+
+        duration = np.array([1.0, 1.0])
+        solar = np.array([10.0, 0.0])
+        load = np.array([2.0, 6.0])
+
+        summary = summarise_load_following(
+            duration_hours=duration,
+            solar_power_kw=solar,
+            load_power_kw=load,
+            battery=battery,
+            initial_soc_kwh=0.0,
+            initial_power_kw=0.0,
+        )
+
+        assert summary.hours == pytest.approx(2.0)
+        assert summary.grid_import_kwh == pytest.approx(0.0)
+        assert summary.grid_export_kwh == pytest.approx(0.0)
+        assert summary.self_sufficiency == pytest.approx(1.0)
+        assert summary.losses_kwh == pytest.approx(0.0)
+        assert summary.soc_percentile_kwh(1.0) == pytest.approx(8.0)
+
+    def test_summary_invalid_percentile_raises(self):
+        """Verify out-of-range percentiles raise ValueError."""
+        battery = Battery(capacity_kwh=20.0, max_power_kw=10.0, efficiency=1.0)
+        summary = summarise_load_following(
+            duration_hours=np.array([1.0]),
+            solar_power_kw=np.array([10.0]),
+            load_power_kw=np.array([2.0]),
+            battery=battery,
+            initial_soc_kwh=0.0,
+            initial_power_kw=0.0,
+        )
+        with pytest.raises(ValueError):
+            summary.soc_percentile_kwh(1.5)
+
+    def test_summary_by_day(self):
+        """Verify hourly telemetry over two days gives two daily summaries."""
+        battery = Battery(capacity_kwh=20.0, max_power_kw=10.0, efficiency=0.9)
+
+        duration = np.ones(48)
+        solar = np.tile(np.concatenate([np.zeros(8), np.full(8, 5.0), np.zeros(8)]), 2)
+        load = np.full(48, 1.0)
+
+        days = summarise_load_following_by_period(
+            duration_hours=duration,
+            solar_power_kw=solar,
+            load_power_kw=load,
+            battery=battery,
+            initial_soc_kwh=0.0,
+            initial_power_kw=0.0,
+            period="day",
+        )
+
+        assert len(days) == 2
+        assert all(day.hours == pytest.approx(24.0) for day in days)
+
+    def test_summary_unknown_period_raises(self):
+        """Verify an unknown period raises ValueError."""
+        battery = Battery(capacity_kwh=20.0, max_power_kw=10.0, efficiency=0.9)
+        with pytest.raises(ValueError, match="period"):
+            summarise_load_following_by_period(
+                duration_hours=np.array([1.0]),
+                solar_power_kw=np.array([1.0]),
+                load_power_kw=np.array([1.0]),
+                battery=battery,
+                initial_soc_kwh=0.0,
+                initial_power_kw=0.0,
+                period="week",
+            )
+
+
+# ============================================================================
+# simulate_load_following_with_events Tests
+# ============================================================================
+
+
+class TestSimulationEvents:
+    """Tests for battery event reporting."""
+
+    def test_events_report_limits(self):
+        """Verify power limits and hitting full are reported with their step."""
+        battery = Battery(capacity_kwh=10.0, max_power_kw=5.0, efficiency=1.0)
+
+        duration = np.array([1.0, 1.0])
+        solar = np.array([8.0, 5.0])
+        load = np.array([1.0, 1.0])
+
+        soc, power, events = simulate_load_following_with_events(
+            duration_hours=duration,
+            solar_power_kw=solar,
+            load_power_kw=load,
+            battery=battery,
+            initial_soc_kwh=2.0,
+            initial_power_kw=0.0,
+        )
+
+        assert len(soc) == 2
+        kinds = [(event.step, event.kind) for event in events]
+        assert kinds == [(0, "power_limited"), (1, "hit_full"), (1, "energy_limited")]
+        assert events[0].requested_kw == pytest.approx(7.0)
+        assert events[0].delivered_kw == pytest.approx(5.0)
+        assert events[1].requested_kw is None
+
+
+# ============================================================================
+# estimate_backup_hours Tests
+# ============================================================================
+
+
+class TestBackupHours:
+    """Tests for the backup duration estimate."""
+
+    def test_backup_hours_decrease_as_battery_drains(self):
+        """Verify autonomy falls by an hour for every hour of discharge."""
+        battery = Battery(capacity_kwh=10.0, max_power_kw=5.0, efficiency=1.0)
+
+        backup = estimate_backup_hours(
+            duration_hours=np.ones(4),
+            solar_power_kw=np.zeros(4),
+            load_power_kw=np.ones(4),
+            battery=battery,
+            initial_soc_kwh=3.0,
+            initial_power_kw=0.0,
+        )
+
+        np.testing.assert_allclose(backup, [3.0, 2.0, 1.0, 0.0])
+
+    def test_backup_hours_invalid_fraction_raises(self):
+        """Verify an out-of-range critical load fraction raises ValueError."""
+        battery = Battery(capacity_kwh=10.0, max_power_kw=5.0, efficiency=1.0)
+        with pytest.raises(ValueError):
+            estimate_backup_hours(
+                duration_hours=np.ones(2),
+                solar_power_kw=np.zeros(2),
+                load_power_kw=np.ones(2),
+                battery=battery,
+                initial_soc_kwh=3.0,
+                initial_power_kw=0.0,
+                critical_load_fraction=2.0,
+            )
+
+
+# ============================================================================
+# simulate_load_following_channels Tests
+# ============================================================================
+
+
+class TestChannels:
+    """Tests for simulation on named telemetry channels."""
+
+    def test_channels_match_aggregated_columns(self):
+        """Verify several channels simulate like their totals."""
+        battery = Battery(capacity_kwh=10.0, max_power_kw=5.0, efficiency=0.9)
+        duration = np.ones(3)
+
+        soc, power = simulate_load_following_channels(
+            duration_hours=duration,
+            generation_kw={"pv_east": np.array([2.0, 1.0, 0.0]), "wind": np.array([1.0, 0.0, 0.5])},
+            consumption_kw={"critical": np.array([0.5, 1.0, 1.0]), "pool": np.array([0.5, 0.0, 0.5])},
+            battery=battery,
+            initial_soc_kwh=0.0,
+            initial_power_kw=0.0,
+        )
+        expected_soc, expected_power = simulate_load_following(
+            duration_hours=duration,
+            solar_power_kw=np.array([3.0, 1.0, 0.5]),
+            load_power_kw=np.array([1.0, 1.0, 1.5]),
+            battery=battery,
+            initial_soc_kwh=0.0,
+            initial_power_kw=0.0,
+        )
+
+        np.testing.assert_allclose(soc, expected_soc)
+        np.testing.assert_allclose(power, expected_power)
+
+    def test_channel_length_mismatch_raises(self):
+        """Verify a channel of the wrong length raises ValueError."""
+        battery = Battery(capacity_kwh=10.0, max_power_kw=5.0, efficiency=0.9)
+        with pytest.raises(ValueError):
+            simulate_load_following_channels(
+                duration_hours=np.ones(3),
+                generation_kw={"pv": np.ones(2)},
+                consumption_kw={},
+                battery=battery,
+                initial_soc_kwh=0.0,
+                initial_power_kw=0.0,
+            )